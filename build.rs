@@ -0,0 +1,20 @@
+use std::{env, process::Command};
+
+/// Bakes the git SHA into the binary for GET /api/info. Builds without a git
+/// checkout (e.g. in a container) can pass GIT_SHA in the environment instead.
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let sha = env::var("GIT_SHA").ok().or_else(git_sha).unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GIT_SHA={}", sha);
+}
+
+fn git_sha() -> Option<String> {
+    let output = Command::new("git").args(&["rev-parse", "HEAD"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok().map(|sha| sha.trim().to_owned())
+}
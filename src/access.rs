@@ -0,0 +1,425 @@
+use mongodb::bson::oid::ObjectId;
+use rocket::{
+    error,
+    http::{Method, Status},
+    request::{FromRequest, Outcome, Request},
+};
+use thiserror::Error;
+
+use crate::auth::{scoped_user, AuthError, AuthenticatedUser, Scope};
+use crate::repo::{Repo, RepoError};
+use crate::store::Store;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AccessError {
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error("the path has no valid id")]
+    InvalidId,
+    #[error("there's nothing with that id")]
+    NotFound,
+    #[error("it belongs to someone else")]
+    Forbidden,
+    #[error("could not look up who it belongs to: {0}")]
+    Lookup(String),
+}
+
+impl AccessError {
+    pub fn status(&self) -> Status {
+        match self {
+            AccessError::Auth(AuthError::MissingSecret)
+            | AccessError::Auth(AuthError::Lookup(_))
+            | AccessError::Lookup(_) => Status::InternalServerError,
+            AccessError::Auth(AuthError::Disabled)
+            | AccessError::Auth(AuthError::MissingScope) => Status::Forbidden,
+            AccessError::Auth(_) => Status::Unauthorized,
+            AccessError::InvalidId => Status::BadRequest,
+            AccessError::NotFound => Status::NotFound,
+            AccessError::Forbidden => Status::Forbidden,
+        }
+    }
+}
+
+pub fn access_status(err: AccessError) -> Status {
+    err.status()
+}
+
+fn lookup_error(err: RepoError) -> AccessError {
+    error!("{:?}", err);
+    AccessError::Lookup(err.to_string())
+}
+
+/// A 403 unless `user_id`, from a request body or query, is the caller.
+pub fn check_user(user: &AuthenticatedUser, user_id: &ObjectId) -> Result<(), AccessError> {
+    if &user.id != user_id {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(())
+}
+
+/// Lists can be seen and changed by their owner and the users they're shared
+/// with; anyone else gets a 403. Trashed lists count, so they can be restored.
+pub async fn check_list(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    list_id: &ObjectId,
+) -> Result<(), AccessError> {
+    let members = repo.get_list_members(list_id)
+        .await
+        .map_err(lookup_error)?
+        .ok_or(AccessError::NotFound)?;
+    if !members.includes(&user.id) {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(())
+}
+
+/// Only the list's owner can delete, share or hand on the list; members get
+/// a 403. Trashed lists count, so they can be restored or purged.
+pub async fn check_list_owner(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    list_id: &ObjectId,
+) -> Result<(), AccessError> {
+    let members = repo.get_list_members(list_id)
+        .await
+        .map_err(lookup_error)?
+        .ok_or(AccessError::NotFound)?;
+    check_user(user, &members.user_id)
+}
+
+/// Households can be seen and changed by their members; anyone else gets a
+/// 403.
+pub async fn check_household(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    household_id: &ObjectId,
+) -> Result<(), AccessError> {
+    let household = repo.get_household_by_id(household_id)
+        .await
+        .map_err(lookup_error)?
+        .ok_or(AccessError::NotFound)?;
+    if !household.includes(&user.id) {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(())
+}
+
+/// Whether the store is in one of the user's households.
+async fn in_store_household(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    store: &Store,
+) -> Result<bool, AccessError> {
+    let household_id = match &store.household_id {
+        Some(household_id) => household_id,
+        None => return Ok(false),
+    };
+    let household = repo.get_household_by_id(household_id).await.map_err(lookup_error)?;
+    Ok(household.map_or(false, |household| household.includes(&user.id)))
+}
+
+/// The store and whether the caller can change it, if they can see it:
+/// shared stores, their own and their households'.
+async fn store_for(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    store_id: &ObjectId,
+) -> Result<(Store, bool), AccessError> {
+    let store = repo.get_store_by_id(store_id)
+        .await
+        .map_err(lookup_error)?
+        .ok_or(AccessError::NotFound)?;
+    let can_change = store.is_owned_by(&user.id) || in_store_household(repo, user, &store).await?;
+    if !can_change && !store.is_visible_to(&user.id) {
+        return Err(AccessError::Forbidden);
+    }
+    Ok((store, can_change))
+}
+
+/// The store, if the caller can see it: shared stores, their own and their
+/// households'.
+pub async fn visible_store(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    store_id: &ObjectId,
+) -> Result<Store, AccessError> {
+    let (store, _) = store_for(repo, user, store_id).await?;
+    Ok(store)
+}
+
+/// Only the owner and their household can change a store, so shared stores
+/// can't be changed.
+async fn check_store(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    store_id: &ObjectId,
+    method: Method,
+) -> Result<(), AccessError> {
+    let (_, can_change) = store_for(repo, user, store_id).await?;
+    if method != Method::Get && !can_change {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(())
+}
+
+async fn check_template(
+    repo: &Repo,
+    user: &AuthenticatedUser,
+    template_id: &ObjectId,
+) -> Result<(), AccessError> {
+    let template = repo.get_template_by_id(template_id)
+        .await
+        .map_err(lookup_error)?
+        .ok_or(AccessError::NotFound)?;
+    check_user(user, &template.user_id)
+}
+
+async fn caller(request: &Request<'_>) -> Result<AuthenticatedUser, AccessError> {
+    match request.guard::<AuthenticatedUser>().await {
+        Outcome::Success(user) => Ok(user),
+        Outcome::Failure((_, err)) => Err(AccessError::Auth(err)),
+        Outcome::Forward(()) => Err(AccessError::Auth(AuthError::MissingToken)),
+    }
+}
+
+/// List routes that are sent with POST but only read.
+const READ_ONLY_LIST_ROUTES: &[&str] = &["diff_list", "print_lists"];
+
+/// Reading lists takes `lists:read` and changing them `lists:write`.
+fn list_scope(request: &Request<'_>) -> Scope {
+    let read_only_route = request
+        .route()
+        .and_then(|route| route.name.as_deref())
+        .map_or(false, |name| READ_ONLY_LIST_ROUTES.contains(&name));
+    if read_only_route || matches!(request.method(), Method::Get | Method::Head) {
+        Scope::ListsRead
+    } else {
+        Scope::ListsWrite
+    }
+}
+
+/// Like `caller`, but credentials scoped to lists get through too, if their
+/// scopes cover the request.
+async fn list_caller(request: &Request<'_>) -> Result<AuthenticatedUser, AccessError> {
+    let user = scoped_user(request).await.map_err(|(_, err)| AccessError::Auth(err))?;
+    if !user.has_scope(list_scope(request)) {
+        return Err(AccessError::Auth(AuthError::MissingScope));
+    }
+    Ok(user)
+}
+
+fn repo<'r>(request: &'r Request<'_>) -> Result<&'r Repo, AccessError> {
+    request
+        .rocket()
+        .state::<Repo>()
+        .ok_or_else(|| AccessError::Lookup("no repo".to_string()))
+}
+
+/// The id after the collection in the path, as in `/lists/<id>/items`.
+fn path_id(request: &Request<'_>) -> Result<ObjectId, AccessError> {
+    request
+        .param::<&str>(1)
+        .and_then(Result::ok)
+        .and_then(|id| ObjectId::with_string(id).ok())
+        .ok_or(AccessError::InvalidId)
+}
+
+fn outcome<T>(result: Result<T, AccessError>) -> Outcome<T, AccessError> {
+    match result {
+        Ok(access) => Outcome::Success(access),
+        Err(err) => Outcome::Failure((err.status(), err)),
+    }
+}
+
+/// Lets a `/users/<id>/...` route through for that user only. Users can only
+/// get at their own things, so any other `<id>` is a 403.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserAccess;
+
+async fn user_access(request: &Request<'_>) -> Result<UserAccess, AccessError> {
+    let user = caller(request).await?;
+    check_user(&user, &path_id(request)?)?;
+    Ok(UserAccess)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UserAccess {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(user_access(request).await)
+    }
+}
+
+/// A caller who can see and change the list in a `/lists/<id>/...` route; see
+/// `check_list`. Credentials scoped to lists can be used; see `list_scope`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListAccess {
+    pub user: AuthenticatedUser,
+}
+
+async fn list_access(request: &Request<'_>) -> Result<ListAccess, AccessError> {
+    let user = list_caller(request).await?;
+    check_list(repo(request)?, &user, &path_id(request)?).await?;
+    Ok(ListAccess { user })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ListAccess {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(list_access(request).await)
+    }
+}
+
+/// The owner of the list in a `/lists/<id>/...` route; see
+/// `check_list_owner`. Unlike `ListAccess`, scoped credentials can't be used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListOwnerAccess {
+    pub user: AuthenticatedUser,
+}
+
+async fn list_owner_access(request: &Request<'_>) -> Result<ListOwnerAccess, AccessError> {
+    let user = caller(request).await?;
+    check_list_owner(repo(request)?, &user, &path_id(request)?).await?;
+    Ok(ListOwnerAccess { user })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ListOwnerAccess {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(list_owner_access(request).await)
+    }
+}
+
+/// A caller for list routes without a list id in the path, like making a
+/// list. Unlike `AuthenticatedUser`, credentials scoped to lists can be used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListUser {
+    pub user: AuthenticatedUser,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ListUser {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(list_caller(request).await.map(|user| ListUser { user }))
+    }
+}
+
+/// A caller who can use the store in a `/stores/<id>/...` route: anyone
+/// signed in can read a shared store, but only the owner can change a store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreAccess;
+
+async fn store_access(request: &Request<'_>) -> Result<StoreAccess, AccessError> {
+    let user = caller(request).await?;
+    check_store(repo(request)?, &user, &path_id(request)?, request.method()).await?;
+    Ok(StoreAccess)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for StoreAccess {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(store_access(request).await)
+    }
+}
+
+/// A member of the household in a `/households/<id>/...` route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HouseholdAccess;
+
+async fn household_access(request: &Request<'_>) -> Result<HouseholdAccess, AccessError> {
+    let user = caller(request).await?;
+    check_household(repo(request)?, &user, &path_id(request)?).await?;
+    Ok(HouseholdAccess)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for HouseholdAccess {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(household_access(request).await)
+    }
+}
+
+/// The owner of the template in a `/templates/<id>/...` route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateAccess;
+
+async fn template_access(request: &Request<'_>) -> Result<TemplateAccess, AccessError> {
+    let user = caller(request).await?;
+    check_template(repo(request)?, &user, &path_id(request)?).await?;
+    Ok(TemplateAccess)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TemplateAccess {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(template_access(request).await)
+    }
+}
+
+/// A signed in admin, for the `/admin` routes. Anyone else gets a 403.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdminAccess {
+    pub user: AuthenticatedUser,
+}
+
+async fn admin_access(request: &Request<'_>) -> Result<AdminAccess, AccessError> {
+    let user = caller(request).await?;
+    let is_admin = repo(request)?
+        .get_user_by_id(&user.id)
+        .await
+        .map_err(lookup_error)?
+        .map_or(false, |account| account.is_admin());
+    if !is_admin {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(AdminAccess { user })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminAccess {
+    type Error = AccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        outcome(admin_access(request).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn access_errors_map_to_statuses() {
+        assert_eq!(AccessError::Forbidden.status(), Status::Forbidden);
+        assert_eq!(AccessError::NotFound.status(), Status::NotFound);
+        assert_eq!(AccessError::InvalidId.status(), Status::BadRequest);
+        assert_eq!(AccessError::Auth(AuthError::MissingToken).status(), Status::Unauthorized);
+        assert_eq!(AccessError::Auth(AuthError::Disabled).status(), Status::Forbidden);
+        assert_eq!(AccessError::Auth(AuthError::MissingScope).status(), Status::Forbidden);
+        assert_eq!(
+            AccessError::Auth(AuthError::MissingSecret).status(),
+            Status::InternalServerError
+        );
+    }
+
+    #[test]
+    fn only_the_user_passes_the_user_check() {
+        let user = AuthenticatedUser { id: ObjectId::new(), scopes: None };
+        assert_eq!(check_user(&user, &user.id.clone()), Ok(()));
+        assert_eq!(check_user(&user, &ObjectId::new()), Err(AccessError::Forbidden));
+    }
+}
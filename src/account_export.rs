@@ -0,0 +1,131 @@
+use chrono::Utc;
+use futures::stream::{self, BoxStream, StreamExt};
+use mongodb::bson::DateTime;
+use rocket::{Responder, error, http::Header, response::stream::TextStream};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::admin_handlers::Account;
+use crate::api_key::ApiKey;
+use crate::api_key_handlers::ApiKeyInfo;
+use crate::favorite::Favorite;
+use crate::list::List;
+use crate::repo::{Collections, Repo, RepoError};
+use crate::store::Store;
+use crate::template::Template;
+use crate::user::User;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+    #[error("could not write json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("the user has no _id")]
+    MissingId,
+}
+
+/// One array in the archive: each document as JSON, read as it's needed.
+type Section = (&'static str, BoxStream<'static, Result<String, ExportError>>);
+
+fn section<T: Serialize + Send + 'static>(
+    name: &'static str,
+    documents: BoxStream<'static, Result<T, RepoError>>,
+) -> Section {
+    let documents = documents.map(|document| -> Result<String, ExportError> {
+        Ok(serde_json::to_string(&document?)?)
+    });
+    (name, documents.boxed())
+}
+
+/// The archive's JSON, written out a document at a time. If reading fails
+/// partway the archive is cut off, so it won't parse, rather than quietly
+/// leaving data out.
+fn archive_stream(head: String, sections: Vec<Section>) -> BoxStream<'static, String> {
+    let stream = TextStream! {
+        yield head;
+        for (name, mut documents) in sections {
+            yield format!(",\"{}\":[", name);
+            let mut first = true;
+            while let Some(document) = documents.next().await {
+                match document {
+                    Ok(json) if first => {
+                        first = false;
+                        yield json;
+                    }
+                    Ok(json) => {
+                        yield format!(",{}", json);
+                    }
+                    Err(err) => {
+                        error!("Export cut short: {:?}", err);
+                        return;
+                    }
+                }
+            }
+            yield "]".to_string();
+        }
+        yield "}".to_string();
+    };
+    stream.0.boxed()
+}
+
+/// A user's data as a JSON file to download:
+/// `{"exportedAt", "user", "lists", "stores", "favorites", "templates",
+/// "households", "apiKeys"}`.
+#[derive(Responder)]
+#[response(content_type = "json")]
+pub struct AccountArchive {
+    inner: TextStream<BoxStream<'static, String>>,
+    disposition: Header<'static>,
+}
+
+impl AccountArchive {
+    /// Everything stored for the user, without password hashes, reset tokens
+    /// or API key hashes. Lists in the archive and the trash are included, as
+    /// the user's history. Households only list member ids.
+    pub async fn build(repo: &Repo, user: User) -> Result<Self, ExportError> {
+        let user_id = user._id.clone().ok_or(ExportError::MissingId)?;
+        // Left open for the sections to follow.
+        let head = format!(
+            "{{\"exportedAt\":{},\"user\":{}",
+            serde_json::to_string(&DateTime::from(Utc::now()))?,
+            serde_json::to_string(&Account::from(user))?
+        );
+
+        let lists = repo
+            .stream_documents_by_user::<List>(&user_id, &Collections::Lists)
+            .await?;
+        let stores = repo
+            .stream_documents_by_user::<Store>(&user_id, &Collections::Stores)
+            .await?;
+        let favorites = repo
+            .stream_documents_by_user::<Favorite>(&user_id, &Collections::Favorites)
+            .await?;
+        let templates = repo
+            .stream_documents_by_user::<Template>(&user_id, &Collections::Templates)
+            .await?;
+        let households = repo.get_households_by_user(&user_id).await?;
+        let api_keys = repo
+            .stream_documents_by_user::<ApiKey>(&user_id, &Collections::ApiKeys)
+            .await?
+            .map(|api_key| api_key.map(ApiKeyInfo::from))
+            .boxed();
+        let sections = vec![
+            section("lists", lists),
+            section("stores", stores),
+            section("favorites", favorites),
+            section("templates", templates),
+            section("households", stream::iter(households.into_iter().map(Ok)).boxed()),
+            section("apiKeys", api_keys),
+        ];
+
+        let filename = format!("grok-list-export-{}.json", user_id.to_hex());
+        Ok(AccountArchive {
+            inner: TextStream(archive_stream(head, sections)),
+            disposition: Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        })
+    }
+}
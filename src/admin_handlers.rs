@@ -0,0 +1,205 @@
+use chrono::Utc;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::{State, delete, get, http::Status, post, put, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use rocket::{error, info};
+
+use crate::access::AdminAccess;
+use crate::handler_util::{parse_object_id, parse_page};
+use crate::maintenance::MaintenanceSwitch;
+use crate::repo::{Repo, Usage};
+use crate::user::{Role, User as RepoUser};
+
+/// A user as admins see them, with their role and whether they're disabled.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Account {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub email: String,
+    pub role: Role,
+    #[serde(
+        rename(serialize = "disabledAt", deserialize = "disabledAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub disabled_at: Option<DateTime>,
+}
+
+impl From<RepoUser> for Account {
+    fn from(user: RepoUser) -> Self {
+        Account {
+            id: user._id,
+            name: user.name,
+            email: user.email,
+            role: user.role,
+            disabled_at: user.disabled_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MaintenanceState {
+    pub maintenance: bool,
+}
+
+/// Every user, or just those whose name or email contains `q`, ignoring case.
+#[get("/admin/users?<q>&<limit>&<after>")]
+pub async fn search_users(
+    _admin: AdminAccess,
+    q: Option<&str>,
+    limit: Option<i64>,
+    after: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<Account>>, Status> {
+    let page = parse_page(limit, after)?;
+    let users = match q.map(str::trim).filter(|q| !q.is_empty()) {
+        Some(q) => repo.search_users(q, &page).await,
+        None => repo.get_users(&page).await,
+    }
+    .map_err(|err| {
+        error!("{:?}", err);
+        Status::InternalServerError
+    })?;
+
+    Ok(Json(users.into_iter().map(Account::from).collect()))
+}
+
+/// Disabled users can't log in, and their tokens, sessions and API keys stop
+/// working until they're enabled again. Their lists are left alone. Admins
+/// can't disable themselves, so there's always one who can undo it.
+#[post("/admin/users/<id>/disable")]
+pub async fn disable_user(
+    admin: AdminAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Account>, Status> {
+    let id = parse_object_id(id)?;
+    if id == admin.user.id {
+        return Err(Status::BadRequest);
+    }
+    let user = repo.set_user_disabled(&id, Some(DateTime::from(Utc::now())))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(Account::from(user)))
+}
+
+#[post("/admin/users/<id>/enable")]
+pub async fn enable_user(
+    _admin: AdminAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Account>, Status> {
+    let id = parse_object_id(id)?;
+    let user = repo.set_user_disabled(&id, None)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(Account::from(user)))
+}
+
+#[get("/admin/usage")]
+pub async fn get_usage(
+    _admin: AdminAccess,
+    repo: &State<Repo>,
+) -> Result<Json<Usage>, Status> {
+    let usage = repo.get_usage()
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(usage))
+}
+
+/// Deletes anyone's list outright, skipping the trash, for abusive content.
+#[delete("/admin/lists/<id>")]
+pub async fn remove_list(
+    _admin: AdminAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let lists_deleted = repo.delete_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if lists_deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
+
+/// Deletes anyone's store, shared stores included.
+#[delete("/admin/stores/<id>")]
+pub async fn remove_store(
+    _admin: AdminAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let stores_deleted = repo.delete_store_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if stores_deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
+
+#[delete("/admin/templates/<id>")]
+pub async fn remove_template(
+    _admin: AdminAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let templates_deleted = repo.delete_template_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if templates_deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
+
+#[get("/admin/maintenance")]
+pub fn get_maintenance(
+    _admin: AdminAccess,
+    switch: &State<MaintenanceSwitch>,
+) -> Json<MaintenanceState> {
+    Json(MaintenanceState { maintenance: switch.is_on() })
+}
+
+/// Turns maintenance mode on or off right away, without a restart.
+#[put("/admin/maintenance", data="<state>")]
+pub fn set_maintenance(
+    _admin: AdminAccess,
+    state: Json<MaintenanceState>,
+    switch: &State<MaintenanceSwitch>,
+) -> Json<MaintenanceState> {
+    switch.set(state.maintenance);
+    info!("Maintenance mode turned {}", if state.maintenance { "on" } else { "off" });
+    Json(MaintenanceState { maintenance: switch.is_on() })
+}
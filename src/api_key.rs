@@ -0,0 +1,82 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::Scope;
+
+const KEY_PREFIX: &str = "grok_";
+const KEY_LEN: usize = 32;
+/// How much of a key is kept in the clear so users can tell their keys apart.
+const HINT_LEN: usize = KEY_PREFIX.len() + 4;
+
+/// A key scripts can send as `X-Api-Key` instead of logging in. Only a hash
+/// of the key is stored; the key itself is shown once, when it's made.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiKey {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    #[serde(rename(serialize = "userId", deserialize = "userId"))]
+    pub user_id: ObjectId,
+    pub name: String,
+    /// The start of the key, like `grok_a1B2`.
+    pub hint: String,
+    #[serde(rename(serialize = "keyHash", deserialize = "keyHash"))]
+    pub key_hash: String,
+    #[serde(rename(serialize = "createdAt", deserialize = "createdAt"))]
+    pub created_at: DateTime,
+    /// What the key is limited to, or `None` for full access.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl ApiKey {
+    /// A new random key for the user, and the record to store for it.
+    pub fn generate(user_id: ObjectId, name: &str, created_at: DateTime) -> (String, ApiKey) {
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(KEY_LEN)
+            .map(char::from)
+            .collect();
+        let key = format!("{}{}", KEY_PREFIX, random);
+        let api_key = ApiKey {
+            _id: None,
+            user_id,
+            name: name.trim().to_owned(),
+            hint: key[..HINT_LEN].to_owned(),
+            key_hash: hash_key(&key),
+            created_at,
+            scopes: None,
+        };
+        (key, api_key)
+    }
+}
+
+/// Keys are long and random, so a plain SHA-256 is enough to store them and
+/// lets them be looked up by hash.
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.trim().as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn only_the_hash_and_a_hint_are_kept() {
+        let user_id = ObjectId::new();
+        let now = DateTime::from(Utc::now());
+        let (key, api_key) = ApiKey::generate(user_id.clone(), " scripts ", now);
+
+        assert!(key.starts_with("grok_"));
+        assert_eq!(key.len(), KEY_PREFIX.len() + KEY_LEN);
+        assert_eq!(api_key.user_id, user_id);
+        assert_eq!(api_key.name, "scripts");
+        assert!(key.starts_with(&api_key.hint));
+        assert_eq!(api_key.key_hash, hash_key(&key));
+        assert_ne!(api_key.key_hash, key);
+        assert_eq!(api_key.key_hash.len(), 64);
+        assert_eq!(api_key.scopes, None);
+    }
+}
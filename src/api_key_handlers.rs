@@ -0,0 +1,121 @@
+use chrono::Utc;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::{State, delete, get, http::Status, post, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use rocket::error;
+
+use crate::api_key::ApiKey;
+use crate::auth::{AuthenticatedUser, Scope};
+use crate::handler_util::parse_object_id;
+use crate::repo::Repo;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NewApiKey {
+    pub name: String,
+    /// Leave out for a key with full access.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// An API key without its hash.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiKeyInfo {
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub hint: String,
+    #[serde(rename(serialize = "createdAt", deserialize = "createdAt"))]
+    pub created_at: DateTime,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl From<ApiKey> for ApiKeyInfo {
+    fn from(api_key: ApiKey) -> Self {
+        ApiKeyInfo {
+            id: api_key._id,
+            name: api_key.name,
+            hint: api_key.hint,
+            created_at: api_key.created_at,
+            scopes: api_key.scopes,
+        }
+    }
+}
+
+/// A newly made key. This is the only time `key` is shown.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+    pub key: String,
+}
+
+/// A key with `scopes` can only do what they allow, like reading lists on a
+/// kitchen display. Making keys takes full access, so scoped keys can't make
+/// keys with more.
+#[post("/auth/api-keys", data="<api_key>")]
+pub async fn create_api_key(
+    auth: AuthenticatedUser,
+    api_key: Json<NewApiKey>,
+    repo: &State<Repo>,
+) -> Result<Json<CreatedApiKey>, Status> {
+    let no_scopes = api_key.scopes.as_ref().map_or(false, Vec::is_empty);
+    if api_key.name.trim().is_empty() || no_scopes {
+        return Err(Status::BadRequest);
+    }
+
+    let scopes = api_key.scopes.clone();
+    let (key, mut api_key) =
+        ApiKey::generate(auth.id, &api_key.name, DateTime::from(Utc::now()));
+    api_key.scopes = scopes;
+    let api_key = repo.add_api_key(&api_key)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new API key returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(CreatedApiKey { info: ApiKeyInfo::from(api_key), key }))
+}
+
+#[get("/auth/api-keys")]
+pub async fn get_api_keys(
+    auth: AuthenticatedUser,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<ApiKeyInfo>>, Status> {
+    let api_keys = repo.get_api_keys_by_user(&auth.id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .into_iter()
+        .map(ApiKeyInfo::from)
+        .collect();
+
+    Ok(Json(api_keys))
+}
+
+/// Revoked keys stop working straight away.
+#[delete("/auth/api-keys/<id>")]
+pub async fn revoke_api_key(
+    auth: AuthenticatedUser,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let deleted = repo.delete_api_key(&auth.id, &id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
@@ -0,0 +1,505 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use mongodb::bson::oid::ObjectId;
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use rocket::{
+    error,
+    http::{Cookie, CookieJar, Status},
+    request::{FromRequest, Outcome, Request},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api_key::hash_key;
+use crate::repo::Repo;
+
+/// Shorter passwords are rejected at registration.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// The private cookie holding a logged in user's session.
+pub const SESSION_COOKIE: &str = "session";
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+const RESET_TOKEN_LEN: usize = 32;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AuthError {
+    #[error("passwords must be at least {} characters", MIN_PASSWORD_LEN)]
+    PasswordTooShort,
+    #[error("could not hash password: {0}")]
+    Hash(String),
+    #[error("no jwt_secret is configured")]
+    MissingSecret,
+    #[error("no bearer token was sent")]
+    MissingToken,
+    #[error("the token is invalid: {0}")]
+    InvalidToken(String),
+    #[error("no current session cookie was sent")]
+    MissingSession,
+    #[error("the API key is unknown or revoked")]
+    InvalidApiKey,
+    #[error("could not look up credentials: {0}")]
+    Lookup(String),
+    #[error("the credentials were revoked")]
+    Revoked,
+    #[error("the account is disabled")]
+    Disabled,
+    #[error("the credentials' scopes don't allow this")]
+    MissingScope,
+}
+
+/// Hashes the password with argon2 and a random salt, in PHC string format
+/// so the parameters are stored alongside the hash.
+pub fn hash_password(password: &str) -> Result<String, AuthError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(AuthError::PasswordTooShort);
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password_simple(password.as_bytes(), salt.as_ref())
+        .map(|hash| hash.to_string())
+        .map_err(|err| AuthError::Hash(err.to_string()))
+}
+
+/// Whether `password` is the one `hash` was made from. A hash that can't be
+/// parsed never matches.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+/// A random token to mail for a password reset.
+pub fn new_reset_token() -> String {
+    OsRng.sample_iter(&Alphanumeric).take(RESET_TOKEN_LEN).map(char::from).collect()
+}
+
+/// Emails are matched ignoring case and surrounding whitespace.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+
+/// How clients prove who they are: a bearer token from logging in, or the
+/// session cookie logging in sets, for the web frontend.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    Jwt,
+    Session,
+}
+
+impl Default for AuthMode {
+    fn default() -> Self {
+        AuthMode::Jwt
+    }
+}
+
+/// `jwt_secret` signs login tokens and has to be set outside of tests and
+/// debug builds. Tokens and sessions last `token_ttl_secs`, and password
+/// reset tokens `password_reset_ttl_secs`.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub auth_mode: AuthMode,
+    #[serde(default)]
+    pub jwt_secret: String,
+    #[serde(default)]
+    pub token_ttl_secs: i64,
+    #[serde(default)]
+    pub password_reset_ttl_secs: i64,
+}
+
+impl AuthConfig {
+    /// When a token or session started at `now` runs out.
+    pub fn expires_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now + Duration::seconds(self.token_ttl_secs)
+    }
+}
+
+/// What scoped credentials are allowed to do. `lists:write` includes
+/// `lists:read`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    #[serde(rename = "lists:read")]
+    ListsRead,
+    #[serde(rename = "lists:write")]
+    ListsWrite,
+}
+
+impl Scope {
+    pub fn includes(self, scope: Scope) -> bool {
+        self == scope || (self == Scope::ListsWrite && scope == Scope::ListsRead)
+    }
+}
+
+/// What a login token says: who it's for (`sub`, the user id), when it was
+/// issued and expires, in seconds since the epoch, and the user's
+/// credentials version (`ver`) at the time. Scoped tokens list their scopes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Claims {
+    pub sub: String,
+    pub iat: i64,
+    pub exp: i64,
+    #[serde(default)]
+    pub ver: i64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// A signed token for the user, good for `token_ttl_secs` from `now` or until
+/// their credentials version changes.
+pub fn issue_token(
+    config: &AuthConfig,
+    user_id: &ObjectId,
+    credentials_version: i64,
+    now: DateTime<Utc>,
+) -> Result<(String, DateTime<Utc>), AuthError> {
+    issue_scoped_token(config, user_id, credentials_version, None, now)
+}
+
+/// Like `issue_token`, but the token can only do what `scopes` allow. `None`
+/// is a token with full access.
+pub fn issue_scoped_token(
+    config: &AuthConfig,
+    user_id: &ObjectId,
+    credentials_version: i64,
+    scopes: Option<&[Scope]>,
+    now: DateTime<Utc>,
+) -> Result<(String, DateTime<Utc>), AuthError> {
+    if config.jwt_secret.is_empty() {
+        return Err(AuthError::MissingSecret);
+    }
+    let expires_at = config.expires_at(now);
+    let claims = Claims {
+        sub: user_id.to_hex(),
+        iat: now.timestamp(),
+        exp: expires_at.timestamp(),
+        ver: credentials_version,
+        scopes: scopes.map(<[Scope]>::to_vec),
+    };
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+    )
+    .map_err(|err| AuthError::InvalidToken(err.to_string()))?;
+
+    Ok((token, expires_at))
+}
+
+/// The token's claims, if it was signed with our secret and hasn't expired.
+pub fn verify_token(config: &AuthConfig, token: &str) -> Result<Claims, AuthError> {
+    if config.jwt_secret.is_empty() {
+        return Err(AuthError::MissingSecret);
+    }
+    decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|err| AuthError::InvalidToken(err.to_string()))
+}
+
+/// Starts a session for the user by setting the private session cookie. The
+/// cookie is encrypted, so its value is only checked for expiry when read.
+pub fn start_session(
+    cookies: &CookieJar<'_>,
+    user_id: &ObjectId,
+    credentials_version: i64,
+    expires_at: DateTime<Utc>,
+) {
+    let value = session_value(user_id, credentials_version, expires_at);
+    cookies.add_private(Cookie::new(SESSION_COOKIE, value));
+}
+
+fn session_value(
+    user_id: &ObjectId,
+    credentials_version: i64,
+    expires_at: DateTime<Utc>,
+) -> String {
+    format!("{}:{}:{}", user_id.to_hex(), credentials_version, expires_at.timestamp())
+}
+
+pub fn end_session(cookies: &CookieJar<'_>) {
+    cookies.remove_private(Cookie::named(SESSION_COOKIE));
+}
+
+/// Who a session cookie's value belongs to and their credentials version
+/// when it started, unless it's run out.
+fn session_user(value: &str, now: DateTime<Utc>) -> Option<(ObjectId, i64)> {
+    let mut parts = value.splitn(3, ':');
+    let id = ObjectId::with_string(parts.next()?).ok()?;
+    let credentials_version: i64 = parts.next()?.parse().ok()?;
+    let expires_at: i64 = parts.next()?.parse().ok()?;
+    if expires_at <= now.timestamp() {
+        return None;
+    }
+    Some((id, credentials_version))
+}
+
+/// The user making the request: who the `Authorization: Bearer` token was
+/// issued to, or whose session cookie was sent, depending on `auth_mode`.
+/// A request with an `X-Api-Key` is the key's owner instead, in either mode.
+/// Handlers that take one reject anyone else with a 401, and credentials
+/// limited to some scopes with a 403; see `scoped_user`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatedUser {
+    pub id: ObjectId,
+    /// What the credentials are limited to, or `None` for full access.
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .map_or(true, |scopes| scopes.iter().any(|granted| granted.includes(scope)))
+    }
+}
+
+fn bearer_token<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+fn repo<'r>(request: &'r Request<'_>) -> Result<&'r Repo, (Status, AuthError)> {
+    request
+        .rocket()
+        .state::<Repo>()
+        .ok_or((Status::InternalServerError, AuthError::Lookup("no repo".to_string())))
+}
+
+/// The user id, credentials version and scopes from the session cookie or
+/// bearer token. Sessions always have full access.
+fn credentials(
+    request: &Request<'_>,
+) -> Result<(ObjectId, i64, Option<Vec<Scope>>), (Status, AuthError)> {
+    let config = request
+        .rocket()
+        .state::<AuthConfig>()
+        .ok_or((Status::InternalServerError, AuthError::MissingSecret))?;
+    if config.auth_mode == AuthMode::Session {
+        return request
+            .cookies()
+            .get_private(SESSION_COOKIE)
+            .and_then(|cookie| session_user(cookie.value(), Utc::now()))
+            .map(|(id, credentials_version)| (id, credentials_version, None))
+            .ok_or((Status::Unauthorized, AuthError::MissingSession));
+    }
+    let token = bearer_token(request).ok_or((Status::Unauthorized, AuthError::MissingToken))?;
+    let claims = verify_token(config, token).map_err(|err| match err {
+        AuthError::MissingSecret => (Status::InternalServerError, err),
+        err => (Status::Unauthorized, err),
+    })?;
+    let id = ObjectId::with_string(&claims.sub)
+        .map_err(|err| (Status::Unauthorized, AuthError::InvalidToken(err.to_string())))?;
+
+    Ok((id, claims.ver, claims.scopes))
+}
+
+/// Credentials only count while their user exists and hasn't changed
+/// password since they were issued. Disabled users get a 403 instead.
+async fn authenticate(request: &Request<'_>) -> Result<AuthenticatedUser, (Status, AuthError)> {
+    let (id, credentials_version, scopes) = credentials(request)?;
+    let user = repo(request)?
+        .get_user_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            (Status::InternalServerError, AuthError::Lookup(err.to_string()))
+        })?
+        .ok_or((Status::Unauthorized, AuthError::Revoked))?;
+    if credentials_version != user.credentials_version {
+        return Err((Status::Unauthorized, AuthError::Revoked));
+    }
+    if user.is_disabled() {
+        return Err((Status::Forbidden, AuthError::Disabled));
+    }
+
+    Ok(AuthenticatedUser { id, scopes })
+}
+
+/// The user making the request, whatever their credentials are limited to.
+/// Guards for routes that scoped credentials can use check the scopes with
+/// `AuthenticatedUser::has_scope` themselves.
+pub async fn scoped_user(request: &Request<'_>) -> Result<AuthenticatedUser, (Status, AuthError)> {
+    if request.headers().contains(API_KEY_HEADER) {
+        let key_user = authenticate_api_key(request).await?;
+        return Ok(AuthenticatedUser { id: key_user.user_id, scopes: key_user.scopes });
+    }
+    authenticate(request).await
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match scoped_user(request).await {
+            Ok(user) if user.scopes.is_some() => {
+                Outcome::Failure((Status::Forbidden, AuthError::MissingScope))
+            }
+            Ok(user) => Outcome::Success(user),
+            Err(failure) => Outcome::Failure(failure),
+        }
+    }
+}
+
+/// The owner of the API key sent as `X-Api-Key`, for routes only scripts
+/// call. Requests without a current key are a 401, and keys of disabled users
+/// are a 403.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKeyUser {
+    pub user_id: ObjectId,
+    pub scopes: Option<Vec<Scope>>,
+}
+
+async fn authenticate_api_key(request: &Request<'_>) -> Result<ApiKeyUser, (Status, AuthError)> {
+    let key = request
+        .headers()
+        .get_one(API_KEY_HEADER)
+        .ok_or((Status::Unauthorized, AuthError::InvalidApiKey))?;
+    let api_key = repo(request)?
+        .get_api_key_by_hash(&hash_key(key))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            (Status::InternalServerError, AuthError::Lookup(err.to_string()))
+        })?
+        .ok_or((Status::Unauthorized, AuthError::InvalidApiKey))?;
+    let owner = repo(request)?
+        .get_user_by_id(&api_key.user_id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            (Status::InternalServerError, AuthError::Lookup(err.to_string()))
+        })?
+        .ok_or((Status::Unauthorized, AuthError::InvalidApiKey))?;
+    if owner.is_disabled() {
+        return Err((Status::Forbidden, AuthError::Disabled));
+    }
+
+    Ok(ApiKeyUser { user_id: api_key.user_id, scopes: api_key.scopes })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKeyUser {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match authenticate_api_key(request).await {
+            Ok(key_user) => Outcome::Success(key_user),
+            Err(failure) => Outcome::Failure(failure),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passwords_verify_against_their_hash() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("battery staple", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+    }
+
+    #[test]
+    fn hashes_are_salted() {
+        assert_ne!(hash_password("correct horse"), hash_password("correct horse"));
+    }
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            auth_mode: AuthMode::Jwt,
+            jwt_secret: "secret".to_string(),
+            token_ttl_secs: 60,
+            password_reset_ttl_secs: 60,
+        }
+    }
+
+    #[test]
+    fn tokens_verify_until_they_expire() {
+        let user_id = ObjectId::new();
+        let (token, expires_at) = issue_token(&config(), &user_id, 3, Utc::now()).unwrap();
+        let claims = verify_token(&config(), &token).unwrap();
+        assert_eq!(claims.sub, user_id.to_hex());
+        assert_eq!(claims.exp, expires_at.timestamp());
+        assert_eq!(claims.ver, 3);
+
+        let other = AuthConfig { jwt_secret: "other".to_string(), ..config() };
+        assert!(matches!(verify_token(&other, &token), Err(AuthError::InvalidToken(..))));
+
+        let (expired, _) =
+            issue_token(&config(), &user_id, 0, Utc::now() - Duration::hours(1)).unwrap();
+        assert!(matches!(verify_token(&config(), &expired), Err(AuthError::InvalidToken(..))));
+    }
+
+    #[test]
+    fn scoped_tokens_carry_their_scopes() {
+        let user_id = ObjectId::new();
+        let scopes = [Scope::ListsRead];
+        let (token, _) =
+            issue_scoped_token(&config(), &user_id, 0, Some(&scopes), Utc::now()).unwrap();
+        assert_eq!(verify_token(&config(), &token).unwrap().scopes, Some(vec![Scope::ListsRead]));
+
+        let (token, _) = issue_token(&config(), &user_id, 0, Utc::now()).unwrap();
+        assert_eq!(verify_token(&config(), &token).unwrap().scopes, None);
+    }
+
+    #[test]
+    fn scopes_limit_what_users_can_do() {
+        let reader = AuthenticatedUser {
+            id: ObjectId::new(),
+            scopes: Some(vec![Scope::ListsRead]),
+        };
+        assert!(reader.has_scope(Scope::ListsRead));
+        assert!(!reader.has_scope(Scope::ListsWrite));
+
+        let writer = AuthenticatedUser { scopes: Some(vec![Scope::ListsWrite]), ..reader.clone() };
+        assert!(writer.has_scope(Scope::ListsRead));
+        assert!(writer.has_scope(Scope::ListsWrite));
+
+        let full = AuthenticatedUser { scopes: None, ..reader };
+        assert!(full.has_scope(Scope::ListsWrite));
+        let nothing = AuthenticatedUser { scopes: Some(Vec::new()), ..full };
+        assert!(!nothing.has_scope(Scope::ListsRead));
+    }
+
+    #[test]
+    fn tokens_need_a_secret() {
+        let config = AuthConfig::default();
+        assert_eq!(
+            issue_token(&config, &ObjectId::new(), 0, Utc::now()),
+            Err(AuthError::MissingSecret)
+        );
+    }
+
+    #[test]
+    fn sessions_name_their_user_until_they_expire() {
+        let user_id = ObjectId::new();
+        let now = Utc::now();
+        let value = session_value(&user_id, 2, config().expires_at(now));
+        assert_eq!(session_user(&value, now), Some((user_id, 2)));
+        assert_eq!(session_user(&value, now + Duration::minutes(2)), None);
+        assert_eq!(session_user("garbage", now), None);
+    }
+
+    #[test]
+    fn short_passwords_are_rejected() {
+        assert_eq!(hash_password("hunter2"), Err(AuthError::PasswordTooShort));
+    }
+}
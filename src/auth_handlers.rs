@@ -0,0 +1,365 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use mongodb::bson::DateTime;
+use rocket::{State, delete, get, http::{CookieJar, Status}, post, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use rocket::error;
+
+use crate::api_key::hash_key;
+use crate::auth::{
+    end_session, hash_password, issue_scoped_token, issue_token, new_reset_token,
+    normalize_email, start_session, verify_password, AuthConfig, AuthError, AuthMode,
+    AuthenticatedUser, Scope,
+};
+use crate::handler_util::repo_error_status;
+use crate::mailer::{Mail, Mailer};
+use crate::provisioning::{provision_user, SignupConfig};
+use crate::repo::{AccountDeletion, DeletionMode, Repo};
+use crate::user::{PasswordReset, User as RepoUser};
+use crate::user_handlers::User;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Registration {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResetRequest {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResetConfirmation {
+    pub token: String,
+    pub password: String,
+}
+
+/// Send `token` back as `Authorization: Bearer <token>` until `expiresAt`. In
+/// session mode there's no token; the session cookie lasts until `expiresAt`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoginResponse {
+    pub user: User,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub token: Option<String>,
+    #[serde(rename(serialize = "expiresAt", deserialize = "expiresAt"))]
+    pub expires_at: DateTime,
+}
+
+/// `password` is only needed for accounts that have one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeletionRequest {
+    pub mode: DeletionMode,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenRequest {
+    pub scopes: Vec<Scope>,
+}
+
+/// A bearer token that can only do what `scopes` allow, until `expiresAt`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScopedToken {
+    pub token: String,
+    pub scopes: Vec<Scope>,
+    #[serde(rename(serialize = "expiresAt", deserialize = "expiresAt"))]
+    pub expires_at: DateTime,
+}
+
+/// Makes an account that can log in. An email that's already registered is a
+/// 409 and a password shorter than `MIN_PASSWORD_LEN` is a 400.
+#[post("/auth/register", data="<registration>")]
+pub async fn register(
+    registration: Json<Registration>,
+    repo: &State<Repo>,
+    signup: &State<SignupConfig>,
+) -> Result<Json<User>, Status> {
+    let email = normalize_email(&registration.email);
+    if registration.name.trim().is_empty() || email.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let existing = repo.get_user_by_email(&email)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if existing.is_some() {
+        return Err(Status::Conflict);
+    }
+
+    let password_hash = hash_password(&registration.password).map_err(|err| match err {
+        AuthError::PasswordTooShort => Status::BadRequest,
+        err => {
+            error!("{:?}", err);
+            Status::InternalServerError
+        }
+    })?;
+    let new_user = repo.add_user(&RepoUser::with_password(
+        registration.name.trim().to_owned(),
+        email,
+        password_hash))
+        .await
+        .map_err(repo_error_status)?
+        .ok_or_else(|| {
+            error!("No new user returned");
+            Status::InternalServerError
+        })?;
+
+    if let Some(user_id) = &new_user._id {
+        if let Err(err) = provision_user(repo, signup, user_id).await {
+            error!("Could not provision new user {:?}: {:?}", user_id, err);
+        }
+    }
+
+    Ok(Json(User::new(new_user._id, new_user.name, new_user.email)))
+}
+
+/// An unknown email and a wrong password are both a 401, so logins can't be
+/// used to find out who has an account. A disabled account is a 403.
+#[post("/auth/login", data="<credentials>")]
+pub async fn login(
+    credentials: Json<Credentials>,
+    cookies: &CookieJar<'_>,
+    repo: &State<Repo>,
+    config: &State<AuthConfig>,
+) -> Result<Json<LoginResponse>, Status> {
+    let user = repo.get_user_by_email(&normalize_email(&credentials.email))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::Unauthorized)?;
+    let verified = user
+        .password_hash
+        .as_deref()
+        .map_or(false, |hash| verify_password(&credentials.password, hash));
+    if !verified {
+        return Err(Status::Unauthorized);
+    }
+    if user.is_disabled() {
+        return Err(Status::Forbidden);
+    }
+
+    let user_id = user._id.clone().ok_or_else(|| {
+        error!("User without an _id: {:?}", user.email);
+        Status::InternalServerError
+    })?;
+    let (token, expires_at) = match config.auth_mode {
+        AuthMode::Jwt => {
+            let issued = issue_token(config, &user_id, user.credentials_version, Utc::now());
+            let (token, expires_at) = issued.map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?;
+            (Some(token), expires_at)
+        }
+        AuthMode::Session => {
+            let expires_at = config.expires_at(Utc::now());
+            start_session(cookies, &user_id, user.credentials_version, expires_at);
+            (None, expires_at)
+        }
+    };
+
+    Ok(Json(LoginResponse {
+        user: User::new(user._id, user.name, user.email),
+        token,
+        expires_at: DateTime::from(expires_at),
+    }))
+}
+
+/// Ends the session, if there is one. Bearer tokens can't be taken back, so
+/// clients using them just forget theirs.
+#[post("/auth/logout")]
+pub fn logout(cookies: &CookieJar<'_>) -> Status {
+    end_session(cookies);
+    Status::NoContent
+}
+
+/// Whoever the bearer token or session belongs to.
+#[get("/auth/me")]
+pub async fn get_current_user(
+    auth: AuthenticatedUser,
+    repo: &State<Repo>,
+) -> Result<Json<User>, Status> {
+    let user = repo.get_user_by_id(&auth.id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::Unauthorized)?;
+
+    Ok(Json(User::new(user._id, user.name, user.email)))
+}
+
+/// Issues a token limited to `scopes`, say for a device that only shows a
+/// list. It takes full access to ask for one, and bearer tokens are only
+/// issued in jwt mode, so in session mode this is a 400; use a scoped API key
+/// instead. No scopes is a 400 too.
+#[post("/auth/tokens", data="<request>")]
+pub async fn create_scoped_token(
+    auth: AuthenticatedUser,
+    request: Json<TokenRequest>,
+    repo: &State<Repo>,
+    config: &State<AuthConfig>,
+) -> Result<Json<ScopedToken>, Status> {
+    if config.auth_mode != AuthMode::Jwt || request.scopes.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let user = repo.get_user_by_id(&auth.id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::Unauthorized)?;
+
+    let scopes = request.into_inner().scopes;
+    let issued = issue_scoped_token(
+        config,
+        &auth.id,
+        user.credentials_version,
+        Some(&scopes),
+        Utc::now(),
+    );
+    let (token, expires_at) = issued.map_err(|err| {
+        error!("{:?}", err);
+        Status::InternalServerError
+    })?;
+
+    Ok(Json(ScopedToken {
+        token,
+        scopes,
+        expires_at: DateTime::from(expires_at),
+    }))
+}
+
+/// Mails a token for `confirm_password_reset` to the account's email. The
+/// response is a 202 whether or not there's an account, so this can't be used
+/// to find out who has one.
+#[post("/auth/password-reset", data="<request>")]
+pub async fn request_password_reset(
+    request: Json<ResetRequest>,
+    repo: &State<Repo>,
+    config: &State<AuthConfig>,
+    mailer: &State<Arc<dyn Mailer>>,
+) -> Result<Status, Status> {
+    let user = repo.get_user_by_email(&normalize_email(&request.email))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    let (user_id, email) = match user {
+        Some(RepoUser { _id: Some(id), email, .. }) => (id, email),
+        _ => return Ok(Status::Accepted),
+    };
+
+    let token = new_reset_token();
+    let ttl = Duration::seconds(config.password_reset_ttl_secs);
+    let reset = PasswordReset {
+        token_hash: hash_key(&token),
+        expires_at: DateTime::from(Utc::now() + ttl),
+    };
+    repo.set_password_reset(&user_id, &reset)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    let mail = Mail {
+        to: email,
+        subject: "Reset your Grok List password".to_string(),
+        body: format!(
+            "Use this code to choose a new password: {}\n\nIt works once, for the next {} minutes. \
+             If you didn't ask to reset your password you can ignore this email.",
+            token,
+            ttl.num_minutes()
+        ),
+    };
+    mailer.send(mail).await.map_err(|err| {
+        error!("{:?}", err);
+        Status::InternalServerError
+    })?;
+
+    Ok(Status::Accepted)
+}
+
+/// Sets a new password with a token from `request_password_reset`. Tokens,
+/// sessions and API keys from before the reset stop working. An
+/// unknown, used or expired token is a 400, as is a short password.
+#[post("/auth/password-reset/confirm", data="<confirmation>")]
+pub async fn confirm_password_reset(
+    confirmation: Json<ResetConfirmation>,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let password_hash = hash_password(&confirmation.password).map_err(|err| match err {
+        AuthError::PasswordTooShort => Status::BadRequest,
+        err => {
+            error!("{:?}", err);
+            Status::InternalServerError
+        }
+    })?;
+    let now = DateTime::from(Utc::now());
+    let reset = repo.reset_password(&hash_key(&confirmation.token), &password_hash, now)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if !reset {
+        return Err(Status::BadRequest);
+    }
+
+    Ok(Status::NoContent)
+}
+
+/// Closes the caller's own account and ends their session. `delete` removes
+/// everything they made; `anonymize` strips their name and email but leaves
+/// lists and stores they share for the people they share them with. Accounts
+/// with a password have to give it again, or it's a 401.
+#[delete("/auth/me", data="<deletion>")]
+pub async fn delete_account(
+    auth: AuthenticatedUser,
+    deletion: Json<DeletionRequest>,
+    cookies: &CookieJar<'_>,
+    repo: &State<Repo>,
+) -> Result<Json<AccountDeletion>, Status> {
+    let user = repo.get_user_by_id(&auth.id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::Unauthorized)?;
+    if let Some(hash) = user.password_hash.as_deref() {
+        let password = deletion.password.as_deref().unwrap_or_default();
+        if !verify_password(password, hash) {
+            return Err(Status::Unauthorized);
+        }
+    }
+
+    let deleted = repo.delete_account(&auth.id, deletion.mode, DateTime::from(Utc::now()))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::Unauthorized)?;
+    end_session(cookies);
+
+    Ok(Json(deleted))
+}
@@ -0,0 +1,130 @@
+use std::convert::Infallible;
+
+use chrono::{DateTime, Utc};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Method},
+    request::{FromRequest, Outcome},
+    Request, Response,
+};
+
+/// Responses are per user and change often, so browsers and proxies may keep
+/// them but have to check they're still current before using them.
+const CACHE_CONTROL: &str = "private, no-cache";
+
+/// Formats a time the way HTTP headers like `Last-Modified` want it.
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+pub fn last_modified(time: DateTime<Utc>) -> Header<'static> {
+    Header::new("Last-Modified", http_date(time))
+}
+
+/// The `If-Modified-Since` header, if the client sent one we could read.
+/// Unreadable dates are ignored, as if there were no header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfModifiedSince(pub Option<DateTime<Utc>>);
+
+impl IfModifiedSince {
+    /// Whether a document last changed at `modified` is still what the client
+    /// has. HTTP dates only go down to the second, so neither does this.
+    pub fn is_unchanged(&self, modified: DateTime<Utc>) -> bool {
+        self.0.map_or(false, |since| modified.timestamp() <= since.timestamp())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfModifiedSince {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let since = request.headers().get_one("If-Modified-Since").and_then(parse_http_date);
+        Outcome::Success(IfModifiedSince(since))
+    }
+}
+
+/// The raw `If-None-Match` header, if the client sent one. When it's there,
+/// `If-Modified-Since` is ignored, as HTTP asks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfNoneMatch(pub Option<String>);
+
+impl IfNoneMatch {
+    /// Whether the client already has the version tagged `etag`. Weak tags
+    /// match too, since a 304 only needs the tags to be equivalent.
+    pub fn matches(&self, etag: &str) -> bool {
+        self.0.as_deref().map_or(false, |tags| {
+            tags.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let tags = request.headers().get_one("If-None-Match").map(str::to_owned);
+        Outcome::Success(IfNoneMatch(tags))
+    }
+}
+
+/// Adds `Cache-Control` to GET responses that don't set their own.
+pub struct CacheControl;
+
+#[rocket::async_trait]
+impl Fairing for CacheControl {
+    fn info(&self) -> Info {
+        Info {
+            name: "Cache-Control",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if req.method() == Method::Get && !res.headers().contains("Cache-Control") {
+            res.set_raw_header("Cache-Control", CACHE_CONTROL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn http_dates_round_trip() {
+        let time = Utc.ymd(2021, 5, 2).and_hms(13, 4, 5);
+        assert_eq!(http_date(time), "Sun, 02 May 2021 13:04:05 GMT");
+        assert_eq!(parse_http_date("Sun, 02 May 2021 13:04:05 GMT"), Some(time));
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn changes_within_the_same_second_are_unchanged() {
+        let since = Utc.ymd(2021, 5, 2).and_hms(13, 4, 5);
+        let header = IfModifiedSince(Some(since));
+        assert!(header.is_unchanged(Utc.ymd(2021, 5, 2).and_hms_milli(13, 4, 5, 500)));
+        assert!(!header.is_unchanged(Utc.ymd(2021, 5, 2).and_hms(13, 4, 6)));
+        assert!(!IfModifiedSince(None).is_unchanged(since));
+    }
+
+    #[test]
+    fn none_match_compares_entity_tags() {
+        let header = IfNoneMatch(Some("\"3\", W/\"4\"".to_string()));
+        assert!(header.matches("\"3\""));
+        assert!(header.matches("\"4\""));
+        assert!(!header.matches("\"5\""));
+        assert!(IfNoneMatch(Some("*".to_string())).matches("\"5\""));
+        assert!(!IfNoneMatch(None).matches("\"5\""));
+    }
+}
@@ -0,0 +1,240 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Method, Status},
+    Request, Response,
+};
+use serde::Deserialize;
+
+const REQUEST_METHOD: &str = "Access-Control-Request-Method";
+const REQUEST_HEADERS: &str = "Access-Control-Request-Headers";
+
+/// Which browser origins can call the API, and with what. No origins, the
+/// default, turns CORS off; `"*"` lets any origin in, but only origins listed
+/// by name get credentials.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CorsConfig {
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
+    #[serde(default = "default_methods")]
+    pub cors_allowed_methods: Vec<String>,
+    #[serde(default = "default_headers")]
+    pub cors_allowed_headers: Vec<String>,
+    #[serde(default = "default_expose_headers")]
+    pub cors_expose_headers: Vec<String>,
+    /// Lets browsers send cookies, for session auth from another origin.
+    #[serde(default)]
+    pub cors_allow_credentials: bool,
+    #[serde(default = "default_max_age_secs")]
+    pub cors_max_age_secs: u64,
+}
+
+fn default_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "PATCH", "DELETE"].iter().map(|method| method.to_string()).collect()
+}
+
+fn default_headers() -> Vec<String> {
+    ["Authorization", "Content-Type", "If-Match", "If-Modified-Since", "X-Api-Key"]
+        .iter()
+        .map(|header| header.to_string())
+        .collect()
+}
+
+fn default_expose_headers() -> Vec<String> {
+    ["ETag", "Last-Modified"].iter().map(|header| header.to_string()).collect()
+}
+
+fn default_max_age_secs() -> u64 {
+    3600
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            cors_allowed_origins: Vec::new(),
+            cors_allowed_methods: default_methods(),
+            cors_allowed_headers: default_headers(),
+            cors_expose_headers: default_expose_headers(),
+            cors_allow_credentials: false,
+            cors_max_age_secs: default_max_age_secs(),
+        }
+    }
+}
+
+impl CorsConfig {
+    fn is_enabled(&self) -> bool {
+        !self.cors_allowed_origins.is_empty()
+    }
+
+    fn lists_origin(&self, origin: &str) -> bool {
+        self.cors_allowed_origins
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(origin.trim()))
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.lists_origin(origin) || self.cors_allowed_origins.iter().any(|allowed| allowed == "*")
+    }
+
+    /// Browsers refuse credentials with a wildcard origin, so an origin only
+    /// let in by `"*"` doesn't get them.
+    fn allows_credentials(&self, origin: &str) -> bool {
+        self.cors_allow_credentials && self.lists_origin(origin)
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.cors_allowed_methods
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(method.trim()))
+    }
+
+    /// Whether every header in a comma separated `Access-Control-Request-Headers`
+    /// is allowed. Header names are compared ignoring case.
+    fn allows_headers(&self, headers: &str) -> bool {
+        headers
+            .split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .all(|header| {
+                self.cors_allowed_headers
+                    .iter()
+                    .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(header))
+            })
+    }
+
+    /// Whether a preflight from `origin` for `method` with `headers` should
+    /// be let through.
+    fn allows_preflight(&self, origin: &str, method: &str, headers: Option<&str>) -> bool {
+        self.allows_origin(origin)
+            && self.allows_method(method)
+            && headers.map_or(true, |headers| self.allows_headers(headers))
+    }
+}
+
+/// Adds CORS headers to responses for allowed origins, and answers preflight
+/// `OPTIONS` requests itself: a 204 when the origin, method and headers are
+/// all allowed and a 403 otherwise.
+pub struct Cors;
+
+fn allow_origin(config: &CorsConfig, origin: &str, res: &mut Response<'_>) {
+    // Listed origins are echoed, so they can be sent credentials, and the
+    // rest get `*`; either way caches have to key on the origin.
+    res.adjoin_raw_header("Vary", "Origin");
+    if !config.lists_origin(origin) {
+        res.set_raw_header("Access-Control-Allow-Origin", "*");
+        return;
+    }
+    res.set_header(Header::new("Access-Control-Allow-Origin", origin.to_string()));
+    if config.allows_credentials(origin) {
+        res.set_raw_header("Access-Control-Allow-Credentials", "true");
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let config = match req.rocket().state::<CorsConfig>() {
+            Some(config) if config.is_enabled() => config,
+            _ => return,
+        };
+        let origin = match req.headers().get_one("Origin") {
+            Some(origin) => origin,
+            None => return,
+        };
+
+        let requested_method = req.headers().get_one(REQUEST_METHOD);
+        if let (Method::Options, Some(method)) = (req.method(), requested_method) {
+            let requested_headers = req.headers().get_one(REQUEST_HEADERS);
+            let mut preflight = Response::new();
+            if !config.allows_preflight(origin, method, requested_headers) {
+                preflight.set_status(Status::Forbidden);
+                *res = preflight;
+                return;
+            }
+            preflight.set_status(Status::NoContent);
+            allow_origin(config, origin, &mut preflight);
+            preflight.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                config.cors_allowed_methods.join(", "),
+            ));
+            if let Some(headers) = requested_headers {
+                preflight.set_header(Header::new(
+                    "Access-Control-Allow-Headers",
+                    headers.to_string(),
+                ));
+            }
+            preflight.set_header(Header::new(
+                "Access-Control-Max-Age",
+                config.cors_max_age_secs.to_string(),
+            ));
+            *res = preflight;
+            return;
+        }
+
+        if config.allows_origin(origin) {
+            allow_origin(config, origin, res);
+            if !config.cors_expose_headers.is_empty() {
+                res.set_header(Header::new(
+                    "Access-Control-Expose-Headers",
+                    config.cors_expose_headers.join(", "),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(origins: &[&str]) -> CorsConfig {
+        CorsConfig {
+            cors_allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            ..CorsConfig::default()
+        }
+    }
+
+    #[test]
+    fn cors_is_off_without_origins() {
+        assert!(!CorsConfig::default().is_enabled());
+        assert!(!CorsConfig::default().allows_origin("https://app.example"));
+        assert!(config(&["https://app.example"]).is_enabled());
+    }
+
+    #[test]
+    fn origins_match_exactly_or_by_wildcard() {
+        let exact = config(&["https://app.example"]);
+        assert!(exact.allows_origin("https://app.example"));
+        assert!(!exact.allows_origin("https://evil.example"));
+        assert!(!exact.allows_origin("https://app.example.evil"));
+        assert!(config(&["*"]).allows_origin("https://anything.example"));
+    }
+
+    #[test]
+    fn only_listed_origins_get_credentials() {
+        let config = CorsConfig {
+            cors_allow_credentials: true,
+            ..config(&["*", "https://app.example"])
+        };
+        assert!(config.allows_credentials("https://app.example"));
+        assert!(config.allows_origin("https://anything.example"));
+        assert!(!config.allows_credentials("https://anything.example"));
+    }
+
+    #[test]
+    fn preflights_need_an_allowed_method_and_headers() {
+        let config = config(&["https://app.example"]);
+        let origin = "https://app.example";
+        assert!(config.allows_preflight(origin, "patch", None));
+        assert!(config.allows_preflight(origin, "PUT", Some("authorization, content-type")));
+        assert!(!config.allows_preflight(origin, "TRACE", None));
+        assert!(!config.allows_preflight(origin, "GET", Some("X-Secret")));
+        assert!(!config.allows_preflight("https://evil.example", "GET", None));
+    }
+}
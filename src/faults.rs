@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use rand::Rng;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    get,
+    http::{uri::Origin, Method, Status},
+    response::status,
+    serde::json::Json,
+    tokio::time::sleep,
+    Config, Data, Request,
+};
+use serde::{Deserialize, Serialize};
+
+/// Requests picked to fail are rewritten to this route.
+const FAULT_PATH: &str = "/api/fault";
+
+/// Simulated bad conditions for client development. Both default to off, and
+/// they're ignored outside the debug profile so they can't leak into production.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct FaultConfig {
+    #[serde(default)]
+    pub fault_latency_ms: u64,
+    #[serde(default)]
+    pub fault_failure_rate: f64,
+}
+
+impl FaultConfig {
+    fn is_enabled(&self) -> bool {
+        self.fault_latency_ms > 0 || self.fault_failure_rate > 0.0
+    }
+
+    fn failure_rate(&self) -> f64 {
+        self.fault_failure_rate.max(0.0).min(1.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FaultMessage {
+    pub message: String,
+}
+
+/// Delays every request by `fault_latency_ms` and fails a `fault_failure_rate`
+/// fraction of them with a 500, as if the repo had errored.
+pub struct FaultInjection;
+
+#[rocket::async_trait]
+impl Fairing for FaultInjection {
+    fn info(&self) -> Info {
+        Info {
+            name: "Fault injection",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data) {
+        let rocket = req.rocket();
+        let config = match rocket.state::<FaultConfig>() {
+            Some(config) if config.is_enabled() => config.clone(),
+            _ => return,
+        };
+        if rocket.figment().profile() != &Config::DEBUG_PROFILE {
+            return;
+        }
+
+        if config.fault_latency_ms > 0 {
+            sleep(Duration::from_millis(config.fault_latency_ms)).await;
+        }
+        if rand::thread_rng().gen_bool(config.failure_rate()) {
+            req.set_method(Method::Get);
+            req.set_uri(Origin::parse(FAULT_PATH).expect("fault path is a valid uri"));
+        }
+    }
+}
+
+#[get("/fault")]
+pub fn fault() -> status::Custom<Json<FaultMessage>> {
+    status::Custom(
+        Status::InternalServerError,
+        Json(FaultMessage { message: "Injected fault".to_owned() }),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn faults_are_off_by_default() {
+        assert!(!FaultConfig::default().is_enabled());
+    }
+
+    #[test]
+    fn failure_rate_is_clamped_to_a_probability() {
+        let config = FaultConfig { fault_latency_ms: 0, fault_failure_rate: 2.5 };
+        assert_eq!(config.failure_rate(), 1.0);
+
+        let config = FaultConfig { fault_latency_ms: 0, fault_failure_rate: -1.0 };
+        assert_eq!(config.failure_rate(), 0.0);
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Serialize, Deserialize};
+use mongodb::bson::oid::ObjectId;
+
+use crate::list::ListItem;
+
+/// An item the user has starred so it can be added to new lists in one go.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Favorite {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    #[serde(rename(serialize = "userId", deserialize = "userId"))]
+    pub user_id: ObjectId,
+    pub name: String,
+    pub category: Option<String>,
+    pub amount: Option<String>,
+}
+
+impl Favorite {
+    pub fn new(user_id: ObjectId, name: &str, category: Option<&str>, amount: Option<&str>) -> Self {
+        Favorite {
+            _id: None,
+            user_id,
+            name: name.trim().to_owned(),
+            category: category.map(str::to_lowercase),
+            amount: amount.map(str::to_owned),
+        }
+    }
+
+    /// A fresh, unchecked item for a new list.
+    pub fn to_list_item(&self) -> ListItem {
+        let mut builder = ListItem::builder(&self.name);
+        if let Some(category) = &self.category {
+            builder.category(category);
+        }
+        if let Some(amount) = &self.amount {
+            builder.amount(amount);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn favorites_become_unchecked_items() {
+        let favorite = Favorite::new(ObjectId::new(), " milk ", Some("Dairy"), Some("1 gal"));
+        let item = favorite.to_list_item();
+
+        assert_eq!(item.name(), "milk");
+        assert_eq!(item.category(), Some("dairy"));
+        assert_eq!(item.amount(), Some("1 gal"));
+        assert!(!item.is_checked());
+    }
+}
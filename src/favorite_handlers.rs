@@ -0,0 +1,89 @@
+use rocket::{State, delete, get, http::Status, post, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use rocket::error;
+
+use crate::access::UserAccess;
+use crate::favorite::Favorite;
+use crate::handler_util::parse_object_id;
+use crate::repo::Repo;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NewFavorite {
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub amount: Option<String>,
+}
+
+/// Starring the same name twice returns the first favorite rather than a copy.
+#[post("/users/<id>/favorites", data="<favorite>")]
+pub async fn add_favorite(
+    _access: UserAccess,
+    id: &str,
+    favorite: Json<NewFavorite>,
+    repo: &State<Repo>,
+) -> Result<Json<Favorite>, Status> {
+    let id = parse_object_id(id)?;
+    if favorite.name.trim().is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let favorite = Favorite::new(
+        id,
+        &favorite.name,
+        favorite.category.as_deref(),
+        favorite.amount.as_deref(),
+    );
+    let favorite = repo.add_favorite(&favorite)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new favorite returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(favorite))
+}
+
+#[get("/users/<id>/favorites")]
+pub async fn get_favorites(
+    _access: UserAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<Favorite>>, Status> {
+    let id = parse_object_id(id)?;
+    let favorites = repo.get_favorites_by_user(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(favorites))
+}
+
+#[delete("/users/<id>/favorites/<favorite_id>")]
+pub async fn delete_favorite(
+    _access: UserAccess,
+    id: &str,
+    favorite_id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let favorite_id = parse_object_id(favorite_id)?;
+    let deleted = repo.delete_favorite(&id, &favorite_id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
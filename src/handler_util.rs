@@ -0,0 +1,181 @@
+use std::convert::Infallible;
+
+use mongodb::bson::{oid::ObjectId, Document};
+use rocket::{
+    error,
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::repo::{Page, RepoError};
+
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// The body of the `/count` routes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Count {
+    pub count: i64,
+}
+
+pub fn parse_object_id(id: &str) -> Result<ObjectId, Status> {
+    ObjectId::with_string(id).map_err(|err| {
+        error!("{:?}", err);
+        Status::BadRequest
+    })
+}
+
+/// Builds a page from `?limit=&after=`, capping the limit at `MAX_PAGE_SIZE`.
+pub fn parse_page(limit: Option<i64>, after: Option<&str>) -> Result<Page, Status> {
+    if limit.map_or(false, |limit| limit <= 0) {
+        return Err(Status::BadRequest);
+    }
+
+    Ok(Page {
+        limit: Some(limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE)),
+        after: after.map(parse_object_id).transpose()?,
+    })
+}
+
+/// `?order=asc|desc`, ascending when absent.
+pub fn parse_order(order: Option<&str>) -> Result<bool, Status> {
+    match order {
+        Some("asc") | None => Ok(false),
+        Some("desc") => Ok(true),
+        Some(_) => Err(Status::BadRequest),
+    }
+}
+
+/// Turns `?fields=name,items.name` into a mongo projection. Every field has to
+/// be in `allowed`, and a field can't be asked for along with its parent.
+pub fn parse_fields(fields: &str, allowed: &[&str]) -> Result<Document, Status> {
+    let fields: Vec<&str> = fields.split(',').map(str::trim).collect();
+    let mut projection = Document::new();
+    for field in fields.iter() {
+        if !allowed.contains(field) {
+            return Err(Status::BadRequest);
+        }
+        let nested = fields
+            .iter()
+            .any(|parent| field.starts_with(parent) && field[parent.len()..].starts_with('.'));
+        if nested {
+            return Err(Status::BadRequest);
+        }
+        projection.insert(field.to_owned(), 1);
+    }
+    Ok(projection)
+}
+
+pub fn repo_error_status(err: RepoError) -> Status {
+    match err {
+        RepoError::ObjectNotFound(..) => Status::NotFound,
+        RepoError::ConcurrentModification(..) | RepoError::EmailTaken => Status::Conflict,
+        err => {
+            error!("{:?}", err);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// The raw `If-Match` header, if the client sent one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfMatch(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfMatch(request.headers().get_one("If-Match").map(str::to_owned)))
+    }
+}
+
+/// Entity tags are the quoted version number. `None` means any version will
+/// do, either because there's no `If-Match` or it's `*`. Anything that isn't
+/// one of our tags can never match, so it's a 412 straight away.
+pub fn parse_if_match(if_match: &IfMatch) -> Result<Option<i64>, Status> {
+    match if_match.0.as_deref().map(str::trim) {
+        None | Some("*") => Ok(None),
+        Some(tag) => tag
+            .strip_prefix('"')
+            .and_then(|tag| tag.strip_suffix('"'))
+            .and_then(|version| version.parse().ok())
+            .map(Some)
+            .ok_or(Status::PreconditionFailed),
+    }
+}
+
+pub fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Like `repo_error_status`, but a concurrent change means the `If-Match`
+/// precondition failed.
+pub fn precondition_status(err: RepoError) -> Status {
+    match err {
+        RepoError::ConcurrentModification(..) => Status::PreconditionFailed,
+        err => repo_error_status(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn page_limit_defaults_to_and_is_capped_at_max_page_size() {
+        assert_eq!(parse_page(None, None), Ok(Page { limit: Some(MAX_PAGE_SIZE), after: None }));
+        assert_eq!(
+            parse_page(Some(MAX_PAGE_SIZE + 1), None),
+            Ok(Page { limit: Some(MAX_PAGE_SIZE), after: None })
+        );
+        assert_eq!(parse_page(Some(10), None), Ok(Page { limit: Some(10), after: None }));
+    }
+
+    #[test]
+    fn page_rejects_non_positive_limits_and_malformed_cursors() {
+        assert_eq!(parse_page(Some(0), None), Err(Status::BadRequest));
+        assert_eq!(parse_page(None, Some("not-an-id")), Err(Status::BadRequest));
+
+        let after = ObjectId::new();
+        assert_eq!(
+            parse_page(Some(5), Some(&after.to_hex())),
+            Ok(Page { limit: Some(5), after: Some(after) })
+        );
+    }
+
+    #[test]
+    fn if_match_parses_our_etags() {
+        assert_eq!(parse_if_match(&IfMatch(None)), Ok(None));
+        assert_eq!(parse_if_match(&IfMatch(Some("*".to_string()))), Ok(None));
+        assert_eq!(parse_if_match(&IfMatch(Some(etag(3)))), Ok(Some(3)));
+        assert_eq!(
+            parse_if_match(&IfMatch(Some("W/\"3\"".to_string()))),
+            Err(Status::PreconditionFailed)
+        );
+        assert_eq!(
+            parse_if_match(&IfMatch(Some("\"abc\"".to_string()))),
+            Err(Status::PreconditionFailed)
+        );
+    }
+
+    #[test]
+    fn order_defaults_to_ascending() {
+        assert_eq!(parse_order(None), Ok(false));
+        assert_eq!(parse_order(Some("desc")), Ok(true));
+        assert_eq!(parse_order(Some("down")), Err(Status::BadRequest));
+    }
+
+    #[test]
+    fn fields_become_a_projection() {
+        let allowed = ["name", "items", "items.name"];
+        assert_eq!(
+            parse_fields("name, items.name", &allowed),
+            Ok(doc! { "name": 1, "items.name": 1 })
+        );
+        assert_eq!(parse_fields("shareToken", &allowed), Err(Status::BadRequest));
+        assert_eq!(parse_fields("items,items.name", &allowed), Err(Status::BadRequest));
+        assert_eq!(parse_fields("", &allowed), Err(Status::BadRequest));
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Serialize, Deserialize};
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+/// Users who share everything in the household: every member can see and
+/// change the household's lists and stores, without sharing them one by one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Household {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub name: String,
+    pub members: Vec<ObjectId>,
+    #[serde(
+        rename(serialize = "createdAt", deserialize = "createdAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub created_at: Option<DateTime>,
+}
+
+impl Household {
+    /// A household with just the user who started it.
+    pub fn new(name: &str, user_id: ObjectId) -> Self {
+        Household {
+            _id: None,
+            name: name.trim().to_owned(),
+            members: vec![user_id],
+            created_at: None,
+        }
+    }
+
+    pub fn includes(&self, user_id: &ObjectId) -> bool {
+        self.members.contains(user_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn households_start_with_the_user_who_made_them() {
+        let user_id = ObjectId::new();
+        let household = Household::new(" The Smiths ", user_id.clone());
+
+        assert_eq!(household.name, "The Smiths");
+        assert!(household.includes(&user_id));
+        assert!(!household.includes(&ObjectId::new()));
+    }
+}
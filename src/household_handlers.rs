@@ -0,0 +1,220 @@
+use rocket::{State, delete, get, http::Status, post, put, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use mongodb::bson::oid::ObjectId;
+use rocket::error;
+
+use crate::access::{
+    access_status, check_household, HouseholdAccess, ListOwnerAccess, UserAccess,
+};
+use crate::auth::AuthenticatedUser;
+use crate::handler_util::parse_object_id;
+use crate::household::Household;
+use crate::list::List;
+use crate::repo::Repo;
+use crate::store::Store;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NewHousehold {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HouseholdAssignment {
+    #[serde(rename(serialize = "householdId", deserialize = "householdId"))]
+    pub household_id: ObjectId,
+}
+
+/// The caller starts out as the household's only member. Others join by
+/// accepting an invitation.
+#[post("/households", data="<household>")]
+pub async fn create_household(
+    auth: AuthenticatedUser,
+    household: Json<NewHousehold>,
+    repo: &State<Repo>,
+) -> Result<Json<Household>, Status> {
+    if household.name.trim().is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let household = repo.add_household(&Household::new(&household.name, auth.id))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new household returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(household))
+}
+
+#[get("/households/<id>")]
+pub async fn get_household(
+    _access: HouseholdAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Household>, Status> {
+    let id = parse_object_id(id)?;
+    let household = repo.get_household_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(household))
+}
+
+#[get("/users/<id>/households")]
+pub async fn get_user_households(
+    _access: UserAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<Household>>, Status> {
+    let id = parse_object_id(id)?;
+    let households = repo.get_households_by_user(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(households))
+}
+
+/// Any member can remove anyone, themselves included. When the last member
+/// leaves the household is deleted, and its lists and stores go back to
+/// belonging only to the users who made them.
+#[delete("/households/<id>/members/<user_id>")]
+pub async fn remove_household_member(
+    _access: HouseholdAccess,
+    id: &str,
+    user_id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Household>, Status> {
+    let id = parse_object_id(id)?;
+    let user_id = parse_object_id(user_id)?;
+    let household = repo.remove_household_member(&id, &user_id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(household))
+}
+
+/// Lets every member of the household see and change the list. Only the
+/// list's owner, with full credentials, can do this, and they have to be a
+/// member of the household too.
+#[put("/lists/<id>/household", data="<assignment>")]
+pub async fn set_list_household(
+    access: ListOwnerAccess,
+    id: &str,
+    assignment: Json<HouseholdAssignment>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    check_household(repo, &access.user, &assignment.household_id)
+        .await
+        .map_err(access_status)?;
+    let list = repo.set_list_household(&id, Some(&assignment.household_id))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+/// Only the list's owner, with full credentials, can take it out of its
+/// household.
+#[delete("/lists/<id>/household")]
+pub async fn clear_list_household(
+    _access: ListOwnerAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.set_list_household(&id, None)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+/// A 403 unless the caller owns the store, so housemates who can change it
+/// still can't move it; a missing store is a 404.
+async fn check_store_owner(
+    repo: &Repo,
+    auth: &AuthenticatedUser,
+    id: &ObjectId,
+) -> Result<(), Status> {
+    let store = repo.get_store_by_id(id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    if !store.is_owned_by(&auth.id) {
+        return Err(Status::Forbidden);
+    }
+    Ok(())
+}
+
+/// Lets every member of the household use and change the store. Only the
+/// store's owner can do this, and they have to be a member of the household
+/// too.
+#[put("/stores/<id>/household", data="<assignment>")]
+pub async fn set_store_household(
+    auth: AuthenticatedUser,
+    id: &str,
+    assignment: Json<HouseholdAssignment>,
+    repo: &State<Repo>,
+) -> Result<Json<Store>, Status> {
+    let id = parse_object_id(id)?;
+    check_store_owner(repo, &auth, &id).await?;
+    check_household(repo, &auth, &assignment.household_id)
+        .await
+        .map_err(access_status)?;
+    let store = repo.set_store_household(&id, Some(&assignment.household_id))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(store))
+}
+
+/// Only the store's owner can take it out of its household.
+#[delete("/stores/<id>/household")]
+pub async fn clear_store_household(
+    auth: AuthenticatedUser,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Store>, Status> {
+    let id = parse_object_id(id)?;
+    check_store_owner(repo, &auth, &id).await?;
+    let store = repo.set_store_household(&id, None)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(store))
+}
@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+
+use crate::list::ListItem;
+
+/// A line of an import that couldn't be turned into an item. `row` counts data
+/// rows from 1, not counting the header.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub row: usize,
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CsvRow {
+    name: String,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    amount: Option<String>,
+}
+
+/// Parses a CSV with a `name,category,amount` header into items. Bad rows are
+/// reported and skipped rather than failing the whole import.
+pub fn parse_csv_items(csv: &str) -> (Vec<ListItem>, Vec<RowError>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(csv.as_bytes());
+
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for (index, result) in reader.deserialize::<CsvRow>().enumerate() {
+        let row = index + 1;
+        let record = match result {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(RowError { row, message: err.to_string() });
+                continue;
+            }
+        };
+        if record.name.is_empty() {
+            errors.push(RowError { row, message: "name is empty".to_owned() });
+            continue;
+        }
+
+        let mut builder = ListItem::builder(&record.name);
+        if let Some(category) = record.category.filter(|category| !category.is_empty()) {
+            builder.category(&category);
+        }
+        if let Some(amount) = record.amount.filter(|amount| !amount.is_empty()) {
+            builder.amount(&amount);
+        }
+        items.push(builder.build());
+    }
+
+    (items, errors)
+}
+
+/// Units that can follow a bare number, as in "2 lb salmon".
+const UNITS: &[&str] = &[
+    "bag", "bags", "bottle", "bottles", "box", "boxes", "bunch", "bunches", "can", "cans",
+    "cup", "cups", "dozen", "g", "gal", "gallon", "gallons", "jar", "jars", "kg", "l", "lb",
+    "lbs", "loaf", "loaves", "ml", "oz", "pack", "packs", "pt", "qt", "tbsp", "tsp",
+];
+
+fn is_quantity(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_digit() || "½¼¾".contains(c))
+}
+
+/// Drops bullets, checkboxes and "1." style numbering that notes apps add.
+fn strip_marker(line: &str) -> &str {
+    let line = line.trim();
+    for marker in ["- [ ]", "- [x]", "[ ]", "[x]", "-", "*", "+", "•"].iter() {
+        if let Some(rest) = line.strip_prefix(marker) {
+            return rest.trim_start();
+        }
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    match line[digits..].chars().next() {
+        Some('.') | Some(')') if digits > 0 && line[digits + 1..].starts_with(' ') => {
+            line[digits + 1..].trim_start()
+        }
+        _ => line,
+    }
+}
+
+/// Splits a pasted line into a name and an amount using the usual ways people
+/// write quantities: "2lb salmon", "2 cans of beans", "3x eggs", "eggs x3" and
+/// "salmon (2lb)". Lines without a recognisable amount keep their full text.
+fn split_amount(line: &str) -> (String, Option<String>) {
+    if line.ends_with(')') {
+        if let Some(open) = line.rfind('(') {
+            let name = line[..open].trim();
+            let amount = line[open + 1..line.len() - 1].trim();
+            if !name.is_empty() && !amount.is_empty() {
+                return (name.to_owned(), Some(amount.to_owned()));
+            }
+        }
+    }
+
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if let Some((last, rest)) = tokens.split_last() {
+        let count = last.trim_start_matches(|c| c == 'x' || c == '×');
+        if !rest.is_empty() && count.len() < last.len() && !count.is_empty()
+            && count.chars().all(|c| c.is_ascii_digit())
+        {
+            return (rest.join(" "), Some(count.to_owned()));
+        }
+    }
+
+    let first = match tokens.first() {
+        Some(first) if is_quantity(first) => first,
+        _ => return (line.to_owned(), None),
+    };
+    let mut amount = first.trim_end_matches(|c| c == 'x' || c == '×').to_owned();
+    let mut rest = &tokens[1..];
+    if let Some(unit) = rest.first() {
+        if amount.chars().all(|c| !c.is_alphabetic()) && UNITS.contains(&unit.to_lowercase().as_str()) {
+            amount = format!("{} {}", amount, unit);
+            rest = &rest[1..];
+        }
+    }
+    if rest.first() == Some(&"of") {
+        rest = &rest[1..];
+    }
+    if rest.is_empty() || amount.is_empty() {
+        return (line.to_owned(), None);
+    }
+
+    (rest.join(" "), Some(amount))
+}
+
+/// Parses one item per line from text pasted out of a notes app. Blank lines
+/// and headings ending in ':' are skipped.
+pub fn parse_text_items(text: &str) -> Vec<ListItem> {
+    text.lines()
+        .map(strip_marker)
+        .filter(|line| !line.is_empty() && !line.ends_with(':'))
+        .map(|line| {
+            let (name, amount) = split_amount(line);
+            let mut builder = ListItem::builder(&name);
+            if let Some(amount) = amount {
+                builder.amount(&amount);
+            }
+            builder.build()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn summary(items: &[ListItem]) -> Vec<(String, Option<String>, Option<String>)> {
+        items
+            .iter()
+            .map(|item| {
+                (
+                    item.name().to_owned(),
+                    item.category().map(str::to_owned),
+                    item.amount().map(str::to_owned),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn csv_rows_become_items() {
+        let (items, errors) = parse_csv_items(
+            "name,category,amount\nsalmon, Meat ,2lb\nfoil,,\n\"bread, sourdough\",bakery,1\n",
+        );
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(
+            summary(&items),
+            vec![
+                ("salmon".to_string(), Some("meat".to_string()), Some("2lb".to_string())),
+                ("foil".to_string(), None, None),
+                ("bread, sourdough".to_string(), Some("bakery".to_string()), Some("1".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_rows_are_reported_and_skipped() {
+        let (items, errors) = parse_csv_items("name,category,amount\n,produce,1\neggs,dairy,12\n");
+
+        assert_eq!(
+            summary(&items),
+            vec![("eggs".to_string(), Some("dairy".to_string()), Some("12".to_string()))]
+        );
+        assert_eq!(errors, vec![RowError { row: 1, message: "name is empty".to_string() }]);
+    }
+
+    #[test]
+    fn category_and_amount_columns_are_optional() {
+        let (items, errors) = parse_csv_items("name\nfoil\n");
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(summary(&items), vec![("foil".to_string(), None, None)]);
+    }
+
+    #[test]
+    fn amounts_are_split_from_names() {
+        let cases = [
+            ("2lb salmon", "salmon", Some("2lb")),
+            ("2 lb salmon", "salmon", Some("2 lb")),
+            ("2 cans of black beans", "black beans", Some("2 cans")),
+            ("3x eggs", "eggs", Some("3")),
+            ("eggs x3", "eggs", Some("3")),
+            ("salmon (2lb)", "salmon", Some("2lb")),
+            ("½ cup sugar", "sugar", Some("½ cup")),
+            ("6 apples", "apples", Some("6")),
+            ("foil", "foil", None),
+            ("7up", "7up", None),
+            ("xbox controller", "xbox controller", None),
+        ];
+
+        for (line, name, amount) in cases.iter() {
+            assert_eq!(
+                split_amount(line),
+                (name.to_string(), amount.map(str::to_owned)),
+                "splitting {:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn pasted_lines_become_items() {
+        let items = parse_text_items("Groceries:\n- 2lb salmon\n\n1. foil\n[ ] eggs x12\n");
+
+        assert_eq!(
+            summary(&items),
+            vec![
+                ("salmon".to_string(), None, Some("2lb".to_string())),
+                ("foil".to_string(), None, None),
+                ("eggs".to_string(), None, Some("12".to_string())),
+            ]
+        );
+    }
+}
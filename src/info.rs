@@ -0,0 +1,28 @@
+use rocket::{fairing::AdHoc, get, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BuildInfo {
+    pub version: String,
+    #[serde(rename(serialize = "gitSha", deserialize = "gitSha"))]
+    pub git_sha: String,
+    pub profile: String,
+}
+
+/// The version and SHA are fixed at compile time; the profile is whichever one
+/// Rocket was launched with.
+pub fn build_info() -> AdHoc {
+    AdHoc::on_ignite("Build info", |rocket| async {
+        let info = BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            git_sha: env!("GIT_SHA").to_owned(),
+            profile: rocket.figment().profile().to_string(),
+        };
+        rocket.manage(info)
+    })
+}
+
+#[get("/info")]
+pub fn get_info(info: &State<BuildInfo>) -> Json<BuildInfo> {
+    Json(info.inner().clone())
+}
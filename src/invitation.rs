@@ -0,0 +1,88 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use serde::{Serialize, Deserialize};
+
+use crate::api_key::hash_key;
+
+const TOKEN_LEN: usize = 32;
+/// How long the invitee has to accept.
+pub const INVITATION_LIFETIME_DAYS: i64 = 7;
+
+/// What accepting an invitation joins.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "id", rename_all = "lowercase")]
+pub enum InvitationTarget {
+    Household(ObjectId),
+    List(ObjectId),
+}
+
+/// An invitation mailed to `email`, waiting to be accepted. Only a hash of
+/// the mailed token is kept, as for password resets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Invitation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub email: String,
+    pub target: InvitationTarget,
+    #[serde(rename(serialize = "invitedBy", deserialize = "invitedBy"))]
+    pub invited_by: ObjectId,
+    #[serde(rename(serialize = "tokenHash", deserialize = "tokenHash"))]
+    pub token_hash: String,
+    #[serde(rename(serialize = "createdAt", deserialize = "createdAt"))]
+    pub created_at: DateTime,
+    #[serde(rename(serialize = "expiresAt", deserialize = "expiresAt"))]
+    pub expires_at: DateTime,
+    #[serde(
+        rename(serialize = "acceptedAt", deserialize = "acceptedAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub accepted_at: Option<DateTime>,
+}
+
+impl Invitation {
+    /// A new random token for the invitation, and the record to store for it.
+    pub fn generate(
+        email: &str,
+        target: InvitationTarget,
+        invited_by: ObjectId,
+        created_at: DateTime,
+        expires_at: DateTime,
+    ) -> (String, Invitation) {
+        let token: String =
+            OsRng.sample_iter(&Alphanumeric).take(TOKEN_LEN).map(char::from).collect();
+        let invitation = Invitation {
+            _id: None,
+            email: email.to_owned(),
+            target,
+            invited_by,
+            token_hash: hash_key(&token),
+            created_at,
+            expires_at,
+            accepted_at: None,
+        };
+        (token, invitation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn only_a_hash_of_the_token_is_kept() {
+        let now = Utc::now();
+        let (token, invitation) = Invitation::generate(
+            "friend@bar.com",
+            InvitationTarget::Household(ObjectId::new()),
+            ObjectId::new(),
+            DateTime::from(now),
+            DateTime::from(now + Duration::days(INVITATION_LIFETIME_DAYS)),
+        );
+
+        assert_eq!(token.len(), TOKEN_LEN);
+        assert_eq!(invitation.token_hash, hash_key(&token));
+        assert_eq!(invitation.accepted_at, None);
+    }
+}
@@ -0,0 +1,301 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::{State, http::Status, post, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use rocket::error;
+
+use crate::access::{HouseholdAccess, ListOwnerAccess};
+use crate::api_key::hash_key;
+use crate::auth::{hash_password, normalize_email, AuthError, AuthenticatedUser};
+use crate::handler_util::{parse_object_id, repo_error_status};
+use crate::invitation::{Invitation, InvitationTarget, INVITATION_LIFETIME_DAYS};
+use crate::mailer::{Mail, Mailer};
+use crate::provisioning::{provision_user, SignupConfig};
+use crate::repo::Repo;
+use crate::user::User as RepoUser;
+use crate::user_handlers::User;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NewInvitation {
+    pub email: String,
+}
+
+/// An invitation without its token hash.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InvitationInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub email: String,
+    pub target: InvitationTarget,
+    #[serde(rename(serialize = "expiresAt", deserialize = "expiresAt"))]
+    pub expires_at: DateTime,
+}
+
+impl From<Invitation> for InvitationInfo {
+    fn from(invitation: Invitation) -> Self {
+        InvitationInfo {
+            id: invitation._id,
+            email: invitation.email,
+            target: invitation.target,
+            expires_at: invitation.expires_at,
+        }
+    }
+}
+
+/// `name` and `password` are only needed when nobody has registered with the
+/// invited email yet; they're used to make the account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InvitationAcceptance {
+    pub token: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AcceptedInvitation {
+    pub user: User,
+    pub target: InvitationTarget,
+}
+
+/// Stores the invitation and mails its token to `email`. `what` says what
+/// they're invited to, for the mail.
+async fn send_invitation(
+    repo: &Repo,
+    mailer: &Arc<dyn Mailer>,
+    invited_by: &ObjectId,
+    email: &str,
+    target: InvitationTarget,
+    what: &str,
+) -> Result<InvitationInfo, Status> {
+    let email = normalize_email(email);
+    if email.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let inviter = repo.get_user_by_id(invited_by)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::Unauthorized)?;
+
+    let now = Utc::now();
+    let lifetime = Duration::days(INVITATION_LIFETIME_DAYS);
+    let (token, invitation) = Invitation::generate(
+        &email,
+        target,
+        invited_by.clone(),
+        DateTime::from(now),
+        DateTime::from(now + lifetime),
+    );
+    let invitation = repo.add_invitation(&invitation)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new invitation returned");
+            Status::InternalServerError
+        })?;
+
+    let mail = Mail {
+        to: email,
+        subject: format!("{} invited you to Grok List", inviter.name),
+        body: format!(
+            "{} invited you to {}. Use this code to accept: {}\n\nIt works once, for the next {} \
+             days. If you don't have an account yet, you'll make one when you accept.",
+            inviter.name,
+            what,
+            token,
+            lifetime.num_days()
+        ),
+    };
+    mailer.send(mail).await.map_err(|err| {
+        error!("{:?}", err);
+        Status::InternalServerError
+    })?;
+
+    Ok(InvitationInfo::from(invitation))
+}
+
+/// Mails `email` a code that adds them to the household when accepted.
+#[post("/households/<id>/invitations", data="<invitation>")]
+pub async fn invite_to_household(
+    auth: AuthenticatedUser,
+    _access: HouseholdAccess,
+    id: &str,
+    invitation: Json<NewInvitation>,
+    repo: &State<Repo>,
+    mailer: &State<Arc<dyn Mailer>>,
+) -> Result<Json<InvitationInfo>, Status> {
+    let id = parse_object_id(id)?;
+    let household = repo.get_household_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let what = format!("join the household \"{}\"", household.name);
+    let target = InvitationTarget::Household(id);
+    let info = send_invitation(repo, mailer, &auth.id, &invitation.email, target, &what).await?;
+
+    Ok(Json(info))
+}
+
+/// Mails `email` a code that shares the list with them when accepted. Only the
+/// list's owner, with full credentials, can invite people to it.
+#[post("/lists/<id>/invitations", data="<invitation>")]
+pub async fn invite_to_list(
+    access: ListOwnerAccess,
+    id: &str,
+    invitation: Json<NewInvitation>,
+    repo: &State<Repo>,
+    mailer: &State<Arc<dyn Mailer>>,
+) -> Result<Json<InvitationInfo>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let what = format!("share the list \"{}\"", list.name);
+    let target = InvitationTarget::List(id);
+    let info =
+        send_invitation(repo, mailer, &access.user.id, &invitation.email, target, &what).await?;
+
+    Ok(Json(info))
+}
+
+/// Makes an account for the invited email from `name` and `password`, as
+/// registering would.
+async fn register_invitee(
+    repo: &Repo,
+    signup: &SignupConfig,
+    email: &str,
+    name: &str,
+    password_hash: String,
+) -> Result<RepoUser, Status> {
+    let user = RepoUser::with_password(name.to_owned(), email.to_owned(), password_hash);
+    let user = repo.add_user(&user)
+        .await
+        .map_err(repo_error_status)?
+        .ok_or_else(|| {
+            error!("No new user returned");
+            Status::InternalServerError
+        })?;
+    if let Some(user_id) = &user._id {
+        if let Err(err) = provision_user(repo, signup, user_id).await {
+            error!("Could not provision new user {:?}: {:?}", user_id, err);
+        }
+    }
+    Ok(user)
+}
+
+/// Accepts an invitation with the mailed token, adding whoever has the invited
+/// email to the household or list. Without an account for that email one is
+/// made from `name` and `password`, which are then required. An unknown,
+/// used or expired token is a 400, as is a missing name or short password,
+/// and a disabled account is a 403. The invitee logs in afterwards as usual.
+#[post("/invitations/accept", data="<acceptance>")]
+pub async fn accept_invitation(
+    acceptance: Json<InvitationAcceptance>,
+    repo: &State<Repo>,
+    signup: &State<SignupConfig>,
+) -> Result<Json<AcceptedInvitation>, Status> {
+    let now = DateTime::from(Utc::now());
+    let invitation = repo.get_pending_invitation(&hash_key(&acceptance.token), now.clone())
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::BadRequest)?;
+    let existing = repo.get_user_by_email(&invitation.email)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if existing.as_ref().map_or(false, RepoUser::is_disabled) {
+        return Err(Status::Forbidden);
+    }
+    // Everything that can be rejected is checked before the invitation is used up.
+    let registration = match (&existing, acceptance.name.as_deref(), &acceptance.password) {
+        (Some(_), _, _) => None,
+        (None, Some(name), Some(password)) if !name.trim().is_empty() => {
+            let password_hash = hash_password(password).map_err(|err| match err {
+                AuthError::PasswordTooShort => Status::BadRequest,
+                err => {
+                    error!("{:?}", err);
+                    Status::InternalServerError
+                }
+            })?;
+            Some((name.trim(), password_hash))
+        }
+        (None, _, _) => return Err(Status::BadRequest),
+    };
+
+    let id = invitation._id.clone().ok_or_else(|| {
+        error!("Invitation without an _id for {:?}", invitation.email);
+        Status::InternalServerError
+    })?;
+    let accepted = repo.accept_invitation(&id, now)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if !accepted {
+        return Err(Status::BadRequest);
+    }
+
+    let user = match (existing, registration) {
+        (Some(user), _) => user,
+        (None, Some((name, password_hash))) => {
+            register_invitee(repo, signup, &invitation.email, name, password_hash).await?
+        }
+        (None, None) => return Err(Status::BadRequest),
+    };
+    let user_id = user._id.clone().ok_or_else(|| {
+        error!("User without an _id: {:?}", user.email);
+        Status::InternalServerError
+    })?;
+
+    let joined = match &invitation.target {
+        InvitationTarget::Household(household_id) => repo
+            .add_household_member(household_id, &user_id)
+            .await
+            .map(|household| household.is_some()),
+        InvitationTarget::List(list_id) => match repo.get_list_members(list_id).await {
+            Ok(Some(members)) if members.includes(&user_id) => Ok(true),
+            Ok(Some(_)) => repo
+                .add_list_collaborator(list_id, &user_id)
+                .await
+                .map(|list| list.is_some()),
+            Ok(None) => Ok(false),
+            Err(err) => Err(err),
+        },
+    };
+    let joined = joined.map_err(|err| {
+        error!("{:?}", err);
+        Status::InternalServerError
+    })?;
+    if !joined {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Json(AcceptedInvitation {
+        user: User::new(user._id, user.name, user.email),
+        target: invitation.target,
+    }))
+}
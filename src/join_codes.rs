@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rand::Rng;
+use rocket::{error, http::Status, post, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+
+use crate::access::{access_status, check_user, ListOwnerAccess};
+use crate::auth::AuthenticatedUser;
+use crate::handler_util::parse_object_id;
+use crate::list::{JoinCode, List};
+use crate::repo::Repo;
+
+const CODE_PREFIX: &str = "GROK-";
+/// No 0/O or 1/I, so codes survive being read out over the phone.
+const CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+const CODE_LEN: usize = 4;
+const CODE_LIFETIME_MINUTES: i64 = 15;
+
+/// Codes are short enough to guess, so each user only gets a few misses per window.
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const ATTEMPT_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JoinRequest {
+    pub code: String,
+    #[serde(rename(serialize = "userId", deserialize = "userId"))]
+    pub user_id: ObjectId,
+}
+
+fn new_join_code() -> String {
+    let mut rng = rand::thread_rng();
+    let code: String = (0..CODE_LEN)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}{}", CODE_PREFIX, code)
+}
+
+/// Accepts codes typed in lowercase or without the prefix.
+fn normalize_code(code: &str) -> String {
+    let code = code.trim().to_uppercase();
+    if code.starts_with(CODE_PREFIX) {
+        code
+    } else {
+        format!("{}{}", CODE_PREFIX, code)
+    }
+}
+
+/// Failed join attempts per user within the current window.
+#[derive(Debug, Default)]
+pub struct JoinAttempts {
+    failures: Mutex<HashMap<String, (u32, Instant)>>,
+}
+
+impl JoinAttempts {
+    fn is_blocked(&self, client: &str, now: Instant) -> bool {
+        let failures = self.failures.lock().unwrap_or_else(|err| err.into_inner());
+        failures.get(client).map_or(false, |(count, started)| {
+            *count >= MAX_FAILED_ATTEMPTS && now.duration_since(*started) < ATTEMPT_WINDOW
+        })
+    }
+
+    fn record_failure(&self, client: &str, now: Instant) {
+        let mut failures = self.failures.lock().unwrap_or_else(|err| err.into_inner());
+        failures.retain(|_, (_, started)| now.duration_since(*started) < ATTEMPT_WINDOW);
+        failures.entry(client.to_owned()).or_insert((0, now)).0 += 1;
+    }
+}
+
+/// Issues a fresh join code for the list, replacing any earlier one. Codes
+/// still live on another list are skipped. Only the owner, with full
+/// credentials, can let others join.
+#[post("/lists/<id>/join-code")]
+pub async fn create_join_code(
+    _access: ListOwnerAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<JoinCode>, Status> {
+    let id = parse_object_id(id)?;
+    let mut code = new_join_code();
+    while repo.get_list_by_join_code(&code)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .is_some()
+    {
+        code = new_join_code();
+    }
+    let join_code = JoinCode {
+        code,
+        expires_at: DateTime::from(Utc::now() + chrono::Duration::minutes(CODE_LIFETIME_MINUTES)),
+    };
+    repo.set_list_join_code(&id, &join_code)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(join_code))
+}
+
+/// Adds the user as a collaborator on the list the code belongs to. Users can
+/// only join lists themselves. Misses are counted per user, so one user's
+/// typos never use up anyone else's codes.
+#[post("/join", data="<request>")]
+pub async fn join_list(
+    auth: AuthenticatedUser,
+    request: Json<JoinRequest>,
+    attempts: &State<JoinAttempts>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    check_user(&auth, &request.user_id).map_err(access_status)?;
+    let client = auth.id.to_hex();
+    if attempts.is_blocked(&client, Instant::now()) {
+        return Err(Status::TooManyRequests);
+    }
+
+    let list = repo.get_list_by_join_code(&normalize_code(&request.code))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    let list = match list {
+        Some(list) => list,
+        None => {
+            attempts.record_failure(&client, Instant::now());
+            return Err(Status::NotFound);
+        }
+    };
+    if list.user_id == request.user_id {
+        return Err(Status::BadRequest);
+    }
+
+    let list_id = list._id.ok_or_else(|| {
+        error!("List returned from mongo had no _id");
+        Status::InternalServerError
+    })?;
+    let list = repo.add_list_collaborator(&list_id, &request.user_id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn join_codes_are_prefixed_and_unambiguous() {
+        let code = new_join_code();
+        assert!(code.starts_with(CODE_PREFIX));
+        assert_eq!(code.len(), CODE_PREFIX.len() + CODE_LEN);
+        assert!(code[CODE_PREFIX.len()..].bytes().all(|c| CODE_ALPHABET.contains(&c)));
+    }
+
+    #[test]
+    fn typed_codes_are_normalized() {
+        assert_eq!(normalize_code(" grok-7f3k "), "GROK-7F3K");
+        assert_eq!(normalize_code("7f3k"), "GROK-7F3K");
+    }
+
+    #[test]
+    fn users_are_blocked_after_too_many_misses() {
+        let attempts = JoinAttempts::default();
+        let now = Instant::now();
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            assert!(!attempts.is_blocked("user-1", now));
+            attempts.record_failure("user-1", now);
+        }
+
+        assert!(attempts.is_blocked("user-1", now));
+        assert!(!attempts.is_blocked("user-2", now));
+        assert!(!attempts.is_blocked("user-1", now + ATTEMPT_WINDOW));
+    }
+}
@@ -1,5 +1,6 @@
-use serde::{Serialize, Deserialize};
-use mongodb::bson::oid::ObjectId;
+use chrono::NaiveDate;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use mongodb::bson::{oid::ObjectId, DateTime};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct List {
@@ -9,6 +10,115 @@ pub struct List {
     #[serde(rename(serialize = "userId", deserialize = "userId"))]
     pub user_id: ObjectId,
     pub items: Vec<ListItem>,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub view: ListView,
+    #[serde(
+        rename(serialize = "createdAt", deserialize = "createdAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub created_at: Option<DateTime>,
+    #[serde(
+        rename(serialize = "updatedAt", deserialize = "updatedAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub updated_at: Option<DateTime>,
+    #[serde(
+        rename(serialize = "deletedAt", deserialize = "deletedAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub deleted_at: Option<DateTime>,
+    #[serde(rename(serialize = "sharedWith", deserialize = "sharedWith"), default)]
+    pub shared_with: Vec<ObjectId>,
+    /// Every member of the household can see and change the list.
+    #[serde(
+        rename(serialize = "householdId", deserialize = "householdId"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub household_id: Option<ObjectId>,
+    #[serde(
+        rename(serialize = "shareToken", deserialize = "shareToken"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub share_token: Option<String>,
+    #[serde(
+        rename(serialize = "joinCode", deserialize = "joinCode"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub join_code: Option<JoinCode>,
+    /// Lowercased names of "did you forget?" suggestions dismissed for this trip.
+    #[serde(
+        rename(serialize = "dismissedSuggestions", deserialize = "dismissedSuggestions"),
+        default
+    )]
+    pub dismissed_suggestions: Vec<String>,
+    /// Bumped by every change, so clients can detect concurrent edits.
+    #[serde(default)]
+    pub version: i64,
+}
+
+/// A list without its items, for screens that show many lists at once.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ListSummary {
+    pub _id: ObjectId,
+    pub name: String,
+    #[serde(rename(serialize = "userId", deserialize = "userId"))]
+    pub user_id: ObjectId,
+    #[serde(rename(serialize = "itemCount", deserialize = "itemCount"))]
+    pub item_count: i64,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(
+        rename(serialize = "createdAt", deserialize = "createdAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub created_at: Option<DateTime>,
+    #[serde(
+        rename(serialize = "updatedAt", deserialize = "updatedAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub updated_at: Option<DateTime>,
+    #[serde(rename(serialize = "sharedWith", deserialize = "sharedWith"), default)]
+    pub shared_with: Vec<ObjectId>,
+    #[serde(
+        rename(serialize = "householdId", deserialize = "householdId"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub household_id: Option<ObjectId>,
+    #[serde(default)]
+    pub version: i64,
+}
+
+/// Who a list belongs to: its owner, the users it's shared with and the
+/// members of its household, if it has one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ListMembers {
+    pub _id: ObjectId,
+    #[serde(rename(serialize = "userId", deserialize = "userId"))]
+    pub user_id: ObjectId,
+    #[serde(rename(serialize = "sharedWith", deserialize = "sharedWith"), default)]
+    pub shared_with: Vec<ObjectId>,
+    #[serde(rename(serialize = "householdMembers", deserialize = "householdMembers"), default)]
+    pub household_members: Vec<ObjectId>,
+}
+
+impl ListMembers {
+    /// Members can see and change everything about the list.
+    pub fn includes(&self, user_id: &ObjectId) -> bool {
+        &self.user_id == user_id
+            || self.shared_with.contains(user_id)
+            || self.household_members.contains(user_id)
+    }
 }
 
 impl List {
@@ -19,6 +129,149 @@ impl List {
     pub fn add_item(&mut self, item: ListItem) {
         self.items.push(item);
     }
+
+    /// Adds the item, or folds it into an item with the same name, ignoring
+    /// case and spacing. The merged item is unchecked, keeps its id and place,
+    /// and adds the amounts together where they can be.
+    pub fn merge_item(&mut self, item: ListItem) {
+        let name = normalize_name(&item.name);
+        let existing = self.items.iter_mut().find(|existing| normalize_name(&existing.name) == name);
+        let existing = match existing {
+            Some(existing) => existing,
+            None => return self.add_item(item),
+        };
+
+        existing.amount = merge_amounts(existing.amount.as_deref(), item.amount.as_deref());
+        existing.checked = false;
+        if existing.category.is_none() {
+            existing.category = item.category;
+        }
+        existing.need_by = match (existing.need_by.take(), item.need_by) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// Copies the list's items into a new, unsaved list owned by the same user.
+    pub fn duplicate(&self, name: Option<&str>) -> List {
+        let mut builder = List::builder(
+            name.map(str::to_owned).unwrap_or_else(|| self.name.clone()),
+            self.user_id.clone(),
+        );
+        for item in self.items.iter() {
+            builder.add_item(item.clone());
+        }
+        let mut list = builder.build();
+        list.view = self.view;
+        list
+    }
+
+    /// Compares a client's copy of this list against it, matching items by id.
+    pub fn diff(&self, name: &str, items: &[ListItem]) -> ListDiff {
+        let added = self.items
+            .iter()
+            .filter(|item| !items.iter().any(|other| other._id == item._id))
+            .cloned()
+            .collect();
+        let removed = items
+            .iter()
+            .filter(|other| !self.items.iter().any(|item| item._id == other._id))
+            .map(|other| other._id.clone())
+            .collect();
+        let changed = self.items
+            .iter()
+            .filter(|item| items.iter().any(|other| other._id == item._id && other != *item))
+            .cloned()
+            .collect();
+
+        ListDiff {
+            name: if name == self.name { None } else { Some(self.name.clone()) },
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    /// Groups items by category following the order of `categories`. Categories the
+    /// ordering doesn't mention come next in the order they first appear, and
+    /// uncategorized items come last.
+    pub fn items_by_category(&self, categories: &[String]) -> Vec<(Option<String>, Vec<ListItem>)> {
+        let mut groups: Vec<(Option<String>, Vec<ListItem>)> = categories
+            .iter()
+            .map(|category| (Some(category.to_owned()), Vec::new()))
+            .collect();
+        let mut uncategorized = Vec::new();
+
+        for item in self.items.iter() {
+            match &item.category {
+                Some(category) => {
+                    match groups.iter_mut().find(|(name, _)| name.as_ref() == Some(category)) {
+                        Some((_, items)) => items.push(item.clone()),
+                        None => groups.push((Some(category.to_owned()), vec![item.clone()])),
+                    }
+                }
+                None => uncategorized.push(item.clone()),
+            }
+        }
+        groups.push((None, uncategorized));
+
+        groups.into_iter().filter(|(_, items)| !items.is_empty()).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    None,
+    Category,
+    Aisle,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// How every client should render the list.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListView {
+    pub group_by: GroupBy,
+    pub sort_direction: SortDirection,
+    pub hide_checked: bool,
+}
+
+impl Default for ListView {
+    fn default() -> Self {
+        ListView {
+            group_by: GroupBy::Category,
+            sort_direction: SortDirection::Asc,
+            hide_checked: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ListUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(
+        rename(serialize = "userId", deserialize = "userId"),
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub user_id: Option<ObjectId>,
+}
+
+/// What a client has to apply to its copy of a list to match the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ListDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub added: Vec<ListItem>,
+    pub removed: Vec<ObjectId>,
+    pub changed: Vec<ListItem>,
 }
 
 #[derive(Debug, Clone)]
@@ -45,6 +298,17 @@ impl ListBuilder {
             name: self.name.clone(),
             user_id: self.user_id.clone(),
             items: self.items.clone(),
+            archived: false,
+            view: ListView::default(),
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            shared_with: Vec::new(),
+            household_id: None,
+            share_token: None,
+            join_code: None,
+            dismissed_suggestions: Vec::new(),
+            version: 0,
         }
     }
 
@@ -54,17 +318,133 @@ impl ListBuilder {
     }
 }
 
+/// A short code someone can type in to join the list, valid until `expires_at`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JoinCode {
+    pub code: String,
+    #[serde(rename(serialize = "expiresAt", deserialize = "expiresAt"))]
+    pub expires_at: DateTime,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ListItem {
+    #[serde(default = "ObjectId::new")]
+    _id: ObjectId,
     name: String,
     category: Option<String>,
     amount: Option<String>,
+    #[serde(default)]
+    checked: bool,
+    #[serde(
+        rename(serialize = "needBy", deserialize = "needBy"),
+        skip_serializing_if = "Option::is_none",
+        default,
+        deserialize_with = "deserialize_need_by"
+    )]
+    need_by: Option<String>,
+}
+
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Splits "2 lb" or "2lb" into the quantity, the unit and whether they were
+/// spaced apart.
+fn parse_amount(amount: &str) -> Option<(f64, String, bool)> {
+    let amount = amount.trim();
+    let digits = amount
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or_else(|| amount.len());
+    let quantity = amount[..digits].parse().ok()?;
+    let unit = &amount[digits..];
+    Some((quantity, unit.trim().to_lowercase(), unit.starts_with(' ')))
+}
+
+/// Adds amounts with the same unit ("2 lb" and "1 lb" make "3 lb"). Amounts
+/// that can't be added are kept side by side.
+fn merge_amounts(existing: Option<&str>, added: Option<&str>) -> Option<String> {
+    let (existing, added) = match (existing, added) {
+        (Some(existing), Some(added)) => (existing, added),
+        (existing, added) => return existing.or(added).map(str::to_owned),
+    };
+    match (parse_amount(existing), parse_amount(added)) {
+        (Some((a, unit, spaced)), Some((b, other_unit, _))) if unit == other_unit => {
+            let total = ((a + b) * 100.0).round() / 100.0;
+            let separator = if spaced { " " } else { "" };
+            Some(format!("{}{}{}", total, separator, unit))
+        }
+        _ => Some(format!("{} + {}", existing, added)),
+    }
+}
+
+/// Formats a need-by date as YYYY-MM-DD, so dates compare correctly as strings.
+pub fn format_need_by(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+pub fn parse_need_by(date: &str) -> Result<NaiveDate, chrono::ParseError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+}
+
+fn deserialize_need_by<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|date| parse_need_by(&date).map(format_need_by).map_err(de::Error::custom))
+        .transpose()
 }
 
 impl ListItem {
     pub fn builder(name: &str) -> ListItemBuilder {
         ListItemBuilder::new(name)
     }
+
+    pub fn id(&self) -> &ObjectId {
+        &self._id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    pub fn amount(&self) -> Option<&str> {
+        self.amount.as_deref()
+    }
+
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn need_by(&self) -> Option<&str> {
+        self.need_by.as_deref()
+    }
+
+    /// A copy with a new id, unchecked and with no need-by date, for starting
+    /// another list with the same item.
+    pub fn fresh_copy(&self) -> ListItem {
+        ListItem {
+            _id: ObjectId::new(),
+            checked: false,
+            need_by: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ListItemUpdate {
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub amount: Option<String>,
+    pub checked: Option<bool>,
+    #[serde(
+        rename(serialize = "needBy", deserialize = "needBy"),
+        default,
+        deserialize_with = "deserialize_need_by"
+    )]
+    pub need_by: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +452,8 @@ pub struct ListItemBuilder {
     name: String,
     category: Option<String>,
     amount: Option<String>,
+    checked: bool,
+    need_by: Option<String>,
 }
 
 impl ListItemBuilder {
@@ -80,6 +462,8 @@ impl ListItemBuilder {
             name: name.to_owned(),
             category: None,
             amount: None,
+            checked: false,
+            need_by: None,
         }
     }
 
@@ -93,11 +477,24 @@ impl ListItemBuilder {
         self
     }
 
+    pub fn checked<'a>(&'a mut self, checked: bool) -> &'a mut Self {
+        self.checked = checked;
+        self
+    }
+
+    pub fn need_by<'a>(&'a mut self, date: NaiveDate) -> &'a mut Self {
+        self.need_by = Some(format_need_by(date));
+        self
+    }
+
     pub fn build(&self) -> ListItem {
         ListItem {
+            _id: ObjectId::new(),
             name: self.name.clone(),
             category: self.category.clone(),
             amount: self.amount.clone(),
+            checked: self.checked,
+            need_by: self.need_by.clone(),
         }
     }
 }
@@ -105,6 +502,7 @@ impl ListItemBuilder {
 #[cfg(test)]
 mod test {
     use super::*;
+    use rocket::serde::json::from_str;
 
     #[test]
     fn category_and_amount_none_by_default() {
@@ -114,6 +512,14 @@ mod test {
         assert_eq!(item.name, "broccoli".to_owned());
         assert_eq!(item.category, None);
         assert_eq!(item.amount, None);
+        assert!(!item.checked);
+    }
+
+    #[test]
+    fn each_built_item_gets_its_own_id() {
+        let builder = ListItem::builder("broccoli");
+
+        assert_ne!(builder.build().id(), builder.build().id());
     }
 
     #[test]
@@ -185,4 +591,158 @@ mod test {
 
         assert_eq!(list.items, vec![item_1, item_2, item_3]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn items_grouped_in_category_order_with_uncategorized_last() {
+        let salmon = ListItem::builder("salmon").category("meat").build();
+        let foil = ListItem::builder("foil").build();
+        let broccoli = ListItem::builder("broccoli").category("produce").build();
+        let chips = ListItem::builder("chips").category("snacks").build();
+        let apples = ListItem::builder("apples").category("produce").build();
+        let list = List::builder("test_list".to_string(), ObjectId::new())
+            .add_item(salmon.clone())
+            .add_item(foil.clone())
+            .add_item(broccoli.clone())
+            .add_item(chips.clone())
+            .add_item(apples.clone())
+            .build();
+
+        let groups = list.items_by_category(&[
+            "produce".to_string(),
+            "bakery".to_string(),
+            "meat".to_string(),
+        ]);
+
+        assert_eq!(
+            groups,
+            vec![
+                (Some("produce".to_string()), vec![broccoli, apples]),
+                (Some("meat".to_string()), vec![salmon]),
+                (Some("snacks".to_string()), vec![chips]),
+                (None, vec![foil]),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_copies_items_into_an_unsaved_list() {
+        let item = ListItem::builder("salmon").build();
+        let mut list = List::builder("weekly".to_string(), ObjectId::new())
+            .add_item(item.clone())
+            .build();
+        list._id = Some(ObjectId::new());
+        list.archived = true;
+        list.shared_with = vec![ObjectId::new()];
+
+        let copy = list.duplicate(None);
+        assert_eq!(copy._id, None);
+        assert_eq!(copy.name, "weekly");
+        assert_eq!(copy.user_id, list.user_id);
+        assert_eq!(copy.items, vec![item]);
+        assert!(!copy.archived);
+        assert!(copy.shared_with.is_empty());
+
+        assert_eq!(list.duplicate(Some("next week")).name, "next week");
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed_items() {
+        let salmon = ListItem::builder("salmon").build();
+        let brocc = ListItem::builder("brocc").build();
+        let foil = ListItem::builder("foil").build();
+        let mut checked_salmon = salmon.clone();
+        checked_salmon.checked = true;
+        let list = List::builder("groceries".to_string(), ObjectId::new())
+            .add_item(checked_salmon.clone())
+            .add_item(brocc.clone())
+            .build();
+
+        let diff = list.diff("old name", &[salmon, foil.clone()]);
+
+        assert_eq!(
+            diff,
+            ListDiff {
+                name: Some("groceries".to_string()),
+                added: vec![brocc],
+                removed: vec![foil._id],
+                changed: vec![checked_salmon],
+            }
+        );
+        assert_eq!(list.diff("groceries", &list.items), ListDiff::default());
+    }
+
+    #[test]
+    fn need_by_dates_are_validated_and_normalized() {
+        let item: ListItem = from_str(r#"{"name": "cake", "needBy": "2026-1-5"}"#).unwrap();
+        assert_eq!(item.need_by(), Some("2026-01-05"));
+
+        let missing: ListItem = from_str(r#"{"name": "cake"}"#).unwrap();
+        assert_eq!(missing.need_by(), None);
+
+        assert!(from_str::<ListItem>(r#"{"name": "cake", "needBy": "Friday"}"#).is_err());
+    }
+
+    #[test]
+    fn fresh_copies_are_unchecked_with_new_ids() {
+        let item = ListItem::builder("salmon")
+            .category("meat")
+            .checked(true)
+            .need_by(NaiveDate::from_ymd(2021, 5, 1))
+            .build();
+
+        let copy = item.fresh_copy();
+        assert_ne!(copy.id(), item.id());
+        assert_eq!(copy.name(), "salmon");
+        assert_eq!(copy.category(), Some("meat"));
+        assert!(!copy.is_checked());
+        assert_eq!(copy.need_by(), None);
+    }
+
+    #[test]
+    fn amounts_with_the_same_unit_are_added() {
+        assert_eq!(merge_amounts(Some("2"), Some("3")), Some("5".to_string()));
+        assert_eq!(merge_amounts(Some("2 lb"), Some("1.5lb")), Some("3.5 lb".to_string()));
+        assert_eq!(merge_amounts(Some("1 Gal"), Some("1 gal")), Some("2 gal".to_string()));
+        assert_eq!(merge_amounts(Some("2 lb"), Some("1 bag")), Some("2 lb + 1 bag".to_string()));
+        assert_eq!(merge_amounts(Some("a few"), Some("2")), Some("a few + 2".to_string()));
+        assert_eq!(merge_amounts(None, Some("2")), Some("2".to_string()));
+        assert_eq!(merge_amounts(None, None), None);
+    }
+
+    #[test]
+    fn merging_an_item_folds_it_into_a_match() {
+        let milk = ListItem::builder("Whole  Milk").amount("1").checked(true).build();
+        let mut list = List::builder("groceries".to_string(), ObjectId::new())
+            .add_item(milk.clone())
+            .build();
+
+        list.merge_item(ListItem::builder("whole milk").category("dairy").amount("2").build());
+        list.merge_item(ListItem::builder("eggs").build());
+
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.items[0].id(), milk.id());
+        assert_eq!(list.items[0].name(), "Whole  Milk");
+        assert_eq!(list.items[0].amount(), Some("3"));
+        assert_eq!(list.items[0].category(), Some("dairy"));
+        assert!(!list.items[0].is_checked());
+        assert_eq!(list.items[1].name(), "eggs");
+    }
+
+    #[test]
+    fn members_are_the_owner_collaborators_and_household() {
+        let owner = ObjectId::new();
+        let collaborator = ObjectId::new();
+        let housemate = ObjectId::new();
+        let members = ListMembers {
+            _id: ObjectId::new(),
+            user_id: owner.clone(),
+            shared_with: vec![collaborator.clone()],
+            household_members: vec![owner.clone(), housemate.clone()],
+        };
+
+        assert!(members.includes(&owner));
+        assert!(members.includes(&collaborator));
+        assert!(members.includes(&housemate));
+        assert!(!members.includes(&ObjectId::new()));
+    }
+}
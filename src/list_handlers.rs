@@ -0,0 +1,1428 @@
+use rocket::{Responder, State, delete, get, http::{ContentType, Header, Status}, patch, post, put, response::content::Html, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use mongodb::bson::{oid::ObjectId, Document};
+use rand::{distributions::Alphanumeric, Rng};
+use rocket::error;
+
+use crate::access::{
+    access_status, check_list, check_list_owner, check_user, visible_store, ListAccess,
+    ListOwnerAccess, ListUser, UserAccess,
+};
+use crate::auth::AuthenticatedUser;
+use crate::caching::{last_modified, IfModifiedSince, IfNoneMatch};
+use crate::handler_util::{
+    etag, parse_fields, parse_if_match, parse_object_id, parse_order, parse_page,
+    precondition_status, repo_error_status, Count, IfMatch,
+};
+use crate::import::{parse_csv_items, parse_text_items, RowError};
+use crate::list::{
+    format_need_by, parse_need_by, List, ListDiff, ListItem, ListItemUpdate, ListSummary,
+    ListUpdate, ListView,
+};
+use crate::render::{exporter, print_html, share_text, SHARE_TEXT_MAX_LEN};
+use crate::repo::{FrequentItem, ListQuery, Repo, RepoError};
+use crate::sort::SortSpec;
+use crate::store::Store;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NewList {
+    pub name: String,
+    #[serde(rename(serialize = "userId", deserialize = "userId"))]
+    pub user_id: ObjectId,
+    #[serde(default)]
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ListConfig {
+    /// Refuse a new list named like one of the user's active lists, unless the
+    /// request passes `allow_duplicate=true`.
+    #[serde(default)]
+    pub reject_duplicate_lists: bool,
+    /// Fold an added item into one with the same name instead of duplicating it.
+    #[serde(default)]
+    pub merge_duplicate_items: bool,
+}
+
+/// Long enough that share links can't be guessed or enumerated.
+const SHARE_TOKEN_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShareLink {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Collaborator {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkAction {
+    Archive,
+    Delete,
+    Restore,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkRequest {
+    pub action: BulkAction,
+    pub ids: Vec<ObjectId>,
+}
+
+impl BulkAction {
+    /// Deleting and restoring lists is up to their owners, like
+    /// `delete_list` and `restore_list`.
+    fn is_owner_only(self) -> bool {
+        matches!(self, BulkAction::Delete | BulkAction::Restore)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOutcome {
+    Applied,
+    NotFound,
+    Forbidden,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkResult {
+    pub id: ObjectId,
+    pub outcome: BulkOutcome,
+}
+
+/// With `reject_duplicate_lists` configured, a list named like one of the
+/// user's active lists is a 409 unless `allow_duplicate` is set.
+/// `from_favorites` adds the user's favorites after the given items, skipping
+/// any already on the list. Users can only make lists for themselves.
+#[post("/lists?<allow_duplicate>&<from_favorites>", data="<list>")]
+pub async fn create_list(
+    access: ListUser,
+    list: Json<NewList>,
+    allow_duplicate: Option<bool>,
+    from_favorites: Option<bool>,
+    config: &State<ListConfig>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    check_user(&access.user, &list.user_id).map_err(access_status)?;
+    if config.reject_duplicate_lists && !allow_duplicate.unwrap_or(false) {
+        let duplicate = repo.has_active_list_named(&list.user_id, &list.name)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?;
+        if duplicate {
+            return Err(Status::Conflict);
+        }
+    }
+
+    let mut builder = List::builder(list.name.to_owned(), list.user_id.clone());
+    for item in list.items.iter() {
+        builder.add_item(item.clone());
+    }
+    if from_favorites.unwrap_or(false) {
+        let favorites = repo.get_favorites_by_user(&list.user_id)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?;
+        let favorites = favorites.iter().filter(|favorite| {
+            !list.items.iter().any(|item| item.name().eq_ignore_ascii_case(&favorite.name))
+        });
+        for favorite in favorites {
+            builder.add_item(favorite.to_list_item());
+        }
+    }
+
+    let new_list = repo.add_list(&builder.build())
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new list returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(new_list))
+}
+
+/// A list with its version as the `ETag`, for clients to send back in `If-Match`.
+#[derive(Responder)]
+pub struct TaggedList {
+    inner: Json<List>,
+    etag: Header<'static>,
+}
+
+impl TaggedList {
+    fn new(list: List) -> Self {
+        TaggedList {
+            etag: Header::new("ETag", etag(list.version)),
+            inner: Json(list),
+        }
+    }
+}
+
+/// Fields clients can pick with `?fields=`.
+const LIST_FIELDS: &[&str] = &[
+    "name", "userId", "archived", "view", "createdAt", "updatedAt", "sharedWith", "version",
+    "items", "items._id", "items.name", "items.category", "items.amount", "items.checked",
+    "items.needBy",
+];
+
+/// A `TaggedList` that also says when it last changed.
+#[derive(Responder)]
+pub struct DatedList {
+    inner: TaggedList,
+    last_modified: Header<'static>,
+}
+
+/// The client's copy of the list is current, so there's no body.
+#[derive(Responder)]
+#[response(status = 304)]
+pub struct NotModified {
+    inner: (),
+    etag: Header<'static>,
+    last_modified: Header<'static>,
+}
+
+/// A whole list, or just the fields asked for with `?fields=`.
+#[derive(Responder)]
+pub enum ListResponse {
+    Full(TaggedList),
+    Dated(DatedList),
+    NotModified(NotModified),
+    Fields(Json<Document>),
+}
+
+/// `fields` picks which fields to return, as in `name,items.name`, for clients
+/// that don't need every item. `_id` is always included and there's no ETag.
+///
+/// Whole lists come with `Last-Modified`. When the client's `If-None-Match`
+/// has the list's `ETag`, the response is a 304 without the list. Without
+/// `If-None-Match`, the same goes when `Last-Modified` is no later than
+/// `If-Modified-Since`, but that only goes down to the second.
+#[get("/lists/<id>?<fields>")]
+pub async fn get_list(
+    _access: ListAccess,
+    id: &str,
+    fields: Option<&str>,
+    if_none_match: IfNoneMatch,
+    if_modified_since: IfModifiedSince,
+    repo: &State<Repo>,
+) -> Result<ListResponse, Status> {
+    let id = parse_object_id(id)?;
+    if let Some(fields) = fields {
+        let projection = parse_fields(fields, LIST_FIELDS)?;
+        let list = repo.get_list_fields(&id, projection)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?
+            .ok_or(Status::NotFound)?;
+        return Ok(ListResponse::Fields(Json(list)));
+    }
+
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let tag = etag(list.version);
+    let is_unchanged = |updated_at| match if_none_match.0 {
+        Some(_) => if_none_match.matches(&tag),
+        None => if_modified_since.is_unchanged(updated_at),
+    };
+    let response = match list.updated_at.map(|updated_at| updated_at.0) {
+        Some(updated_at) if is_unchanged(updated_at) => {
+            ListResponse::NotModified(NotModified {
+                inner: (),
+                etag: Header::new("ETag", tag.clone()),
+                last_modified: last_modified(updated_at),
+            })
+        }
+        Some(updated_at) => ListResponse::Dated(DatedList {
+            inner: TaggedList::new(list),
+            last_modified: last_modified(updated_at),
+        }),
+        None => ListResponse::Full(TaggedList::new(list)),
+    };
+
+    Ok(response)
+}
+
+/// With `If-Match` the update only applies if the list hasn't changed since
+/// the client fetched it; otherwise it's a 412. Only the owner, with full
+/// credentials, can hand the list to someone else with `userId`; anyone else
+/// gets a 403, and a user who doesn't exist or is disabled is a 400.
+#[patch("/lists/<id>", data="<update>")]
+pub async fn update_list(
+    access: ListAccess,
+    id: &str,
+    update: Json<ListUpdate>,
+    if_match: IfMatch,
+    repo: &State<Repo>,
+) -> Result<TaggedList, Status> {
+    let id = parse_object_id(id)?;
+    let version = parse_if_match(&if_match)?;
+    if let Some(new_owner) = &update.user_id {
+        if access.user.scopes.is_some() {
+            return Err(Status::Forbidden);
+        }
+        let list = repo.get_list_by_id(&id)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?
+            .ok_or(Status::NotFound)?;
+        check_user(&access.user, &list.user_id).map_err(access_status)?;
+        let owner = repo.get_user_by_id(new_owner)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?;
+        if owner.map_or(true, |owner| owner.is_disabled()) {
+            return Err(Status::BadRequest);
+        }
+    }
+    let list = repo.update_list(&id, &update, version)
+        .await
+        .map_err(precondition_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(TaggedList::new(list))
+}
+
+/// Moves the list to the trash; it can be restored until it's purged. Only
+/// the owner, with full credentials, can do this, or restore or purge it.
+#[delete("/lists/<id>")]
+pub async fn delete_list(
+    _access: ListOwnerAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let lists_trashed = repo.trash_lists_by_ids(&[id])
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if lists_trashed == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
+
+#[get("/users/<id>/trash")]
+pub async fn get_user_trash(
+    _access: UserAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<List>>, Status> {
+    let id = parse_object_id(id)?;
+    let lists = repo.get_trashed_lists_by_user(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(lists))
+}
+
+#[post("/lists/<id>/restore")]
+pub async fn restore_list(
+    _access: ListOwnerAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.restore_list(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+/// Permanently deletes a list. Only lists already in the trash can be purged.
+#[delete("/lists/<id>/purge")]
+pub async fn purge_list(
+    _access: ListOwnerAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    if list.deleted_at.is_none() {
+        return Err(Status::Conflict);
+    }
+
+    let lists_deleted = repo.delete_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if lists_deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
+
+/// With `dry_run` the per-id outcomes are reported without writing anything.
+/// Lists the caller isn't a member of are left alone and reported as forbidden.
+/// So are lists they don't own for `delete` and `restore`, and every list for
+/// those two with scoped credentials. `restore` takes lists out of the archive
+/// and the trash; the other actions treat trashed lists as not found.
+#[post("/lists/bulk?<dry_run>", data="<request>")]
+pub async fn bulk_update_lists(
+    access: ListUser,
+    request: Json<BulkRequest>,
+    dry_run: Option<bool>,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<BulkResult>>, Status> {
+    let include_trashed = request.action == BulkAction::Restore;
+    let owner_only = request.action.is_owner_only();
+    if owner_only && access.user.scopes.is_some() {
+        let results = request.ids
+            .iter()
+            .map(|id| BulkResult { id: id.clone(), outcome: BulkOutcome::Forbidden })
+            .collect();
+        return Ok(Json(results));
+    }
+    let members = repo.get_list_members_by_ids(&request.ids, include_trashed)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    let results: Vec<BulkResult> = request.ids
+        .iter()
+        .map(|id| BulkResult {
+            id: id.clone(),
+            outcome: match members.iter().find(|members| &members._id == id) {
+                Some(members) if owner_only && members.user_id != access.user.id => {
+                    BulkOutcome::Forbidden
+                }
+                Some(members) if members.includes(&access.user.id) => BulkOutcome::Applied,
+                Some(_) => BulkOutcome::Forbidden,
+                None => BulkOutcome::NotFound,
+            },
+        })
+        .collect();
+    let applied: Vec<ObjectId> = results
+        .iter()
+        .filter(|result| result.outcome == BulkOutcome::Applied)
+        .map(|result| result.id.clone())
+        .collect();
+
+    if !dry_run.unwrap_or(false) {
+        let result = match request.action {
+            BulkAction::Archive => repo.set_lists_archived(&applied, true).await,
+            BulkAction::Restore => repo.restore_lists_by_ids(&applied).await,
+            BulkAction::Delete => repo.trash_lists_by_ids(&applied).await,
+        };
+        result.map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    }
+
+    Ok(Json(results))
+}
+
+#[get("/lists/<id>/share-text?<store_id>")]
+pub async fn get_share_text(
+    access: ListAccess,
+    id: &str,
+    store_id: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<String, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let categories = store_categories(&access.user, store_id, repo).await?;
+
+    Ok(share_text(&list, &categories, SHARE_TEXT_MAX_LEN))
+}
+
+/// The category order of the given store, or no order without one.
+async fn store_categories(
+    user: &AuthenticatedUser,
+    store_id: Option<&str>,
+    repo: &Repo,
+) -> Result<Vec<String>, Status> {
+    let store_id = match store_id {
+        Some(store_id) => parse_object_id(store_id)?,
+        None => return Ok(Vec::new()),
+    };
+    let store = visible_store(repo, user, &store_id).await.map_err(access_status)?;
+
+    Ok(store.categories)
+}
+
+/// `format` is `markdown` (the default) or `text`.
+#[get("/lists/<id>/export?<format>&<store_id>")]
+pub async fn export_list(
+    access: ListAccess,
+    id: &str,
+    format: Option<&str>,
+    store_id: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<(ContentType, String), Status> {
+    let exporter = exporter(format.unwrap_or("markdown")).ok_or(Status::BadRequest)?;
+    let id = parse_object_id(id)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    let categories = store_categories(&access.user, store_id, repo).await?;
+
+    Ok((exporter.content_type(), exporter.export(&list, &categories)))
+}
+
+/// An explicit `archived` filter wins; otherwise archived lists are hidden
+/// unless `include_archived` is set.
+fn parse_list_query(
+    archived: Option<bool>,
+    include_archived: Option<bool>,
+    sort: Option<&str>,
+    order: Option<&str>,
+) -> Result<ListQuery, Status> {
+    let archived = match (archived, include_archived) {
+        (Some(archived), _) => Some(archived),
+        (None, Some(true)) => None,
+        (None, _) => Some(false),
+    };
+    let sort = SortSpec::parse(sort, parse_order(order)?).map_err(|err| {
+        error!("{:?}", err);
+        Status::BadRequest
+    })?;
+
+    Ok(ListQuery { archived, sort })
+}
+
+/// `sort` is a comma separated list of `name`, `created_at` or `updated_at`,
+/// each optionally followed by `:asc` or `:desc`, as in `updated_at:desc,name`.
+/// `order` is the direction for keys that don't give one. The `after` cursor
+/// follows insertion order, so it can't be combined with `sort`.
+///
+/// Lists that fail to deserialize are logged and skipped so one bad document
+/// doesn't hide the rest of a user's lists; cursor errors still fail the request.
+/// `fields` works as it does for a single list. `summary` returns each list
+/// without its items but with how many it has; it can't be combined with `fields`.
+#[get(
+    "/users/<id>/lists?<limit>&<after>&<archived>&<include_archived>&<sort>&<order>&<fields>&<summary>"
+)]
+pub async fn get_user_lists(
+    _access: UserAccess,
+    id: &str,
+    limit: Option<i64>,
+    after: Option<&str>,
+    archived: Option<bool>,
+    include_archived: Option<bool>,
+    sort: Option<&str>,
+    order: Option<&str>,
+    fields: Option<&str>,
+    summary: Option<bool>,
+    repo: &State<Repo>,
+) -> Result<UserListsResponse, Status> {
+    let id = parse_object_id(id)?;
+    let page = parse_page(limit, after)?;
+    let query = parse_list_query(archived, include_archived, sort, order)?;
+    if !query.sort.is_empty() && page.after.is_some() {
+        return Err(Status::BadRequest);
+    }
+    let summary = summary.unwrap_or(false);
+    if summary && fields.is_some() {
+        return Err(Status::BadRequest);
+    }
+    if summary {
+        let summaries = repo.get_list_summaries_by_user(&id, &query, &page)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?;
+        return Ok(UserListsResponse::Summaries(Json(summaries)));
+    }
+    if let Some(fields) = fields {
+        let projection = parse_fields(fields, LIST_FIELDS)?;
+        let lists = repo.get_list_fields_by_user(&id, &query, &page, projection)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?;
+        return Ok(UserListsResponse::Fields(Json(lists)));
+    }
+
+    let results = repo.get_lists_by_user(&id, &query, &page)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    let mut lists = Vec::new();
+    for result in results {
+        match result {
+            Ok(list) => lists.push(list),
+            Err(RepoError::BsonDe(err)) => error!("Skipping list for user {:?}: {:?}", id, err),
+            Err(err) => {
+                error!("{:?}", err);
+                return Err(Status::InternalServerError);
+            }
+        }
+    }
+
+    Ok(UserListsResponse::Full(Json(lists)))
+}
+
+#[derive(Responder)]
+pub enum UserListsResponse {
+    Full(Json<Vec<List>>),
+    Fields(Json<Vec<Document>>),
+    Summaries(Json<Vec<ListSummary>>),
+}
+
+/// How many lists `get_user_lists` would return across every page, with the
+/// same `archived` and `include_archived` filters.
+#[get("/users/<id>/lists/count?<archived>&<include_archived>")]
+pub async fn count_user_lists(
+    _access: UserAccess,
+    id: &str,
+    archived: Option<bool>,
+    include_archived: Option<bool>,
+    repo: &State<Repo>,
+) -> Result<Json<Count>, Status> {
+    let id = parse_object_id(id)?;
+    let query = parse_list_query(archived, include_archived, None, None)?;
+    let count = repo.count_lists_by_user(&id, query.archived)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(Count { count }))
+}
+
+#[get("/lists/<id>/items/count")]
+pub async fn count_list_items(
+    _access: ListAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Count>, Status> {
+    let id = parse_object_id(id)?;
+    let count = repo.count_list_items(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(Count { count }))
+}
+
+/// With `merge_duplicate_items` configured, adding "milk" to a list with
+/// "Milk" on it updates that item rather than adding a second one.
+#[post("/lists/<id>/items", data="<item>")]
+pub async fn add_list_item(
+    _access: ListAccess,
+    id: &str,
+    item: Json<ListItem>,
+    config: &State<ListConfig>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.add_list_item(&id, &item, config.merge_duplicate_items)
+        .await
+        .map_err(repo_error_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+#[delete("/lists/<list_id>/items/<item_id>")]
+pub async fn remove_list_item(
+    _access: ListAccess,
+    list_id: &str,
+    item_id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let list_id = parse_object_id(list_id)?;
+    let item_id = parse_object_id(item_id)?;
+    let list = repo.remove_list_item(&list_id, &item_id)
+        .await
+        .map_err(repo_error_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+#[patch("/lists/<list_id>/items/<item_id>", data="<update>")]
+pub async fn update_list_item(
+    _access: ListAccess,
+    list_id: &str,
+    item_id: &str,
+    update: Json<ListItemUpdate>,
+    if_match: IfMatch,
+    repo: &State<Repo>,
+) -> Result<TaggedList, Status> {
+    let list_id = parse_object_id(list_id)?;
+    let item_id = parse_object_id(item_id)?;
+    let version = parse_if_match(&if_match)?;
+    let list = repo.update_list_item(&list_id, &item_id, &update, version)
+        .await
+        .map_err(precondition_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(TaggedList::new(list))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PrintBatchRequest {
+    pub ids: Vec<ObjectId>,
+}
+
+#[post("/lists/print-batch", data="<request>")]
+pub async fn print_lists(
+    access: ListUser,
+    request: Json<PrintBatchRequest>,
+    repo: &State<Repo>,
+) -> Result<Html<String>, Status> {
+    let mut lists = Vec::new();
+    for id in request.ids.iter() {
+        check_list(repo, &access.user, id).await.map_err(access_status)?;
+        let list = repo.get_list_by_id(id)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?
+            .ok_or(Status::NotFound)?;
+        lists.push(list);
+    }
+
+    Ok(Html(print_html(&lists)))
+}
+
+#[post("/lists/<id>/items/bulk", data="<items>")]
+pub async fn add_list_items(
+    _access: ListAccess,
+    id: &str,
+    items: Json<Vec<ListItem>>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.add_list_items(&id, &items)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+/// `items` are the items parsed from the upload, so clients can show what was
+/// understood; `list` is the list after they were appended.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportResult {
+    pub list: List,
+    pub items: Vec<ListItem>,
+    pub errors: Vec<RowError>,
+}
+
+async fn append_imported_items(
+    id: &str,
+    items: Vec<ListItem>,
+    errors: Vec<RowError>,
+    dry_run: bool,
+    repo: &Repo,
+) -> Result<Json<ImportResult>, Status> {
+    let id = parse_object_id(id)?;
+    let list = if items.is_empty() || dry_run {
+        repo.get_list_by_id(&id).await
+    } else {
+        repo.add_list_items(&id, &items).await
+    };
+    let list = list
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(ImportResult { list, items, errors }))
+}
+
+/// Appends the parsed rows in one write. Rows that don't parse are reported
+/// in `errors` and left out; they don't fail the import.
+#[post("/lists/<id>/import?<dry_run>", format="text/csv", data="<csv>", rank = 1)]
+pub async fn import_list_items(
+    _access: ListAccess,
+    id: &str,
+    dry_run: Option<bool>,
+    csv: String,
+    repo: &State<Repo>,
+) -> Result<Json<ImportResult>, Status> {
+    let (items, errors) = parse_csv_items(&csv);
+    append_imported_items(id, items, errors, dry_run.unwrap_or(false), repo).await
+}
+
+/// Imports a list pasted as plain text, one item per line. With `dry_run` the
+/// parsed items are returned for confirmation without being saved.
+#[post("/lists/<id>/import?<dry_run>", format="text/plain", data="<text>", rank = 2)]
+pub async fn import_pasted_items(
+    _access: ListAccess,
+    id: &str,
+    dry_run: Option<bool>,
+    text: String,
+    repo: &State<Repo>,
+) -> Result<Json<ImportResult>, Status> {
+    let items = parse_text_items(&text);
+    append_imported_items(id, items, Vec::new(), dry_run.unwrap_or(false), repo).await
+}
+
+#[post("/lists/<id>/clear-checked")]
+pub async fn clear_checked_items(
+    _access: ListAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.clear_checked_items(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+/// The copy belongs to the caller, even when they're copying a list shared
+/// with them.
+#[post("/lists/<id>/clone?<name>")]
+pub async fn clone_list(
+    access: ListAccess,
+    id: &str,
+    name: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let mut copy = list.duplicate(name);
+    copy.user_id = access.user.id;
+    let new_list = repo.add_list(&copy)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new list returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(new_list))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ListSnapshot {
+    pub name: String,
+    pub items: Vec<ListItem>,
+}
+
+#[post("/lists/<id>/diff", data="<snapshot>")]
+pub async fn diff_list(
+    _access: ListAccess,
+    id: &str,
+    snapshot: Json<ListSnapshot>,
+    repo: &State<Repo>,
+) -> Result<Json<ListDiff>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list.diff(&snapshot.name, &snapshot.items)))
+}
+
+async fn set_item_checked(
+    list_id: &str,
+    item_id: &str,
+    checked: bool,
+    repo: &Repo,
+) -> Result<Json<List>, Status> {
+    let list_id = parse_object_id(list_id)?;
+    let item_id = parse_object_id(item_id)?;
+    let list = repo.set_list_item_checked(&list_id, &item_id, checked)
+        .await
+        .map_err(repo_error_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+#[post("/lists/<list_id>/items/<item_id>/check")]
+pub async fn check_list_item(
+    _access: ListAccess,
+    list_id: &str,
+    item_id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    set_item_checked(list_id, item_id, true, repo).await
+}
+
+#[post("/lists/<list_id>/items/<item_id>/uncheck")]
+pub async fn uncheck_list_item(
+    _access: ListAccess,
+    list_id: &str,
+    item_id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    set_item_checked(list_id, item_id, false, repo).await
+}
+
+async fn set_list_archived(id: &str, archived: bool, repo: &Repo) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.set_list_archived(&id, archived)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+#[post("/lists/<id>/archive")]
+pub async fn archive_list(
+    _access: ListAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    set_list_archived(id, true, repo).await
+}
+
+#[post("/lists/<id>/unarchive")]
+pub async fn unarchive_list(
+    _access: ListAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    set_list_archived(id, false, repo).await
+}
+
+fn new_share_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SHARE_TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Creates a public share token for the list, replacing any previous one.
+/// Only the owner, with full credentials, can publish or revoke the link.
+#[post("/lists/<id>/share-link")]
+pub async fn create_share_link(
+    _access: ListOwnerAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<ShareLink>, Status> {
+    let id = parse_object_id(id)?;
+    let token = new_share_token();
+    repo.set_list_share_token(&id, Some(&token))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(ShareLink { token }))
+}
+
+#[delete("/lists/<id>/share-link")]
+pub async fn revoke_share_link(
+    _access: ListOwnerAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    repo.set_list_share_token(&id, None)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Status::NoContent)
+}
+
+/// Read-only view of a list for anyone holding its share token.
+#[get("/shared/<token>")]
+pub async fn get_shared_list(token: &str, repo: &State<Repo>) -> Result<Json<ListSnapshot>, Status> {
+    let list = repo.get_list_by_share_token(token)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(ListSnapshot { name: list.name, items: list.items }))
+}
+
+async fn find_collaborator(email: &str, repo: &Repo) -> Result<ObjectId, Status> {
+    repo.get_user_by_email(email)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .and_then(|user| user._id)
+        .ok_or(Status::NotFound)
+}
+
+/// Shares the list with the user registered under `email`. Only the owner,
+/// with full credentials, can share the list, and sharing with themselves is
+/// rejected.
+#[post("/lists/<id>/collaborators", data="<collaborator>")]
+pub async fn add_list_collaborator(
+    _access: ListOwnerAccess,
+    id: &str,
+    collaborator: Json<Collaborator>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let user_id = find_collaborator(&collaborator.email, repo).await?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    if list.user_id == user_id {
+        return Err(Status::BadRequest);
+    }
+
+    let list = repo.add_list_collaborator(&id, &user_id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+/// The owner, with full credentials, can stop sharing the list with anyone;
+/// collaborators can only take themselves off it.
+#[delete("/lists/<id>/collaborators?<email>")]
+pub async fn remove_list_collaborator(
+    auth: AuthenticatedUser,
+    _access: ListAccess,
+    id: &str,
+    email: &str,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let user_id = find_collaborator(email, repo).await?;
+    if user_id != auth.id {
+        check_list_owner(repo, &auth, &id).await.map_err(access_status)?;
+    }
+    let list = repo.remove_list_collaborator(&id, &user_id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+#[put("/lists/<id>/view", data="<view>")]
+pub async fn update_list_view(
+    _access: ListAccess,
+    id: &str,
+    view: Json<ListView>,
+    if_match: IfMatch,
+    repo: &State<Repo>,
+) -> Result<TaggedList, Status> {
+    let id = parse_object_id(id)?;
+    let version = parse_if_match(&if_match)?;
+    let list = repo.update_list_view(&id, &view, version)
+        .await
+        .map_err(precondition_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(TaggedList::new(list))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CategoryGroup {
+    pub category: Option<String>,
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SortedList {
+    #[serde(rename(serialize = "listId", deserialize = "listId"))]
+    pub list_id: ObjectId,
+    #[serde(rename(serialize = "storeId", deserialize = "storeId"))]
+    pub store_id: ObjectId,
+    pub name: String,
+    pub groups: Vec<CategoryGroup>,
+    /// "Did you forget?" staples the store carries that aren't on the list.
+    #[serde(default)]
+    pub suggestions: Vec<FrequentItem>,
+}
+
+/// How many of the user's most frequent items are considered for suggestions.
+const STAPLE_CANDIDATES: i64 = 50;
+/// An item has to have been on this many lists to count as a staple.
+const MIN_STAPLE_COUNT: i64 = 2;
+const MAX_SUGGESTIONS: usize = 5;
+
+/// The user's staples in categories the store carries, leaving out anything
+/// already on the list or dismissed for this trip. Purchases aren't recorded
+/// per store, so "staples" are the items the user lists most often.
+fn forgotten_staples(list: &List, store: &Store, frequent: Vec<FrequentItem>) -> Vec<FrequentItem> {
+    frequent
+        .into_iter()
+        .filter(|staple| staple.count >= MIN_STAPLE_COUNT)
+        .filter(|staple| {
+            staple.category.as_ref().map_or(false, |category| store.categories.contains(category))
+        })
+        .filter(|staple| {
+            let name = staple.name.to_lowercase();
+            !list.items.iter().any(|item| item.name().to_lowercase() == name)
+                && !list.dismissed_suggestions.contains(&name)
+        })
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// Groups the list's items in the store's walking order, uncategorized items last,
+/// along with staples the caller may have forgotten. Staples come from the
+/// caller's own lists, so collaborators never see the owner's history.
+#[get("/lists/<id>/sorted?<store>")]
+pub async fn get_sorted_list(
+    access: ListAccess,
+    id: &str,
+    store: &str,
+    repo: &State<Repo>,
+) -> Result<Json<SortedList>, Status> {
+    let id = parse_object_id(id)?;
+    let store_id = parse_object_id(store)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    let store = visible_store(repo, &access.user, &store_id).await.map_err(access_status)?;
+
+    let frequent = repo.get_frequent_items(&access.user.id, STAPLE_CANDIDATES)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    let suggestions = forgotten_staples(&list, &store, frequent);
+
+    let groups = list.items_by_category(&store.categories)
+        .into_iter()
+        .map(|(category, items)| CategoryGroup { category, items })
+        .collect();
+
+    Ok(Json(SortedList { list_id: id, store_id, name: list.name, groups, suggestions }))
+}
+
+/// Hides a "did you forget?" suggestion for the rest of this list's trip.
+#[post("/lists/<id>/suggestions/dismissed", data="<name>")]
+pub async fn dismiss_suggestion(
+    _access: ListAccess,
+    id: &str,
+    name: Json<String>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    if name.trim().is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let list = repo.dismiss_list_suggestion(&id, &name)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(list))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemMatch {
+    #[serde(rename(serialize = "listId", deserialize = "listId"))]
+    pub list_id: ObjectId,
+    #[serde(rename(serialize = "listName", deserialize = "listName"))]
+    pub list_name: String,
+    pub item: ListItem,
+}
+
+#[get("/users/<id>/items/search?<q>")]
+pub async fn search_user_items(
+    _access: UserAccess,
+    id: &str,
+    q: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<ItemMatch>>, Status> {
+    let id = parse_object_id(id)?;
+    let query = q.trim().to_lowercase();
+    if query.is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let lists = repo.search_lists_by_item_name(&id, &query)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    let mut matches = Vec::new();
+    for list in lists {
+        let list_id = list._id.clone().ok_or_else(|| {
+            error!("List returned from mongo had no _id");
+            Status::InternalServerError
+        })?;
+        for item in list.items.iter().filter(|item| item.name().to_lowercase().contains(&query)) {
+            matches.push(ItemMatch {
+                list_id: list_id.clone(),
+                list_name: list.name.clone(),
+                item: item.clone(),
+            });
+        }
+    }
+
+    Ok(Json(matches))
+}
+
+/// Unchecked items with a need-by date, soonest first. `before` (YYYY-MM-DD)
+/// limits them to items needed on or before that day.
+#[get("/users/<id>/items/due?<before>")]
+pub async fn get_due_items(
+    _access: UserAccess,
+    id: &str,
+    before: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<ItemMatch>>, Status> {
+    let id = parse_object_id(id)?;
+    let before = before
+        .map(|before| {
+            parse_need_by(before).map(format_need_by).map_err(|err| {
+                error!("{:?}", err);
+                Status::BadRequest
+            })
+        })
+        .transpose()?;
+
+    let lists = repo.get_lists_with_items_due(&id, before.as_deref())
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    let mut due = Vec::new();
+    for list in lists {
+        let list_id = list._id.clone().ok_or_else(|| {
+            error!("List returned from mongo had no _id");
+            Status::InternalServerError
+        })?;
+        let items = list.items.iter().filter(|item| {
+            !item.is_checked()
+                && item.need_by().map_or(false, |need_by| {
+                    before.as_deref().map_or(true, |before| need_by <= before)
+                })
+        });
+        for item in items {
+            due.push(ItemMatch {
+                list_id: list_id.clone(),
+                list_name: list.name.clone(),
+                item: item.clone(),
+            });
+        }
+    }
+    due.sort_by(|a, b| a.item.need_by().cmp(&b.item.need_by()));
+
+    Ok(Json(due))
+}
+
+/// Suggestions returned when the client doesn't ask for a number.
+const DEFAULT_FREQUENT_ITEMS: i64 = 10;
+const MAX_FREQUENT_ITEMS: i64 = 50;
+
+/// The items the user adds most often, for one-tap re-adds on a new list.
+#[get("/users/<id>/items/frequent?<limit>")]
+pub async fn get_frequent_items(
+    _access: UserAccess,
+    id: &str,
+    limit: Option<i64>,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<FrequentItem>>, Status> {
+    let id = parse_object_id(id)?;
+    if limit.map_or(false, |limit| limit <= 0) {
+        return Err(Status::BadRequest);
+    }
+    let limit = limit.unwrap_or(DEFAULT_FREQUENT_ITEMS).min(MAX_FREQUENT_ITEMS);
+
+    let items = repo.get_frequent_items(&id, limit)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(items))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sort::{ListSort, SortKey};
+
+    #[test]
+    fn list_query_parses_sort_and_order() {
+        assert_eq!(
+            parse_list_query(None, None, None, None),
+            Ok(ListQuery { archived: Some(false), ..ListQuery::default() })
+        );
+        assert_eq!(
+            parse_list_query(Some(false), None, Some("updated_at"), Some("desc")),
+            Ok(ListQuery {
+                archived: Some(false),
+                sort: SortSpec {
+                    keys: vec![SortKey { field: ListSort::UpdatedAt, descending: true }],
+                    descending: true,
+                },
+            })
+        );
+        assert_eq!(
+            parse_list_query(None, None, Some("updated_at:desc,name"), None)
+                .map(|query| query.sort.keys),
+            Ok(vec![
+                SortKey { field: ListSort::UpdatedAt, descending: true },
+                SortKey { field: ListSort::Name, descending: false },
+            ])
+        );
+        assert_eq!(parse_list_query(None, None, Some("created"), None), Err(Status::BadRequest));
+        assert_eq!(parse_list_query(None, None, Some("name"), Some("up")), Err(Status::BadRequest));
+    }
+
+    #[test]
+    fn share_tokens_are_long_and_unique() {
+        let token = new_share_token();
+        assert_eq!(token.len(), SHARE_TOKEN_LEN);
+        assert!(token.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(token, new_share_token());
+    }
+
+    #[test]
+    fn list_query_hides_archived_by_default() {
+        assert_eq!(parse_list_query(None, Some(true), None, None), Ok(ListQuery::default()));
+        assert_eq!(
+            parse_list_query(None, Some(false), None, None).map(|query| query.archived),
+            Ok(Some(false))
+        );
+        assert_eq!(
+            parse_list_query(Some(true), Some(false), None, None).map(|query| query.archived),
+            Ok(Some(true))
+        );
+    }
+
+    #[test]
+    fn forgotten_staples_skip_listed_dismissed_and_unstocked_items() {
+        let staple = |name: &str, category: &str, count: i64| FrequentItem {
+            name: name.to_string(),
+            category: Some(category.to_string()),
+            count,
+        };
+        let mut list = List::builder("groceries".to_string(), ObjectId::new())
+            .add_item(ListItem::builder("Milk").build())
+            .build();
+        list.dismissed_suggestions = vec!["butter".to_string()];
+        let store = Store::with_categories("corner store", &["dairy".to_string()]).unwrap();
+
+        let suggestions = forgotten_staples(
+            &list,
+            &store,
+            vec![
+                staple("milk", "dairy", 5),
+                staple("eggs", "dairy", 4),
+                staple("butter", "dairy", 3),
+                staple("nails", "hardware", 3),
+                staple("kefir", "dairy", 1),
+            ],
+        );
+        assert_eq!(suggestions, vec![staple("eggs", "dairy", 4)]);
+    }
+}
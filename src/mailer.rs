@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex};
+
+use rocket::{fairing::AdHoc, info};
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MailError {
+    #[error("could not send mail: {0}")]
+    Send(String),
+}
+
+/// Sends mail to users. Handlers take the managed `Arc<dyn Mailer>`.
+#[rocket::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Writes mail to the log instead of sending it, for running without a mail
+/// server. Anyone who can read the log can read the mail.
+pub struct LogMailer;
+
+#[rocket::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        info!("Mail to {} ({}):\n{}", mail.to, mail.subject, mail.body);
+        Ok(())
+    }
+}
+
+/// Keeps mail in memory, where tests can read it back.
+#[derive(Debug, Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Mail>>,
+}
+
+impl MemoryMailer {
+    pub fn sent(&self) -> Vec<Mail> {
+        self.sent.lock().expect("mailer lock poisoned").clone()
+    }
+}
+
+#[rocket::async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        self.sent.lock().expect("mailer lock poisoned").push(mail);
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailerKind {
+    Log,
+    Memory,
+}
+
+impl Default for MailerKind {
+    fn default() -> Self {
+        MailerKind::Log
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct MailerConfig {
+    #[serde(default)]
+    pub mailer: MailerKind,
+}
+
+/// Manages the configured `Arc<dyn Mailer>`. The memory mailer is managed as
+/// an `Arc<MemoryMailer>` too, so its mail can be read back.
+pub fn mailer() -> AdHoc {
+    AdHoc::on_ignite("Mailer", |rocket| async {
+        let kind = rocket.state::<MailerConfig>().map_or(MailerKind::Log, |config| config.mailer);
+        match kind {
+            MailerKind::Log => rocket.manage(Arc::new(LogMailer) as Arc<dyn Mailer>),
+            MailerKind::Memory => {
+                let outbox = Arc::new(MemoryMailer::default());
+                rocket.manage(outbox.clone()).manage(outbox as Arc<dyn Mailer>)
+            }
+        }
+    })
+}
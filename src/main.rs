@@ -1,11 +1,93 @@
-use rocket::{error, fairing::AdHoc, launch, routes};
+use rocket::{error, fairing::AdHoc, info, launch, routes};
 use serde::Deserialize;
-use crate::{repo::Repo, user_handlers::create_user};
+use crate::{
+    admin_handlers::{
+        disable_user, enable_user, get_maintenance, get_usage, remove_list, remove_store,
+        remove_template, search_users, set_maintenance,
+    },
+    api_key_handlers::{create_api_key, get_api_keys, revoke_api_key},
+    auth::AuthConfig,
+    auth_handlers::{
+        confirm_password_reset, create_scoped_token, delete_account, get_current_user, login,
+        logout, register, request_password_reset,
+    },
+    caching::CacheControl,
+    cors::{Cors, CorsConfig},
+    faults::{fault, FaultConfig, FaultInjection},
+    favorite_handlers::{add_favorite, delete_favorite, get_favorites},
+    household_handlers::{
+        clear_list_household, clear_store_household, create_household, get_household,
+        get_user_households, remove_household_member, set_list_household, set_store_household,
+    },
+    info::{build_info, get_info},
+    invitation_handlers::{accept_invitation, invite_to_household, invite_to_list},
+    join_codes::{create_join_code, join_list, JoinAttempts},
+    list_handlers::{
+        add_list_collaborator, add_list_item, add_list_items, archive_list, bulk_update_lists,
+        check_list_item, clear_checked_items, clone_list, count_list_items, count_user_lists,
+        create_list, create_share_link, delete_list, diff_list, dismiss_suggestion, export_list,
+        get_due_items, get_frequent_items, get_list, get_share_text, get_shared_list,
+        get_sorted_list, get_user_lists, get_user_trash, import_list_items, import_pasted_items,
+        print_lists, purge_list, remove_list_collaborator, remove_list_item, restore_list,
+        revoke_share_link, search_user_items, unarchive_list, uncheck_list_item, update_list,
+        update_list_item, update_list_view, ListConfig,
+    },
+    mailer::{mailer, MailerConfig},
+    maintenance::{maintenance, maintenance_switch, MaintenanceConfig, MaintenanceMode},
+    provisioning::SignupConfig,
+    repo::Repo,
+    scheduler::{recurring_lists, SchedulerConfig},
+    status::{get_status, StatusTracker},
+    store_handlers::{
+        add_store_category, create_store, delete_store, get_store, get_stores, import_store_layouts,
+        reorder_store_categories,
+    },
+    template_handlers::{
+        clear_template_recurrence, create_list_from_template, delete_template, get_template,
+        get_user_templates, save_list_as_template, set_template_recurrence,
+    },
+    user_handlers::{
+        count_users, create_user, export_user, get_user_by_email, get_user_stats, get_users,
+        update_user,
+    },
+};
 use thiserror::Error;
 
+mod access;
+mod account_export;
+mod admin_handlers;
+mod api_key;
+mod api_key_handlers;
+mod auth;
+mod auth_handlers;
+mod caching;
+mod cors;
+mod faults;
+mod favorite;
+mod favorite_handlers;
+mod handler_util;
+mod household;
+mod household_handlers;
+mod import;
+mod info;
+mod invitation;
+mod invitation_handlers;
+mod join_codes;
 mod list;
+mod list_handlers;
+mod mailer;
+mod maintenance;
+mod provisioning;
+mod recurrence;
+mod render;
 mod repo;
+mod scheduler;
+mod sort;
+mod status;
 mod store;
+mod store_handlers;
+mod template;
+mod template_handlers;
 mod user;
 mod user_handlers;
 
@@ -24,8 +106,124 @@ enum StartUpError {
 #[launch]
 fn rocket() -> _ {
     rocket::build()
-        .mount("/api", routes![create_user])
+        .mount("/api", routes![
+            create_user,
+            update_user,
+            create_list,
+            get_list,
+            update_list,
+            delete_list,
+            bulk_update_lists,
+            get_share_text,
+            get_user_lists,
+            add_list_item,
+            remove_list_item,
+            update_list_item,
+            create_store,
+            get_store,
+            get_stores,
+            print_lists,
+            delete_store,
+            reorder_store_categories,
+            add_list_items,
+            clear_checked_items,
+            clone_list,
+            diff_list,
+            check_list_item,
+            uncheck_list_item,
+            update_list_view,
+            get_sorted_list,
+            search_user_items,
+            import_store_layouts,
+            get_users,
+            get_user_by_email,
+            archive_list,
+            unarchive_list,
+            maintenance,
+            get_user_trash,
+            restore_list,
+            purge_list,
+            add_list_collaborator,
+            remove_list_collaborator,
+            create_share_link,
+            revoke_share_link,
+            get_shared_list,
+            fault,
+            add_store_category,
+            export_list,
+            import_list_items,
+            get_status,
+            import_pasted_items,
+            get_due_items,
+            get_info,
+            create_join_code,
+            join_list,
+            get_user_stats,
+            export_user,
+            get_frequent_items,
+            add_favorite,
+            delete_favorite,
+            get_favorites,
+            save_list_as_template,
+            get_template,
+            get_user_templates,
+            create_list_from_template,
+            delete_template,
+            dismiss_suggestion,
+            set_template_recurrence,
+            clear_template_recurrence,
+            count_user_lists,
+            count_list_items,
+            count_users,
+            register,
+            login,
+            get_current_user,
+            delete_account,
+            logout,
+            create_scoped_token,
+            create_household,
+            get_household,
+            get_user_households,
+            remove_household_member,
+            set_list_household,
+            clear_list_household,
+            set_store_household,
+            clear_store_household,
+            invite_to_household,
+            invite_to_list,
+            accept_invitation,
+            create_api_key,
+            get_api_keys,
+            revoke_api_key,
+            request_password_reset,
+            confirm_password_reset,
+            search_users,
+            disable_user,
+            enable_user,
+            get_usage,
+            remove_list,
+            remove_store,
+            remove_template,
+            get_maintenance,
+            set_maintenance,
+        ])
+        .manage(StatusTracker::default())
+        .manage(JoinAttempts::default())
+        .attach(build_info())
         .attach(AdHoc::config::<DbConfig>())
+        .attach(AdHoc::config::<SignupConfig>())
+        .attach(AdHoc::config::<AuthConfig>())
+        .attach(AdHoc::config::<MailerConfig>())
+        .attach(mailer())
+        .attach(AdHoc::config::<ListConfig>())
+        .attach(AdHoc::config::<MaintenanceConfig>())
+        .attach(maintenance_switch())
+        .attach(MaintenanceMode)
+        .attach(AdHoc::config::<FaultConfig>())
+        .attach(FaultInjection)
+        .attach(CacheControl)
+        .attach(AdHoc::config::<CorsConfig>())
+        .attach(Cors)
         .attach(AdHoc::try_on_ignite("Mongo", |rocket| async {
             let db_config = match rocket.state::<DbConfig>() {
                 Some(config) => config,
@@ -41,23 +239,76 @@ fn rocket() -> _ {
                     return Err(rocket);
                 }
             };
+            if let Err(e) = repo.ensure_indexes().await {
+                error!("Could not create Mongo indexes: {:?}", e);
+                return Err(rocket);
+            }
+            match repo.backfill_item_ids().await {
+                Ok(0) => {}
+                Ok(fixed) => info!("Gave ids to the items of {} list(s)", fixed),
+                Err(e) => {
+                    error!("Could not backfill list item ids: {:?}", e);
+                    return Err(rocket);
+                }
+            }
 
             Ok(rocket.manage(repo))
         }))
+        .attach(AdHoc::config::<SchedulerConfig>())
+        .attach(recurring_lists())
 }
 
 #[cfg(test)]
 mod test {
     use std::env;
+    use std::sync::Arc;
 
     use crate::DbConfig;
     use crate::repo::Collections;
+    use crate::list::{
+        GroupBy, JoinCode, List, ListItem, ListItemUpdate, ListSummary, ListUpdate, ListView,
+        SortDirection,
+    };
+    use crate::list_handlers::{
+        BulkAction, BulkOutcome, BulkRequest, BulkResult, CategoryGroup, Collaborator, NewList,
+        ImportResult, ItemMatch, ListSnapshot, ShareLink, SortedList,
+    };
+    use crate::admin_handlers::{Account, MaintenanceState};
+    use crate::api_key_handlers::{ApiKeyInfo, CreatedApiKey, NewApiKey};
+    use crate::auth::{hash_password, issue_token, AuthConfig, Scope};
+    use crate::auth_handlers::{
+        Credentials, DeletionRequest, LoginResponse, Registration, ResetConfirmation,
+        ResetRequest, ScopedToken, TokenRequest,
+    };
+    use crate::caching::http_date;
+    use crate::favorite::Favorite;
+    use crate::favorite_handlers::NewFavorite;
+    use crate::handler_util::Count;
+    use crate::household::Household;
+    use crate::household_handlers::{HouseholdAssignment, NewHousehold};
+    use crate::info::BuildInfo;
+    use crate::invitation::InvitationTarget;
+    use crate::invitation_handlers::{AcceptedInvitation, InvitationAcceptance, NewInvitation};
+    use crate::join_codes::JoinRequest;
+    use crate::mailer::MemoryMailer;
+    use crate::maintenance::MaintenanceMessage;
+    use crate::recurrence::{Recurrence, Weekday};
+    use crate::repo::{
+        AccountDeletion, DeletionMode, FrequentItem, ListQuery, Page, Repo, Usage, UserStats,
+    };
+    use crate::status::{Health, StatusReport};
+    use crate::scheduler::materialize_due_lists;
+    use crate::store::Store;
+    use crate::store_handlers::NewStore;
+    use crate::template::Template;
+    use crate::user::{Role, User as RepoUser};
     use crate::user_handlers::User;
 
     use super::rocket;
-    use mongodb::bson::doc;
+    use mongodb::bson::{doc, oid::ObjectId, Document};
+    use chrono::{NaiveDate, Utc};
     use rocket::local::blocking::Client;
-    use rocket::http::Status;
+    use rocket::http::{ContentType, Header, Status};
     use color_eyre::Result;
 
     fn run_in_test() {
@@ -67,23 +318,60 @@ mod test {
     pub async fn clean_up_db(db_config: &DbConfig) -> Result<()> {
         let client = mongodb::Client::with_uri_str(&db_config.database_url)
             .await?.database(&db_config.database_name);
+        client.collection(&Collections::ApiKeys.to_string()).delete_many(doc! {}, None).await?;
+        client.collection(&Collections::Favorites.to_string()).delete_many(doc! {}, None).await?;
+        client.collection(&Collections::Households.to_string()).delete_many(doc! {}, None).await?;
+        client.collection(&Collections::Invitations.to_string()).delete_many(doc! {}, None).await?;
         client.collection(&Collections::Lists.to_string()).delete_many(doc! {}, None).await?;
         client.collection(&Collections::Stores.to_string()).delete_many(doc! {}, None).await?;
+        client.collection(&Collections::Templates.to_string()).delete_many(doc! {}, None).await?;
         client.collection(&Collections::Users.to_string()).delete_many(doc! {}, None).await?;
 
         Ok(())
     }
 
+    /// Adds a user and returns their id with an `Authorization` header that
+    /// signs them in.
+    async fn sign_in(
+        client: &Client,
+        repo: &Repo,
+        email: &str,
+    ) -> Result<(ObjectId, Header<'static>)> {
+        sign_in_as(client, repo, RepoUser::new(email.to_string(), email.to_string())).await
+    }
+
+    async fn sign_in_as(
+        client: &Client,
+        repo: &Repo,
+        user: RepoUser,
+    ) -> Result<(ObjectId, Header<'static>)> {
+        let user_id = repo
+            .add_user(&user)
+            .await?
+            .and_then(|user| user._id)
+            .expect("new user has an _id");
+        let config = client.rocket().state::<AuthConfig>().expect("auth config is managed");
+        let (token, _) = issue_token(config, &user_id, 0, Utc::now()).expect("token is issued");
+        Ok((user_id, Header::new("Authorization", format!("Bearer {}", token))))
+    }
+
     #[tokio::test]
-    async fn can_add_user() -> Result<()> {
+    async fn admins_can_add_users() -> Result<()> {
         run_in_test();
 
         let rocket = rocket().ignite().await.unwrap();
         let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
         let client = Client::tracked(rocket).expect("valid rocket instant");
+        let mut admin = RepoUser::new("admin".to_string(), "admin@bar.com".to_string());
+        admin.role = Role::Admin;
+        let (_, admin_auth) = sign_in_as(&client, &repo, admin).await?;
+        let (_, auth) = sign_in(&client, &repo, "someone@bar.com").await?;
+
         let response = client
             .post("/api/users")
-            .json(&User::new(None, "foo".to_string(), "foo@bar.com".to_string()))
+            .json(&User::new(None, "foo".to_string(), " Foo@Bar.com ".to_string()))
+            .header(admin_auth.clone())
             .dispatch();
 
         assert_eq!(response.status(), Status::Ok);
@@ -91,6 +379,3117 @@ mod test {
         assert_eq!(json.name, "foo");
         assert_eq!(json.email, "foo@bar.com");
 
+        let taken = client
+            .post("/api/users")
+            .json(&User::new(None, "bar".to_string(), "foo@bar.com".to_string()))
+            .header(admin_auth.clone())
+            .dispatch();
+        assert_eq!(taken.status(), Status::Conflict);
+
+        let not_admin = client
+            .post("/api/users")
+            .json(&User::new(None, "squatter".to_string(), "squat@bar.com".to_string()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(not_admin.status(), Status::Forbidden);
+        let signed_out = client
+            .post("/api/users")
+            .json(&User::new(None, "squatter".to_string(), "squat@bar.com".to_string()))
+            .dispatch();
+        assert_eq!(signed_out.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_update_user() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "foo@bar.com").await?;
+        let (other_id, _) = sign_in(&client, &repo, "other@bar.com").await?;
+
+        let response = client
+            .put(format!("/api/users/{}", user_id.to_hex()))
+            .json(&User::new(None, "bar".to_string(), "bar@baz.com".to_string()))
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json = response.into_json::<User>().unwrap();
+        assert_eq!(json.id, Some(user_id));
+        assert_eq!(json.name, "bar");
+        assert_eq!(json.email, "bar@baz.com");
+
+        let taken = client
+            .put(format!("/api/users/{}", user_id.to_hex()))
+            .json(&User::new(None, "bar".to_string(), "Other@bar.com".to_string()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(taken.status(), Status::Conflict);
+
+        let someone_else = client
+            .put(format!("/api/users/{}", other_id.to_hex()))
+            .json(&User::new(None, "bar".to_string(), "bar@baz.com".to_string()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(someone_else.status(), Status::Forbidden);
+
+        let signed_out = client
+            .put(format!("/api/users/{}", other_id.to_hex()))
+            .json(&User::new(None, "bar".to_string(), "bar@baz.com".to_string()))
+            .dispatch();
+        assert_eq!(signed_out.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn deleting_an_account_removes_its_lists() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "foo@bar.com").await?;
+        repo.add_list(&List::builder("first".to_string(), user_id.clone()).build()).await?;
+        repo.add_list(&List::builder("second".to_string(), user_id.clone()).build()).await?;
+        let owned_lists = repo
+            .get_lists_by_user(&user_id, &ListQuery::default(), &Page::default())
+            .await?
+            .len() as i64;
+
+        let deletion = DeletionRequest { mode: DeletionMode::Delete, password: None };
+
+        let response = client
+            .delete("/api/auth/me")
+            .json(&deletion)
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let deleted = response.into_json::<AccountDeletion>().unwrap();
+        assert_eq!(deleted.lists, owned_lists);
+        assert_eq!(deleted.kept_lists, 0);
+        assert_eq!(repo.get_user_by_id(&user_id).await?, None);
+        let remaining = repo
+            .get_lists_by_user(&user_id, &ListQuery::default(), &Page::default())
+            .await?;
+        assert_eq!(0, remaining.len());
+
+        let deleted = client.delete("/api/auth/me").json(&deletion).header(auth.clone()).dispatch();
+        assert_eq!(deleted.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn users_can_anonymize_their_account() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let password_hash = hash_password("correct horse battery").unwrap();
+        let leaving = RepoUser::with_password(
+            "leaving".to_string(),
+            "leaving@bar.com".to_string(),
+            password_hash,
+        );
+        let (user_id, auth) = sign_in_as(&client, &repo, leaving).await?;
+        let (friend_id, friend_auth) = sign_in(&client, &repo, "friend@bar.com").await?;
+        let shared = repo
+            .add_list(&List::builder("shared".to_string(), user_id.clone()).build())
+            .await?
+            .and_then(|list| list._id)
+            .expect("new list has an _id");
+        repo.add_list_collaborator(&shared, &friend_id).await?;
+
+        let wrong_password = client
+            .delete("/api/auth/me")
+            .json(&DeletionRequest {
+                mode: DeletionMode::Anonymize,
+                password: Some("wrong".to_string()),
+            })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(wrong_password.status(), Status::Unauthorized);
+
+        let response = client
+            .delete("/api/auth/me")
+            .json(&DeletionRequest {
+                mode: DeletionMode::Anonymize,
+                password: Some("correct horse battery".to_string()),
+            })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let deleted = response.into_json::<AccountDeletion>().unwrap();
+        assert_eq!(deleted.kept_lists, 1);
+
+        let signed_out = client.get("/api/auth/me").header(auth.clone()).dispatch();
+        assert_eq!(signed_out.status(), Status::Unauthorized);
+        let user = repo.get_user_by_id(&user_id).await?.expect("anonymized user is kept");
+        assert!(!user.email.contains("leaving"));
+        let list = client
+            .get(format!("/api/lists/{}", shared.to_hex()))
+            .header(friend_auth.clone())
+            .dispatch();
+        assert_eq!(list.status(), Status::Ok);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_create_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let item = ListItem::builder("salmon")
+            .category("meat")
+            .amount("2lb")
+            .build();
+        let response = client
+            .post("/api/lists")
+            .json(&NewList {
+                name: "groceries".to_string(),
+                user_id: user_id.clone(),
+                items: vec![item.clone()],
+            })
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json = response.into_json::<List>().unwrap();
+        assert!(json._id.is_some());
+        assert_eq!(json.name, "groceries");
+        assert_eq!(json.user_id, user_id);
+        assert_eq!(json.items, vec![item]);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_get_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let item = ListItem::builder("salmon").category("meat").build();
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).add_item(item).build())
+            .await?
+            .unwrap();
+
+        let response = client
+            .get(format!("/api/lists/{}", list._id.clone().unwrap().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap(), list);
+
+        let signed_out = client
+            .get(format!("/api/lists/{}", list._id.clone().unwrap().to_hex()))
+            .dispatch();
+        assert_eq!(signed_out.status(), Status::Unauthorized);
+
+        let (_, stranger) = sign_in(&client, &repo, "stranger@bar.com").await?;
+        let forbidden = client
+            .get(format!("/api/lists/{}", list._id.clone().unwrap().to_hex()))
+            .header(stranger)
+            .dispatch();
+        assert_eq!(forbidden.status(), Status::Forbidden);
+
+        let missing = client
+            .get(format!("/api/lists/{}", ObjectId::new().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_delete_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .delete(format!("/api/lists/{}", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        let trashed = repo.get_list_by_id(&list_id).await?.unwrap();
+        assert!(trashed.deleted_at.is_some());
+
+        let missing = client
+            .delete(format!("/api/lists/{}", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_archive_lists_in_bulk() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+        let missing_id = ObjectId::new();
+        let other = repo
+            .add_list(&List::builder("theirs".to_string(), ObjectId::new()).build())
+            .await?
+            .unwrap();
+        let other_id = other._id.clone().unwrap();
+
+        let response = client
+            .post("/api/lists/bulk")
+            .json(&BulkRequest {
+                action: BulkAction::Archive,
+                ids: vec![list_id.clone(), missing_id.clone(), other_id.clone()],
+            })
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<Vec<BulkResult>>().unwrap(),
+            vec![
+                BulkResult { id: list_id.clone(), outcome: BulkOutcome::Applied },
+                BulkResult { id: missing_id, outcome: BulkOutcome::NotFound },
+                BulkResult { id: other_id.clone(), outcome: BulkOutcome::Forbidden },
+            ]
+        );
+        assert!(repo.get_list_by_id(&list_id).await?.unwrap().archived);
+        assert!(!repo.get_list_by_id(&other_id).await?.unwrap().archived);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_rename_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+
+        let response = client
+            .patch(format!("/api/lists/{}", list._id.clone().unwrap().to_hex()))
+            .json(&ListUpdate { name: Some("hardware".to_string()), user_id: None })
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json = response.into_json::<List>().unwrap();
+        assert_eq!(json.name, "hardware");
+        assert_eq!(json.user_id, list.user_id);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn only_owners_can_hand_their_lists_on() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let (collaborator_id, collaborator_auth) =
+            sign_in(&client, &repo, "collaborator@bar.com").await?;
+        let list_id = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .and_then(|list| list._id)
+            .unwrap();
+        repo.add_list_collaborator(&list_id, &collaborator_id).await?;
+        let path = format!("/api/lists/{}", list_id.to_hex());
+
+        let taken = client
+            .patch(path.clone())
+            .json(&ListUpdate { name: None, user_id: Some(collaborator_id.clone()) })
+            .header(collaborator_auth.clone())
+            .dispatch();
+        assert_eq!(taken.status(), Status::Forbidden);
+
+        let nobody = client
+            .patch(path.clone())
+            .json(&ListUpdate { name: None, user_id: Some(ObjectId::new()) })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(nobody.status(), Status::BadRequest);
+
+        let response = client
+            .patch(path.clone())
+            .json(&ListUpdate { name: None, user_id: Some(collaborator_id.clone()) })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let json = response.into_json::<List>().unwrap();
+        assert_eq!(json.user_id, collaborator_id);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_get_lists_for_user() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        repo.add_list(&List::builder("other".to_string(), user_id.clone()).build()).await?;
+
+        let response = client
+            .get(format!("/api/users/{}/lists", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<Vec<List>>().unwrap(), vec![list]);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_add_item_to_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let item = ListItem::builder("salmon").category("meat").build();
+
+        let response = client
+            .post(format!("/api/lists/{}/items", list._id.clone().unwrap().to_hex()))
+            .json(&item)
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().items, vec![item.clone()]);
+
+        let missing = client
+            .post(format!("/api/lists/{}/items", ObjectId::new().to_hex()))
+            .json(&item)
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_remove_item_from_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let salmon = ListItem::builder("salmon").build();
+        let brocc = ListItem::builder("brocc").build();
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(salmon.clone())
+                    .add_item(brocc.clone())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .delete(format!("/api/lists/{}/items/{}", list_id.to_hex(), salmon.id().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().items, vec![brocc]);
+
+        let missing = client
+            .delete(format!("/api/lists/{}/items/{}", list_id.to_hex(), salmon.id().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_edit_item_in_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let salmon = ListItem::builder("salmn").build();
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(salmon.clone())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+
+        let response = client
+            .patch(format!(
+                "/api/lists/{}/items/{}",
+                list._id.clone().unwrap().to_hex(),
+                salmon.id().to_hex()
+            ))
+            .json(&ListItemUpdate {
+                name: Some("salmon".to_string()),
+                category: Some("Meat".to_string()),
+                amount: None,
+                checked: None,
+                need_by: None,
+            })
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let items = response.into_json::<List>().unwrap().items;
+        assert_eq!(items[0].name(), "salmon");
+        assert_eq!(items[0].category(), Some("meat"));
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_create_store() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let response = client
+            .post("/api/stores")
+            .json(&NewStore {
+                name: "corner store".to_string(),
+                categories: vec!["Produce".to_string(), "MEAT".to_string()],
+            })
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json = response.into_json::<Store>().unwrap();
+        assert!(json._id.is_some());
+        assert_eq!(json.name, "corner store");
+        assert_eq!(json.categories, vec!["produce", "meat"]);
+        assert_eq!(json.user_id, Some(user_id));
+
+        let unnamed = client
+            .post("/api/stores")
+            .json(&NewStore { name: "  ".to_string(), categories: Vec::new() })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(unnamed.status(), Status::BadRequest);
+
         clean_up_db(&db_config).await
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn can_get_store() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (_, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let mut store = Store::new("corner store");
+        store.add_category("produce");
+        let store = repo.add_store(&store).await?.unwrap();
+        let private = repo
+            .add_store(&Store::new("my store").owned_by(ObjectId::new()))
+            .await?
+            .unwrap();
+
+        let response = client
+            .get(format!("/api/stores/{}", store._id.clone().unwrap().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<Store>().unwrap(), store);
+
+        let forbidden = client
+            .get(format!("/api/stores/{}", private._id.clone().unwrap().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(forbidden.status(), Status::Forbidden);
+
+        let missing = client
+            .get(format!("/api/stores/{}", ObjectId::new().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        let malformed = client.get("/api/stores/not-an-id").header(auth.clone()).dispatch();
+        assert_eq!(malformed.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_delete_store() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let store = repo.add_store(&Store::new("corner store").owned_by(user_id)).await?.unwrap();
+        let store_id = store._id.clone().unwrap();
+        let shared = repo.add_store(&Store::new("chain store")).await?.unwrap();
+
+        let read_only = client
+            .delete(format!("/api/stores/{}", shared._id.clone().unwrap().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(read_only.status(), Status::Forbidden);
+
+        let response = client
+            .delete(format!("/api/stores/{}", store_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(repo.get_store_by_id(&store_id).await?, None);
+
+        let missing = client
+            .delete(format!("/api/stores/{}", store_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_reorder_store_categories() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let mut store = Store::new("corner store").owned_by(user_id);
+        store.add_category("meat");
+        store.add_category("produce");
+        let store = repo.add_store(&store).await?.unwrap();
+        let uri = format!("/api/stores/{}/categories", store._id.clone().unwrap().to_hex());
+
+        let response = client
+            .put(uri.clone())
+            .json(&vec!["produce", "meat"])
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<Store>().unwrap().categories, vec!["produce", "meat"]);
+
+        let duplicated = client
+            .put(uri)
+            .json(&vec!["produce", "produce", "meat"])
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(duplicated.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_add_items_in_bulk() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let items = vec![
+            ListItem::builder("salmon").build(),
+            ListItem::builder("brocc").build(),
+        ];
+
+        let response = client
+            .post(format!("/api/lists/{}/items/bulk", list._id.clone().unwrap().to_hex()))
+            .json(&items)
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().items, items);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn bulk_dry_run_does_not_write() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .post("/api/lists/bulk?dry_run=true")
+            .json(&BulkRequest { action: BulkAction::Delete, ids: vec![list_id.clone()] })
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<Vec<BulkResult>>().unwrap(),
+            vec![BulkResult { id: list_id.clone(), outcome: BulkOutcome::Applied }]
+        );
+        assert_eq!(repo.get_list_by_id(&list_id).await?, Some(list));
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn bulk_deleted_lists_can_be_restored() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list_id = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .and_then(|list| list._id)
+            .unwrap();
+        let applied = vec![BulkResult { id: list_id.clone(), outcome: BulkOutcome::Applied }];
+
+        for action in [BulkAction::Delete, BulkAction::Restore].iter() {
+            let response = client
+                .post("/api/lists/bulk")
+                .json(&BulkRequest { action: *action, ids: vec![list_id.clone()] })
+                .header(auth.clone())
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.into_json::<Vec<BulkResult>>().unwrap(), applied);
+        }
+        let list = repo.get_list_by_id(&list_id).await?.unwrap();
+        assert_eq!(list.deleted_at, None);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_clone_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(
+                &List::builder("weekly".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("salmon").build())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+
+        let response = client
+            .post(format!("/api/lists/{}/clone?name=next%20week", list._id.clone().unwrap().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json = response.into_json::<List>().unwrap();
+        assert_ne!(json._id, list._id);
+        assert_eq!(json.name, "next week");
+        assert_eq!(json.items, list.items);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_check_item_while_shopping() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let salmon = ListItem::builder("salmon").build();
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(salmon.clone())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+        let uri = format!(
+            "/api/lists/{}/items/{}",
+            list._id.clone().unwrap().to_hex(),
+            salmon.id().to_hex()
+        );
+
+        let checked = client.post(format!("{}/check", uri)).header(auth.clone()).dispatch();
+        assert_eq!(checked.status(), Status::Ok);
+        assert!(checked.into_json::<List>().unwrap().items[0].is_checked());
+
+        let unchecked = client.post(format!("{}/uncheck", uri)).header(auth.clone()).dispatch();
+        assert_eq!(unchecked.status(), Status::Ok);
+        assert!(!unchecked.into_json::<List>().unwrap().items[0].is_checked());
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_update_list_view_settings() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        assert_eq!(list.view, ListView::default());
+        let view = ListView {
+            group_by: GroupBy::Aisle,
+            sort_direction: SortDirection::Desc,
+            hide_checked: true,
+        };
+
+        let response = client
+            .put(format!("/api/lists/{}/view", list._id.clone().unwrap().to_hex()))
+            .json(&view)
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().view, view);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_sort_list_by_store_layout() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let salmon = ListItem::builder("salmon").category("meat").build();
+        let foil = ListItem::builder("foil").build();
+        let brocc = ListItem::builder("brocc").category("produce").build();
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(salmon.clone())
+                    .add_item(foil.clone())
+                    .add_item(brocc.clone())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+        let mut store = Store::new("corner store");
+        store.add_category("produce");
+        store.add_category("meat");
+        let store = repo.add_store(&store).await?.unwrap();
+
+        let response = client
+            .get(format!(
+                "/api/lists/{}/sorted?store={}",
+                list._id.clone().unwrap().to_hex(),
+                store._id.clone().unwrap().to_hex()
+            ))
+            .header(auth.clone())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<SortedList>().unwrap().groups,
+            vec![
+                CategoryGroup { category: Some("produce".to_string()), items: vec![brocc] },
+                CategoryGroup { category: Some("meat".to_string()), items: vec![salmon] },
+                CategoryGroup { category: None, items: vec![foil] },
+            ]
+        );
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_import_store_layouts() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let layouts = vec![NewStore {
+            name: "corner store".to_string(),
+            categories: vec!["Produce".to_string(), "meat".to_string()],
+        }];
+
+        let response = client
+            .post("/api/stores/import")
+            .json(&layouts)
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let first = response.into_json::<Vec<Store>>().unwrap();
+        assert_eq!(first[0].categories, vec!["produce", "meat"]);
+        assert_eq!(first[0].user_id, Some(user_id));
+
+        let response = client
+            .post("/api/stores/import")
+            .json(&layouts)
+            .header(auth.clone())
+            .dispatch();
+        let second = response.into_json::<Vec<Store>>().unwrap();
+        assert_eq!(second[0]._id, first[0]._id);
+
+        let invalid = client
+            .post("/api/stores/import")
+            .json(&vec![NewStore {
+                name: "corner store".to_string(),
+                categories: vec!["meat".to_string(), "Meat".to_string()],
+            }])
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(invalid.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_page_through_user_lists() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let mut lists = Vec::new();
+        for name in ["first", "second", "third"].iter() {
+            let list = repo
+                .add_list(&List::builder(name.to_string(), user_id.clone()).build())
+                .await?
+                .unwrap();
+            lists.push(list);
+        }
+
+        let first_page = client
+            .get(format!("/api/users/{}/lists?limit=2", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap();
+        assert_eq!(first_page, lists[..2].to_vec());
+
+        let second_page = client
+            .get(format!(
+                "/api/users/{}/lists?limit=2&after={}",
+                user_id.to_hex(),
+                first_page[1]._id.clone().unwrap().to_hex()
+            ))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap();
+        assert_eq!(second_page, lists[2..].to_vec());
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_look_up_user_by_email() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        repo.add_user(&RepoUser::new("foo".to_string(), "lookup@bar.com".to_string())).await?;
+
+        let response = client
+            .get("/api/users?email=owner%40bar.com")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<User>().unwrap().id, Some(user_id));
+
+        let someone_else = client
+            .get("/api/users?email=lookup%40bar.com")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(someone_else.status(), Status::Forbidden);
+
+        let missing = client
+            .get("/api/users?email=nobody%40bar.com")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn archived_lists_are_hidden_unless_requested() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let kept = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let old = repo
+            .add_list(&List::builder("last week".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let old_id = old._id.clone().unwrap();
+
+        let response = client
+            .post(format!("/api/lists/{}/archive", old_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_json::<List>().unwrap().archived);
+
+        let visible = client
+            .get(format!("/api/users/{}/lists", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap();
+        assert_eq!(visible, vec![kept.clone()]);
+
+        let all = client
+            .get(format!("/api/users/{}/lists?include_archived=true", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap();
+        assert_eq!(all.len(), 2);
+
+        let response = client
+            .post(format!("/api/lists/{}/unarchive", old_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(!response.into_json::<List>().unwrap().archived);
+
+        let missing = client
+            .post(format!("/api/lists/{}/archive", ObjectId::new().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn maintenance_mode_rejects_writes_but_serves_reads() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket()
+            .configure(rocket::Config::figment().merge(("maintenance", true)))
+            .ignite()
+            .await
+            .unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .get(format!("/api/lists/{}", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .delete(format!("/api/lists/{}", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert!(response.into_json::<MaintenanceMessage>().is_some());
+        assert!(repo.get_list_by_id(&list_id).await?.is_some());
+
+        let response = client
+            .post("/api/auth/login")
+            .json(&Credentials {
+                email: "owner@bar.com".to_string(),
+                password: "wrong".to_string(),
+            })
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client.post("/api/auth/logout").dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+
+        let mut admin = RepoUser::new("admin".to_string(), "admin@bar.com".to_string());
+        admin.role = Role::Admin;
+        let (_, admin_auth) = sign_in_as(&client, &repo, admin).await?;
+        let off = MaintenanceState { maintenance: false };
+        let response = client
+            .put("/api/admin/maintenance")
+            .json(&off)
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client
+            .put("/api/admin/maintenance")
+            .json(&off)
+            .header(admin_auth.clone())
+            .dispatch();
+        assert_eq!(response.into_json::<MaintenanceState>(), Some(off));
+        let response = client
+            .delete(format!("/api/lists/{}", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn trashed_lists_can_be_restored_or_purged() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .delete(format!("/api/lists/{}/purge", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+
+        client.delete(format!("/api/lists/{}", list_id.to_hex())).header(auth.clone()).dispatch();
+        let lists = client
+            .get(format!("/api/users/{}/lists", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap();
+        assert!(lists.is_empty());
+        let trash = client
+            .get(format!("/api/users/{}/trash", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap();
+        assert_eq!(trash.len(), 1);
+
+        let response = client
+            .post(format!("/api/lists/{}/restore", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().deleted_at, None);
+
+        client.delete(format!("/api/lists/{}", list_id.to_hex())).header(auth.clone()).dispatch();
+        let response = client
+            .delete(format!("/api/lists/{}/purge", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(repo.get_list_by_id(&list_id).await?, None);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn only_owners_can_trash_restore_or_purge_lists() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let (collaborator_id, collaborator) = sign_in(&client, &repo, "friend@bar.com").await?;
+        let list_id = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .and_then(|list| list._id)
+            .unwrap();
+        repo.add_list_collaborator(&list_id, &collaborator_id).await?;
+        let list_url = format!("/api/lists/{}", list_id.to_hex());
+
+        let response = client.delete(list_url.clone()).header(collaborator.clone()).dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client
+            .post("/api/lists/bulk")
+            .json(&BulkRequest { action: BulkAction::Delete, ids: vec![list_id.clone()] })
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(
+            response.into_json::<Vec<BulkResult>>().unwrap(),
+            vec![BulkResult { id: list_id.clone(), outcome: BulkOutcome::Forbidden }]
+        );
+        assert_eq!(repo.get_list_by_id(&list_id).await?.unwrap().deleted_at, None);
+
+        client.delete(list_url.clone()).header(auth.clone()).dispatch();
+        let response = client
+            .post(format!("{}/restore", list_url))
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client
+            .delete(format!("{}/purge", list_url))
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        assert!(repo.get_list_by_id(&list_id).await?.is_some());
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_share_list_by_email() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let (collaborator_id, collaborator) = sign_in(&client, &repo, "shared@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .post(format!("/api/lists/{}/collaborators", list_id.to_hex()))
+            .json(&Collaborator { email: "shared@bar.com".to_string() })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().shared_with, vec![collaborator_id.clone()]);
+
+        let lists = client
+            .get(format!("/api/users/{}/lists", collaborator_id.to_hex()))
+            .header(collaborator.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap();
+        assert!(lists.iter().any(|shared| shared._id == list._id));
+        let shared = client
+            .get(format!("/api/lists/{}", list_id.to_hex()))
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(shared.status(), Status::Ok);
+
+        let unknown = client
+            .post(format!("/api/lists/{}/collaborators", list_id.to_hex()))
+            .json(&Collaborator { email: "nobody@bar.com".to_string() })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(unknown.status(), Status::NotFound);
+
+        let response = client
+            .delete(format!("/api/lists/{}/collaborators?email=shared%40bar.com", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_json::<List>().unwrap().shared_with.is_empty());
+        let unshared = client
+            .get(format!("/api/lists/{}", list_id.to_hex()))
+            .header(collaborator)
+            .dispatch();
+        assert_eq!(unshared.status(), Status::Forbidden);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn collaborators_can_only_take_themselves_off_lists() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, _) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let (collaborator_id, collaborator) = sign_in(&client, &repo, "shared@bar.com").await?;
+        let (other_id, _) = sign_in(&client, &repo, "other@bar.com").await?;
+        sign_in(&client, &repo, "stranger@bar.com").await?;
+        let list_id = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .and_then(|list| list._id)
+            .unwrap();
+        repo.add_list_collaborator(&list_id, &collaborator_id).await?;
+        repo.add_list_collaborator(&list_id, &other_id).await?;
+        let collaborators_url = format!("/api/lists/{}/collaborators", list_id.to_hex());
+
+        let response = client
+            .post(collaborators_url.clone())
+            .json(&Collaborator { email: "stranger@bar.com".to_string() })
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client
+            .delete(format!("{}?email=other%40bar.com", collaborators_url))
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .delete(format!("{}?email=shared%40bar.com", collaborators_url))
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().shared_with, vec![other_id]);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn share_links_serve_a_read_only_view_until_revoked() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("salmon").build())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+        let (collaborator_id, collaborator) = sign_in(&client, &repo, "friend@bar.com").await?;
+        repo.add_list_collaborator(&list_id, &collaborator_id).await?;
+
+        let response = client
+            .post(format!("/api/lists/{}/share-link", list_id.to_hex()))
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let link = client
+            .post(format!("/api/lists/{}/share-link", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<ShareLink>()
+            .unwrap();
+
+        let response = client.get(format!("/api/shared/{}", link.token)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<ListSnapshot>().unwrap(),
+            ListSnapshot { name: list.name.clone(), items: list.items.clone() }
+        );
+
+        let response = client
+            .delete(format!("/api/lists/{}/share-link", list_id.to_hex()))
+            .header(collaborator.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client
+            .delete(format!("/api/lists/{}/share-link", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        let revoked = client.get(format!("/api/shared/{}", link.token)).dispatch();
+        assert_eq!(revoked.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_add_store_category() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let store = repo.add_store(&Store::new("corner shop").owned_by(user_id)).await?.unwrap();
+        let store_id = store._id.clone().unwrap();
+
+        let response = client
+            .post(format!("/api/stores/{}/categories", store_id.to_hex()))
+            .json(&"Produce")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<Store>().unwrap().categories, vec!["produce".to_string()]);
+
+        let blank = client
+            .post(format!("/api/stores/{}/categories", store_id.to_hex()))
+            .json(&" ")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(blank.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_export_list_as_markdown() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("salmon").category("meat").build())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .get(format!("/api/lists/{}/export", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::new("text", "markdown")));
+        assert_eq!(
+            response.into_string().unwrap(),
+            "# groceries\n\n## meat\n\n- [ ] salmon\n"
+        );
+
+        let unknown = client
+            .get(format!("/api/lists/{}/export?format=pdf", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(unknown.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_import_items_from_csv() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .post(format!("/api/lists/{}/import", list_id.to_hex()))
+            .header(ContentType::CSV)
+            .body("name,category,amount\nsalmon,meat,2lb\n,produce,\n")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let result = response.into_json::<ImportResult>().unwrap();
+        assert_eq!(result.list.items.len(), 1);
+        assert_eq!(result.list.items[0].name(), "salmon");
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].row, 2);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn status_reports_api_and_mongo() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+
+        let response = client.get("/api/status").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let report = response.into_json::<StatusReport>().unwrap();
+        let names: Vec<&str> = report
+            .components
+            .iter()
+            .map(|component| component.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["api", "mongo"]);
+        assert!(report.components.iter().all(|component| component.status == Health::Up));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_preview_and_import_pasted_text() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let preview = client
+            .post(format!("/api/lists/{}/import?dry_run=true", list_id.to_hex()))
+            .header(ContentType::Plain)
+            .body("2lb salmon\n- foil\n")
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<ImportResult>()
+            .unwrap();
+        assert_eq!(preview.items.len(), 2);
+        assert_eq!(preview.items[0].amount(), Some("2lb"));
+        assert!(preview.list.items.is_empty());
+
+        let imported = client
+            .post(format!("/api/lists/{}/import", list_id.to_hex()))
+            .header(ContentType::Plain)
+            .body("2lb salmon\n- foil\n")
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<ImportResult>()
+            .unwrap();
+        let names: Vec<&str> = imported.list.items.iter().map(|item| item.name()).collect();
+        assert_eq!(names, vec!["salmon", "foil"]);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn stale_if_match_is_rejected_with_412() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+
+        let response = client
+            .get(format!("/api/lists/{}", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+
+        let rename = ListUpdate { name: Some("renamed".to_string()), user_id: None };
+        let response = client
+            .patch(format!("/api/lists/{}", list_id.to_hex()))
+            .header(Header::new("If-Match", etag.clone()))
+            .json(&rename)
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_ne!(response.headers().get_one("ETag").unwrap(), etag);
+
+        let stale = client
+            .patch(format!("/api/lists/{}", list_id.to_hex()))
+            .header(Header::new("If-Match", etag))
+            .json(&ListUpdate { name: Some("clobbered".to_string()), user_id: None })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(stale.status(), Status::PreconditionFailed);
+        assert_eq!(repo.get_list_by_id(&list_id).await?.unwrap().name, "renamed");
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_list_items_due_by_a_date() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let date = |day| NaiveDate::from_ymd(2026, 10, day);
+        repo.add_list(
+            &List::builder("party".to_string(), user_id.clone())
+                .add_item(ListItem::builder("candles").need_by(date(20)).build())
+                .add_item(ListItem::builder("cake").need_by(date(16)).build())
+                .add_item(ListItem::builder("balloons").need_by(date(10)).checked(true).build())
+                .add_item(ListItem::builder("napkins").build())
+                .build(),
+        )
+        .await?;
+
+        let due = client
+            .get(format!("/api/users/{}/items/due?before=2026-10-17", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<ItemMatch>>()
+            .unwrap();
+        let names: Vec<&str> = due.iter().map(|due| due.item.name()).collect();
+        assert_eq!(names, vec!["cake"]);
+
+        let all = client
+            .get(format!("/api/users/{}/items/due", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<ItemMatch>>()
+            .unwrap();
+        let names: Vec<&str> = all.iter().map(|due| due.item.name()).collect();
+        assert_eq!(names, vec!["cake", "candles"]);
+
+        let bad = client
+            .get(format!("/api/users/{}/items/due?before=friday", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(bad.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn info_reports_version_and_profile() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+
+        let response = client.get("/api/info").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let info = response.into_json::<BuildInfo>().unwrap();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.git_sha, env!("GIT_SHA"));
+        assert_eq!(info.profile, "test");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_join_list_with_code() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.clone().unwrap();
+        let (joiner, joiner_auth) = sign_in(&client, &repo, "joiner@bar.com").await?;
+
+        let join_code = client
+            .post(format!("/api/lists/{}/join-code", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<JoinCode>()
+            .unwrap();
+
+        let on_behalf = client
+            .post("/api/join")
+            .json(&JoinRequest { code: join_code.code.clone(), user_id: joiner.clone() })
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(on_behalf.status(), Status::Forbidden);
+
+        let response = client
+            .post("/api/join")
+            .json(&JoinRequest { code: join_code.code.to_lowercase(), user_id: joiner.clone() })
+            .header(joiner_auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<List>().unwrap().shared_with, vec![joiner.clone()]);
+        let minted = client
+            .post(format!("/api/lists/{}/join-code", list_id.to_hex()))
+            .header(joiner_auth.clone())
+            .dispatch();
+        assert_eq!(minted.status(), Status::Forbidden);
+
+        let wrong = client
+            .post("/api/join")
+            .json(&JoinRequest { code: "GROK-0000".to_string(), user_id: joiner })
+            .header(joiner_auth)
+            .dispatch();
+        assert_eq!(wrong.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn duplicate_lists_can_be_rejected() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket()
+            .configure(rocket::Config::figment().merge(("reject_duplicate_lists", true)))
+            .ignite()
+            .await
+            .unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let new_list = NewList {
+            name: "Groceries".to_string(),
+            user_id,
+            items: vec![],
+        };
+
+        let response = client.post("/api/lists").json(&new_list).header(auth.clone()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let duplicate = NewList { name: "groceries".to_string(), ..new_list.clone() };
+        let response = client.post("/api/lists").json(&duplicate).header(auth.clone()).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+
+        let response = client
+            .post("/api/lists?allow_duplicate=true")
+            .json(&duplicate)
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_get_user_stats() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let mut list = List::builder("groceries".to_string(), user_id.clone());
+        list.add_item(ListItem::builder("milk").category("dairy").build())
+            .add_item(ListItem::builder("eggs").build());
+        repo.add_list(&list.build()).await?;
+
+        let stats = client
+            .get(format!("/api/users/{}/stats", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<UserStats>()
+            .unwrap();
+        assert_eq!(stats.lists, 1);
+        assert_eq!(stats.items, 2);
+        assert_eq!(stats.average_items_per_list, 2.0);
+        assert_eq!(stats.top_categories.len(), 1);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn users_can_download_their_data() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let (_, other_auth) = sign_in(&client, &repo, "other@bar.com").await?;
+        let mut list = List::builder("groceries".to_string(), user_id.clone());
+        list.add_item(ListItem::builder("milk").build());
+        repo.add_list(&list.build()).await?;
+        repo.add_list(&List::builder("hardware".to_string(), user_id.clone()).build()).await?;
+
+        let response = client
+            .get(format!("/api/users/{}/export", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let disposition = response.headers().get_one("Content-Disposition").unwrap();
+        assert!(disposition.starts_with("attachment"));
+        let archive: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(archive["user"]["email"], "owner@bar.com");
+        assert_eq!(archive["lists"].as_array().unwrap().len(), 2);
+        assert_eq!(archive["lists"][0]["items"][0]["name"], "milk");
+        assert!(archive["stores"].as_array().unwrap().is_empty());
+        assert!(archive["user"].get("passwordHash").is_none());
+
+        let response = client
+            .get(format!("/api/users/{}/export", user_id.to_hex()))
+            .header(other_auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn can_get_frequent_items() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        for names in [vec!["milk", "eggs"], vec!["milk"]].iter() {
+            let mut list = List::builder("groceries".to_string(), user_id.clone());
+            for name in names {
+                list.add_item(ListItem::builder(name).build());
+            }
+            repo.add_list(&list.build()).await?;
+        }
+
+        let frequent = client
+            .get(format!("/api/users/{}/items/frequent?limit=1", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<FrequentItem>>()
+            .unwrap();
+        assert_eq!(frequent, vec![FrequentItem { name: "milk".to_string(), category: None, count: 2 }]);
+
+        let response = client
+            .get(format!("/api/users/{}/items/frequent?limit=0", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn collections_accept_compound_sorts() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        for name in ["b", "a", "c"].iter() {
+            repo.add_list(&List::builder(name.to_string(), user_id.clone()).build()).await?;
+        }
+        repo.add_store(&Store::new("aldi")).await?;
+        repo.add_store(&Store::new("costco")).await?;
+
+        let lists: Vec<String> = client
+            .get(format!("/api/users/{}/lists?sort=name:desc,created_at", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<List>>()
+            .unwrap()
+            .into_iter()
+            .map(|list| list.name)
+            .collect();
+        assert_eq!(lists, vec!["c", "b", "a"]);
+
+        let response = client
+            .get(format!("/api/users/{}/lists?sort=archived", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let stores: Vec<String> = client
+            .get("/api/stores?sort=name:desc")
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<Store>>()
+            .unwrap()
+            .into_iter()
+            .map(|store| store.name)
+            .collect();
+        assert_eq!(stores, vec!["costco", "aldi"]);
+
+        let response = client.get("/api/stores?sort=categories").header(auth.clone()).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn favorites_can_seed_a_new_list() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let favorites_uri = format!("/api/users/{}/favorites", user_id.to_hex());
+        let star = |name: &str| NewFavorite {
+            name: name.to_string(),
+            category: Some("Dairy".to_string()),
+            amount: None,
+        };
+
+        let milk = client
+            .post(&favorites_uri)
+            .json(&star("milk"))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Favorite>()
+            .unwrap();
+        assert_eq!(milk.category, Some("dairy".to_string()));
+        let again = client
+            .post(&favorites_uri)
+            .json(&star("Milk"))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Favorite>()
+            .unwrap();
+        assert_eq!(again._id, milk._id);
+        client.post(&favorites_uri).json(&star("eggs")).header(auth.clone()).dispatch();
+
+        let favorites = client
+            .get(&favorites_uri)
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<Favorite>>()
+            .unwrap();
+        let names: Vec<&str> = favorites.iter().map(|favorite| favorite.name.as_str()).collect();
+        assert_eq!(names, vec!["eggs", "milk"]);
+
+        let new_list = NewList {
+            name: "groceries".to_string(),
+            user_id: user_id.clone(),
+            items: vec![ListItem::builder("Eggs").amount("12").build()],
+        };
+        let list = client
+            .post("/api/lists?from_favorites=true")
+            .json(&new_list)
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<List>()
+            .unwrap();
+        let items: Vec<(&str, Option<&str>)> =
+            list.items.iter().map(|item| (item.name(), item.amount())).collect();
+        assert_eq!(items, vec![("Eggs", Some("12")), ("milk", None)]);
+
+        let milk_uri = format!("{}/{}", favorites_uri, milk._id.unwrap().to_hex());
+        let deleted = client.delete(&milk_uri).header(auth.clone()).dispatch();
+        assert_eq!(deleted.status(), Status::NoContent);
+        let missing = client.delete(&milk_uri).header(auth.clone()).dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn lists_can_be_started_from_templates() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("milk").checked(true).build())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+
+        let list_id = list._id.unwrap();
+
+        let template = client
+            .post(format!("/api/lists/{}/template?name=weekly%20staples", list_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Template>()
+            .unwrap();
+        assert_eq!(template.name, "weekly staples");
+        let template_id = template._id.clone().unwrap();
+
+        let templates = client
+            .get(format!("/api/users/{}/templates", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<Template>>()
+            .unwrap();
+        assert_eq!(templates, vec![template]);
+
+        let new_list = client
+            .post(format!("/api/templates/{}/lists", template_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<List>()
+            .unwrap();
+        assert_eq!(new_list.name, "weekly staples");
+        assert_eq!(new_list.user_id, user_id);
+        assert_eq!(new_list.items.len(), 1);
+        assert!(!new_list.items[0].is_checked());
+
+        let template_uri = format!("/api/templates/{}", template_id.to_hex());
+        let deleted = client.delete(&template_uri).header(auth.clone()).dispatch();
+        assert_eq!(deleted.status(), Status::NoContent);
+        let missing = client.get(&template_uri).header(auth.clone()).dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn sorted_lists_suggest_forgotten_staples() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        for _ in 0..2 {
+            repo.add_list(
+                &List::builder("last week".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("eggs").category("dairy").build())
+                    .build(),
+            )
+            .await?;
+        }
+        let list = repo
+            .add_list(&List::builder("this week".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.unwrap();
+        let store = repo
+            .add_store(&Store::with_categories("corner store", &["dairy".to_string()]).unwrap())
+            .await?
+            .unwrap();
+        let sorted_uri = format!(
+            "/api/lists/{}/sorted?store={}",
+            list_id.to_hex(),
+            store._id.unwrap().to_hex()
+        );
+
+        let sorted = client
+            .get(&sorted_uri)
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<SortedList>()
+            .unwrap();
+        let suggestions: Vec<&str> =
+            sorted.suggestions.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(suggestions, vec!["eggs"]);
+
+        let response = client
+            .post(format!("/api/lists/{}/suggestions/dismissed", list_id.to_hex()))
+            .json(&"Eggs")
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let sorted = client
+            .get(&sorted_uri)
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<SortedList>()
+            .unwrap();
+        assert!(sorted.suggestions.is_empty());
+
+        let (collaborator_id, collaborator) = sign_in(&client, &repo, "friend@bar.com").await?;
+        let other_list = repo
+            .add_list(&List::builder("next week".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let other_id = other_list._id.unwrap();
+        repo.add_list_collaborator(&other_id, &collaborator_id).await?;
+        let sorted = client
+            .get(sorted_uri.replace(&list_id.to_hex(), &other_id.to_hex()))
+            .header(collaborator)
+            .dispatch()
+            .into_json::<SortedList>()
+            .unwrap();
+        assert!(sorted.suggestions.is_empty());
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn recurring_templates_make_lists_on_schedule() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(
+                &List::builder("weekly staples".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("milk").build())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+        let template = repo.add_template(&Template::from_list(&list, None)).await?.unwrap();
+        let recurrence_uri = format!("/api/templates/{}/recurrence", template._id.unwrap().to_hex());
+        let mut recurrence = Recurrence {
+            weekday: Weekday::Sunday,
+            hour: 8,
+            timezone: "Mars/Olympus".to_string(),
+        };
+
+        let response = client
+            .put(&recurrence_uri)
+            .json(&recurrence)
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        recurrence.timezone = "America/Chicago".to_string();
+        let template = client
+            .put(&recurrence_uri)
+            .json(&recurrence)
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Template>()
+            .unwrap();
+        assert_eq!(template.recurrence, Some(recurrence));
+        let next_run_at = template.next_run_at.unwrap().0;
+
+        let made = materialize_due_lists(&repo, next_run_at - chrono::Duration::minutes(1)).await?;
+        assert!(made.is_empty());
+        let made = materialize_due_lists(&repo, next_run_at).await?;
+        assert_eq!(made.len(), 1);
+        assert_eq!(made[0].name, "weekly staples");
+        assert_eq!(made[0].user_id, list.user_id);
+        let made = materialize_due_lists(&repo, next_run_at).await?;
+        assert!(made.is_empty());
+
+        let template = client
+            .delete(&recurrence_uri)
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Template>()
+            .unwrap();
+        assert_eq!(template.recurrence, None);
+        assert_eq!(template.next_run_at, None);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn added_items_merge_into_duplicates() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let milk = ListItem::builder("Milk").amount("1 gal").build();
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(milk.clone())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+
+        let list = client
+            .post(format!("/api/lists/{}/items", list._id.unwrap().to_hex()))
+            .json(&ListItem::builder("milk").amount("1 gal").build())
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<List>()
+            .unwrap();
+
+        assert_eq!(list.items.len(), 1);
+        assert_eq!(list.items[0].id(), milk.id());
+        assert_eq!(list.items[0].amount(), Some("2 gal"));
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn lists_can_be_trimmed_to_some_fields() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("milk").category("dairy").build())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+
+        let lists = client
+            .get(format!("/api/users/{}/lists?fields=name", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<Document>>()
+            .unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].get_str("name"), Ok("groceries"));
+        assert!(lists[0].contains_key("_id"));
+        assert!(!lists[0].contains_key("items"));
+
+        let uri = format!("/api/lists/{}?fields=items.name", list._id.unwrap().to_hex());
+        let trimmed = client
+            .get(&uri)
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Document>()
+            .unwrap();
+        assert!(!trimmed.contains_key("name"));
+        let items = trimmed.get_array("items").unwrap();
+        assert_eq!(items[0].as_document().unwrap(), &doc! { "name": "milk" });
+
+        let unknown = client
+            .get(format!("/api/users/{}/lists?fields=name,password", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(unknown.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn lists_can_be_summarized_without_items() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        repo.add_list(
+            &List::builder("groceries".to_string(), user_id.clone())
+                .add_item(ListItem::builder("milk").build())
+                .build(),
+        )
+        .await?;
+
+        let summaries = client
+            .get(format!("/api/users/{}/lists?summary=true", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Vec<ListSummary>>()
+            .unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].name, "groceries");
+        assert_eq!(summaries[0].item_count, 1);
+
+        let both = client
+            .get(format!("/api/users/{}/lists?summary=true&fields=name", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(both.status(), Status::BadRequest);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn counts_come_without_documents() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("milk").build())
+                    .build(),
+            )
+            .await?
+            .unwrap();
+
+        let lists = client
+            .get(format!("/api/users/{}/lists/count", user_id.to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Count>()
+            .unwrap();
+        assert_eq!(lists, Count { count: 1 });
+
+        let items = client
+            .get(format!("/api/lists/{}/items/count", list._id.unwrap().to_hex()))
+            .header(auth.clone())
+            .dispatch()
+            .into_json::<Count>()
+            .unwrap();
+        assert_eq!(items, Count { count: 1 });
+
+        let missing = client
+            .get(format!("/api/lists/{}/items/count", ObjectId::new().to_hex()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn unchanged_lists_are_not_sent_again() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "owner@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let uri = format!("/api/lists/{}", list._id.unwrap().to_hex());
+
+        let response = client.get(&uri).header(auth.clone()).dispatch();
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("private, no-cache"));
+        let last_modified = response.headers().get_one("Last-Modified").unwrap().to_string();
+
+        let unchanged = client
+            .get(&uri)
+            .header(Header::new("If-Modified-Since", last_modified))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(unchanged.status(), Status::NotModified);
+        assert!(unchanged.headers().get_one("ETag").is_some());
+
+        let changed = client
+            .get(&uri)
+            .header(Header::new("If-Modified-Since", "Sat, 01 May 2021 00:00:00 GMT"))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(changed.status(), Status::Ok);
+
+        let etag = unchanged.headers().get_one("ETag").unwrap().to_string();
+        let tagged = client
+            .get(&uri)
+            .header(Header::new("If-None-Match", etag.clone()))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(tagged.status(), Status::NotModified);
+        client
+            .patch(&uri)
+            .json(&ListUpdate { name: Some("weekly".to_string()), user_id: None })
+            .header(auth.clone())
+            .dispatch();
+        let edited = client
+            .get(&uri)
+            .header(Header::new("If-None-Match", etag))
+            .header(Header::new("If-Modified-Since", http_date(Utc::now())))
+            .header(auth.clone())
+            .dispatch();
+        assert_eq!(edited.status(), Status::Ok);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn registered_users_can_log_in() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let registration = Registration {
+            name: "foo".to_string(),
+            email: "Login@Bar.com".to_string(),
+            password: "correct horse".to_string(),
+        };
+
+        let user = client
+            .post("/api/auth/register")
+            .json(&registration)
+            .dispatch()
+            .into_json::<User>()
+            .unwrap();
+        assert_eq!(user.email, "login@bar.com");
+        let stored = repo.get_user_by_email("login@bar.com").await?.unwrap();
+        assert_ne!(stored.password_hash.as_deref(), Some("correct horse"));
+
+        let duplicate = client.post("/api/auth/register").json(&registration).dispatch();
+        assert_eq!(duplicate.status(), Status::Conflict);
+
+        let short = client
+            .post("/api/auth/register")
+            .json(&Registration {
+                email: "short@bar.com".to_string(),
+                password: "hunter2".to_string(),
+                ..registration.clone()
+            })
+            .dispatch();
+        assert_eq!(short.status(), Status::BadRequest);
+
+        let logged_in = client
+            .post("/api/auth/login")
+            .json(&Credentials {
+                email: "login@bar.com ".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch()
+            .into_json::<LoginResponse>()
+            .unwrap();
+        assert_eq!(logged_in.user, user);
+
+        let wrong = client
+            .post("/api/auth/login")
+            .json(&Credentials {
+                email: "login@bar.com".to_string(),
+                password: "battery staple".to_string(),
+            })
+            .dispatch();
+        assert_eq!(wrong.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens_identify_the_user() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let user = client
+            .post("/api/auth/register")
+            .json(&Registration {
+                name: "foo".to_string(),
+                email: "bearer@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch()
+            .into_json::<User>()
+            .unwrap();
+        let login = client
+            .post("/api/auth/login")
+            .json(&Credentials {
+                email: "bearer@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch()
+            .into_json::<LoginResponse>()
+            .unwrap();
+
+        let me = client
+            .get("/api/auth/me")
+            .header(Header::new("Authorization", format!("Bearer {}", login.token.unwrap())))
+            .dispatch()
+            .into_json::<User>()
+            .unwrap();
+        assert_eq!(me, user);
+
+        let anonymous = client.get("/api/auth/me").dispatch();
+        assert_eq!(anonymous.status(), Status::Unauthorized);
+
+        let forged = client
+            .get("/api/auth/me")
+            .header(Header::new("Authorization", "Bearer not.a.token"))
+            .dispatch();
+        assert_eq!(forged.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn session_cookies_last_until_logout() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket()
+            .configure(rocket::Config::figment().merge(("auth_mode", "session")))
+            .ignite()
+            .await
+            .unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let user = client
+            .post("/api/auth/register")
+            .json(&Registration {
+                name: "foo".to_string(),
+                email: "session@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch()
+            .into_json::<User>()
+            .unwrap();
+
+        let login = client
+            .post("/api/auth/login")
+            .json(&Credentials {
+                email: "session@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch();
+        assert!(login.cookies().get_private("session").is_some());
+        assert_eq!(login.into_json::<LoginResponse>().unwrap().token, None);
+
+        let me = client.get("/api/auth/me").dispatch().into_json::<User>().unwrap();
+        assert_eq!(me, user);
+
+        let logout = client.post("/api/auth/logout").dispatch();
+        assert_eq!(logout.status(), Status::NoContent);
+        let me = client.get("/api/auth/me").dispatch();
+        assert_eq!(me.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn api_keys_work_until_revoked() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let user = client
+            .post("/api/auth/register")
+            .json(&Registration {
+                name: "foo".to_string(),
+                email: "keys@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch()
+            .into_json::<User>()
+            .unwrap();
+        let token = client
+            .post("/api/auth/login")
+            .json(&Credentials {
+                email: "keys@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch()
+            .into_json::<LoginResponse>()
+            .unwrap()
+            .token
+            .unwrap();
+        let bearer = Header::new("Authorization", format!("Bearer {}", token));
+
+        let created = client
+            .post("/api/auth/api-keys")
+            .header(bearer.clone())
+            .json(&NewApiKey { name: "home automation".to_string(), scopes: None })
+            .dispatch()
+            .into_json::<CreatedApiKey>()
+            .unwrap();
+        let keys = client
+            .get("/api/auth/api-keys")
+            .header(bearer.clone())
+            .dispatch()
+            .into_json::<Vec<ApiKeyInfo>>()
+            .unwrap();
+        assert_eq!(keys, vec![created.info.clone()]);
+
+        let me = client
+            .get("/api/auth/me")
+            .header(Header::new("X-Api-Key", created.key.clone()))
+            .dispatch()
+            .into_json::<User>()
+            .unwrap();
+        assert_eq!(me, user);
+
+        let revoked = client
+            .delete(format!("/api/auth/api-keys/{}", created.info.id.unwrap().to_hex()))
+            .header(bearer)
+            .dispatch();
+        assert_eq!(revoked.status(), Status::NoContent);
+        let me = client
+            .get("/api/auth/me")
+            .header(Header::new("X-Api-Key", created.key))
+            .dispatch();
+        assert_eq!(me.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn scoped_credentials_only_do_what_their_scopes_allow() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (user_id, auth) = sign_in(&client, &repo, "scopes@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_url = format!("/api/lists/{}", list._id.clone().unwrap().to_hex());
+        let item = ListItem::builder("salmon").build();
+
+        let no_scopes = client
+            .post("/api/auth/tokens")
+            .header(auth.clone())
+            .json(&TokenRequest { scopes: vec![] })
+            .dispatch();
+        assert_eq!(no_scopes.status(), Status::BadRequest);
+        let scoped = client
+            .post("/api/auth/tokens")
+            .header(auth.clone())
+            .json(&TokenRequest { scopes: vec![Scope::ListsRead] })
+            .dispatch()
+            .into_json::<ScopedToken>()
+            .unwrap();
+        assert_eq!(scoped.scopes, vec![Scope::ListsRead]);
+        let reader = Header::new("Authorization", format!("Bearer {}", scoped.token));
+
+        let read = client.get(list_url.clone()).header(reader.clone()).dispatch();
+        assert_eq!(read.status(), Status::Ok);
+        let write = client
+            .post(format!("{}/items", list_url))
+            .json(&item)
+            .header(reader.clone())
+            .dispatch();
+        assert_eq!(write.status(), Status::Forbidden);
+        let me = client.get("/api/auth/me").header(reader.clone()).dispatch();
+        assert_eq!(me.status(), Status::Forbidden);
+        let more = client
+            .post("/api/auth/tokens")
+            .header(reader)
+            .json(&TokenRequest { scopes: vec![Scope::ListsWrite] })
+            .dispatch();
+        assert_eq!(more.status(), Status::Forbidden);
+
+        let created = client
+            .post("/api/auth/api-keys")
+            .header(auth)
+            .json(&NewApiKey {
+                name: "kitchen tablet".to_string(),
+                scopes: Some(vec![Scope::ListsWrite]),
+            })
+            .dispatch()
+            .into_json::<CreatedApiKey>()
+            .unwrap();
+        assert_eq!(created.info.scopes, Some(vec![Scope::ListsWrite]));
+        let writer = Header::new("X-Api-Key", created.key);
+        let write = client
+            .post(format!("{}/items", list_url))
+            .json(&item)
+            .header(writer.clone())
+            .dispatch();
+        assert_eq!(write.status(), Status::Ok);
+        let count = client
+            .get(format!("/api/users/{}/lists/count", user_id.to_hex()))
+            .header(writer)
+            .dispatch();
+        assert_eq!(count.status(), Status::Forbidden);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn household_members_share_lists_and_stores() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (owner_id, owner) = sign_in(&client, &repo, "home-owner@bar.com").await?;
+        let (housemate_id, housemate) = sign_in(&client, &repo, "housemate@bar.com").await?;
+        let (outsider_id, outsider) = sign_in(&client, &repo, "outsider@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), owner_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_url = format!("/api/lists/{}", list._id.clone().unwrap().to_hex());
+        let store = repo
+            .add_store(&Store::new("corner store").owned_by(owner_id.clone()))
+            .await?
+            .unwrap();
+        let store_url = format!("/api/stores/{}", store._id.clone().unwrap().to_hex());
+
+        let household = client
+            .post("/api/households")
+            .header(owner.clone())
+            .json(&NewHousehold { name: "home".to_string() })
+            .dispatch()
+            .into_json::<Household>()
+            .unwrap();
+        let household_id = household._id.clone().unwrap();
+        let household_url = format!("/api/households/{}", household_id.to_hex());
+        let added = client
+            .post(format!("{}/members", household_url))
+            .header(owner.clone())
+            .json(&NewInvitation { email: "housemate@bar.com".to_string() })
+            .dispatch();
+        assert_eq!(added.status(), Status::NotFound);
+        let household = repo.add_household_member(&household_id, &housemate_id).await?.unwrap();
+        assert_eq!(household.members, vec![owner_id.clone(), housemate_id.clone()]);
+        let outside = client.get(household_url.clone()).header(outsider.clone()).dispatch();
+        assert_eq!(outside.status(), Status::Forbidden);
+
+        let before = client.get(list_url.clone()).header(housemate.clone()).dispatch();
+        assert_eq!(before.status(), Status::Forbidden);
+        let assignment = HouseholdAssignment { household_id: household_id.clone() };
+        let moved = client
+            .put(format!("{}/household", list_url))
+            .header(owner.clone())
+            .json(&assignment)
+            .dispatch()
+            .into_json::<List>()
+            .unwrap();
+        assert_eq!(moved.household_id, Some(household_id.clone()));
+        let moved = client
+            .put(format!("{}/household", store_url))
+            .header(owner.clone())
+            .json(&assignment)
+            .dispatch();
+        assert_eq!(moved.status(), Status::Ok);
+
+        let read = client.get(list_url.clone()).header(housemate.clone()).dispatch();
+        assert_eq!(read.status(), Status::Ok);
+        let detached = client
+            .delete(format!("{}/household", list_url))
+            .header(housemate.clone())
+            .dispatch();
+        assert_eq!(detached.status(), Status::Forbidden);
+        let detached = client
+            .delete(format!("{}/household", store_url))
+            .header(housemate.clone())
+            .dispatch();
+        assert_eq!(detached.status(), Status::Forbidden);
+        let added = client
+            .post(format!("{}/items", list_url))
+            .json(&ListItem::builder("milk").build())
+            .header(housemate.clone())
+            .dispatch();
+        assert_eq!(added.status(), Status::Ok);
+        let store = client.get(store_url.clone()).header(housemate.clone()).dispatch();
+        assert_eq!(store.status(), Status::Ok);
+        let lists = client
+            .get(format!("/api/users/{}/lists/count", housemate_id.to_hex()))
+            .header(housemate.clone())
+            .dispatch()
+            .into_json::<Count>()
+            .unwrap();
+        assert_eq!(lists.count, 1);
+
+        let outsider_list = repo
+            .add_list(&List::builder("mine".to_string(), outsider_id).build())
+            .await?
+            .unwrap();
+        let sneaky = client
+            .put(format!("/api/lists/{}/household", outsider_list._id.unwrap().to_hex()))
+            .header(outsider.clone())
+            .json(&assignment)
+            .dispatch();
+        assert_eq!(sneaky.status(), Status::Forbidden);
+
+        let left = client
+            .delete(format!("{}/members/{}", household_url, housemate_id.to_hex()))
+            .header(housemate.clone())
+            .dispatch();
+        assert_eq!(left.status(), Status::Ok);
+        let after = client.get(list_url).header(housemate).dispatch();
+        assert_eq!(after.status(), Status::Forbidden);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn passwords_can_be_reset_by_email() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let outbox = rocket.state::<Arc<MemoryMailer>>().unwrap().clone();
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        client
+            .post("/api/auth/register")
+            .json(&Registration {
+                name: "foo".to_string(),
+                email: "reset@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch();
+        let login = |password: &str| {
+            client
+                .post("/api/auth/login")
+                .json(&Credentials {
+                    email: "reset@bar.com".to_string(),
+                    password: password.to_string(),
+                })
+                .dispatch()
+        };
+        let old_token = login("correct horse").into_json::<LoginResponse>().unwrap().token.unwrap();
+
+        let unknown = client
+            .post("/api/auth/password-reset")
+            .json(&ResetRequest { email: "nobody@bar.com".to_string() })
+            .dispatch();
+        assert_eq!(unknown.status(), Status::Accepted);
+        let requested = client
+            .post("/api/auth/password-reset")
+            .json(&ResetRequest { email: "reset@bar.com".to_string() })
+            .dispatch();
+        assert_eq!(requested.status(), Status::Accepted);
+
+        let mail = outbox.sent().into_iter().find(|mail| mail.to == "reset@bar.com").unwrap();
+        let token: String = mail
+            .body
+            .split("new password: ")
+            .nth(1)
+            .unwrap()
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect();
+        let confirm = |token: &str, password: &str| {
+            client
+                .post("/api/auth/password-reset/confirm")
+                .json(&ResetConfirmation {
+                    token: token.to_string(),
+                    password: password.to_string(),
+                })
+                .dispatch()
+                .status()
+        };
+        assert_eq!(confirm(&token, "short"), Status::BadRequest);
+        assert_eq!(confirm(&token, "battery staple"), Status::NoContent);
+        assert_eq!(confirm(&token, "battery staple"), Status::BadRequest);
+
+        assert_eq!(login("correct horse").status(), Status::Unauthorized);
+        assert_eq!(login("battery staple").status(), Status::Ok);
+        let stale = client
+            .get("/api/auth/me")
+            .header(Header::new("Authorization", format!("Bearer {}", old_token)))
+            .dispatch();
+        assert_eq!(stale.status(), Status::Unauthorized);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn invitations_add_new_and_existing_accounts() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let outbox = rocket.state::<Arc<MemoryMailer>>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let (owner_id, owner) = sign_in(&client, &repo, "inviter@bar.com").await?;
+        let (existing_id, existing) = sign_in(&client, &repo, "invited-user@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), owner_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_id = list._id.unwrap();
+        let household = repo
+            .add_household(&Household::new("home", owner_id.clone()))
+            .await?
+            .unwrap();
+        let household_id = household._id.unwrap();
+
+        let invite = |uri: String, email: &str| {
+            client
+                .post(uri)
+                .header(owner.clone())
+                .json(&NewInvitation { email: email.to_string() })
+                .dispatch()
+                .status()
+        };
+        let token_for = |email: &str| -> String {
+            let mail = outbox.sent().into_iter().rev().find(|mail| mail.to == email).unwrap();
+            mail.body
+                .split("accept: ")
+                .nth(1)
+                .unwrap()
+                .chars()
+                .take_while(char::is_ascii_alphanumeric)
+                .collect()
+        };
+        let accept = |acceptance: &InvitationAcceptance| {
+            client.post("/api/invitations/accept").json(acceptance).dispatch()
+        };
+        let list_url = format!("/api/lists/{}", list_id.to_hex());
+        let household_url = format!("/api/households/{}", household_id.to_hex());
+
+        let outsider = client
+            .post(format!("{}/invitations", list_url))
+            .header(existing.clone())
+            .json(&NewInvitation { email: "someone@bar.com".to_string() })
+            .dispatch();
+        assert_eq!(outsider.status(), Status::Forbidden);
+
+        assert_eq!(invite(format!("{}/invitations", list_url), "Invited-User@bar.com"), Status::Ok);
+        let acceptance = InvitationAcceptance {
+            token: token_for("invited-user@bar.com"),
+            name: None,
+            password: None,
+        };
+        let accepted = accept(&acceptance).into_json::<AcceptedInvitation>().unwrap();
+        assert_eq!(accepted.user.id, Some(existing_id));
+        assert_eq!(accepted.target, InvitationTarget::List(list_id));
+        let reshared = client
+            .post(format!("{}/invitations", list_url))
+            .header(existing.clone())
+            .json(&NewInvitation { email: "someone@bar.com".to_string() })
+            .dispatch();
+        assert_eq!(reshared.status(), Status::Forbidden);
+        let read = client.get(list_url.clone()).header(existing).dispatch();
+        assert_eq!(read.status(), Status::Ok);
+        assert_eq!(accept(&acceptance).status(), Status::BadRequest);
+
+        assert_eq!(invite(format!("{}/invitations", household_url), "new@bar.com"), Status::Ok);
+        let token = token_for("new@bar.com");
+        let nameless = InvitationAcceptance { token: token.clone(), name: None, password: None };
+        assert_eq!(accept(&nameless).status(), Status::BadRequest);
+        let acceptance = InvitationAcceptance {
+            token,
+            name: Some("New Person".to_string()),
+            password: Some("correct horse".to_string()),
+        };
+        let accepted = accept(&acceptance).into_json::<AcceptedInvitation>().unwrap();
+        assert_eq!(accepted.target, InvitationTarget::Household(household_id.clone()));
+        let new_user_id = accepted.user.id.clone().unwrap();
+        let household = repo.get_household_by_id(&household_id).await?.unwrap();
+        assert!(household.includes(&new_user_id));
+
+        let login = client
+            .post("/api/auth/login")
+            .json(&Credentials {
+                email: "new@bar.com".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .dispatch();
+        assert_eq!(login.status(), Status::Ok);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn admins_can_manage_users_and_content() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket().ignite().await.unwrap();
+        let db_config = rocket.state::<DbConfig>().unwrap().clone();
+        let repo = Repo::new(&db_config).await?;
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+        let mut admin = RepoUser::new("admin".to_string(), "admin@bar.com".to_string());
+        admin.role = Role::Admin;
+        let (admin_id, admin_auth) = sign_in_as(&client, &repo, admin).await?;
+        let (user_id, auth) = sign_in(&client, &repo, "spammer@bar.com").await?;
+        let list = repo
+            .add_list(&List::builder("spam".to_string(), user_id.clone()).build())
+            .await?
+            .unwrap();
+        let list_uri = format!("/api/lists/{}", list._id.clone().unwrap().to_hex());
+
+        let forbidden = client.get("/api/admin/usage").header(auth.clone()).dispatch();
+        assert_eq!(forbidden.status(), Status::Forbidden);
+
+        let usage = client
+            .get("/api/admin/usage")
+            .header(admin_auth.clone())
+            .dispatch()
+            .into_json::<Usage>()
+            .unwrap();
+        assert!(usage.users >= 2);
+
+        let found = client
+            .get("/api/admin/users?q=SPAMMER")
+            .header(admin_auth.clone())
+            .dispatch()
+            .into_json::<Vec<Account>>()
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, Some(user_id.clone()));
+        assert_eq!(found[0].role, Role::User);
+
+        let disabled = client
+            .post(format!("/api/admin/users/{}/disable", user_id.to_hex()))
+            .header(admin_auth.clone())
+            .dispatch()
+            .into_json::<Account>()
+            .unwrap();
+        assert!(disabled.disabled_at.is_some());
+        let locked_out = client.get(&list_uri).header(auth.clone()).dispatch();
+        assert_eq!(locked_out.status(), Status::Forbidden);
+
+        let themselves = client
+            .post(format!("/api/admin/users/{}/disable", admin_id.to_hex()))
+            .header(admin_auth.clone())
+            .dispatch();
+        assert_eq!(themselves.status(), Status::BadRequest);
+
+        let enabled = client
+            .post(format!("/api/admin/users/{}/enable", user_id.to_hex()))
+            .header(admin_auth.clone())
+            .dispatch()
+            .into_json::<Account>()
+            .unwrap();
+        assert_eq!(enabled.disabled_at, None);
+        assert_eq!(client.get(&list_uri).header(auth.clone()).dispatch().status(), Status::Ok);
+
+        let removed = client
+            .delete(format!("/api/admin/lists/{}", list._id.clone().unwrap().to_hex()))
+            .header(admin_auth.clone())
+            .dispatch();
+        assert_eq!(removed.status(), Status::NoContent);
+        assert_eq!(repo.get_list_by_id(&list._id.unwrap()).await?, None);
+
+        clean_up_db(&db_config).await
+    }
+
+    #[tokio::test]
+    async fn allowed_origins_pass_cors_preflights() -> Result<()> {
+        run_in_test();
+
+        let rocket = rocket()
+            .configure(
+                rocket::Config::figment()
+                    .merge(("cors_allowed_origins", vec!["https://app.example"])),
+            )
+            .ignite()
+            .await
+            .unwrap();
+        let client = Client::tracked(rocket).expect("valid rocket instant");
+
+        let preflight = client
+            .options("/api/lists/bulk")
+            .header(Header::new("Origin", "https://app.example"))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .header(Header::new("Access-Control-Request-Headers", "authorization, content-type"))
+            .dispatch();
+        assert_eq!(preflight.status(), Status::NoContent);
+        let headers = preflight.headers();
+        assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some("https://app.example"));
+        assert!(headers.get_one("Access-Control-Allow-Methods").unwrap().contains("POST"));
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Headers"),
+            Some("authorization, content-type")
+        );
+
+        let elsewhere = client
+            .options("/api/lists/bulk")
+            .header(Header::new("Origin", "https://evil.example"))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .dispatch();
+        assert_eq!(elsewhere.status(), Status::Forbidden);
+        assert_eq!(elsewhere.headers().get_one("Access-Control-Allow-Origin"), None);
+
+        let response = client
+            .get("/api/info")
+            .header(Header::new("Origin", "https://app.example"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://app.example")
+        );
+        assert!(response.headers().get_one("Access-Control-Expose-Headers").is_some());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use rocket::{
+    fairing::{AdHoc, Fairing, Info, Kind},
+    get,
+    http::{uri::Origin, Method, Status},
+    response::status,
+    serde::json::Json,
+    Data, Request,
+};
+use serde::{Deserialize, Serialize};
+
+/// Writes are rewritten to this route while maintenance mode is on.
+const MAINTENANCE_PATH: &str = "/api/maintenance";
+/// Signing in and out doesn't write to the database, and people have to sign
+/// in to read their lists.
+const AUTH_PATHS: &[&str] = &["/api/auth/login", "/api/auth/logout"];
+/// Admins have to be able to turn maintenance mode back off.
+const SWITCH_PATH: &str = "/api/admin/maintenance";
+
+const MAINTENANCE_MESSAGE: &str =
+    "Grok List is undergoing maintenance. Your lists can still be viewed, but changes are paused for a few minutes.";
+
+/// Whether the server starts in maintenance mode; see `MaintenanceSwitch`.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct MaintenanceConfig {
+    #[serde(default)]
+    pub maintenance: bool,
+}
+
+/// Whether maintenance mode is on right now. It starts out as configured, and
+/// admins can flip it without a restart, as around a migration. Clones share
+/// the one switch.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceSwitch(Arc<AtomicBool>);
+
+impl MaintenanceSwitch {
+    pub fn new(on: bool) -> Self {
+        MaintenanceSwitch(Arc::new(AtomicBool::new(on)))
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, on: bool) {
+        self.0.store(on, Ordering::SeqCst);
+    }
+}
+
+/// Manages a `MaintenanceSwitch` set from `MaintenanceConfig`.
+pub fn maintenance_switch() -> AdHoc {
+    AdHoc::on_ignite("Maintenance switch", |rocket| async {
+        let on = rocket.state::<MaintenanceConfig>().map_or(false, |config| config.maintenance);
+        rocket.manage(MaintenanceSwitch::new(on))
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MaintenanceMessage {
+    pub message: String,
+}
+
+/// Rejects every request that could write while the `MaintenanceSwitch` is
+/// on, so migrations can run against a live database. Reads are served as
+/// usual, as are logins, logouts and turning maintenance mode off.
+pub struct MaintenanceMode;
+
+fn is_read(method: Method) -> bool {
+    matches!(method, Method::Get | Method::Head | Method::Options)
+}
+
+fn is_allowed(method: Method, path: &str) -> bool {
+    is_read(method) || AUTH_PATHS.contains(&path) || path == SWITCH_PATH
+}
+
+#[rocket::async_trait]
+impl Fairing for MaintenanceMode {
+    fn info(&self) -> Info {
+        Info {
+            name: "Maintenance mode",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data) {
+        let enabled = req
+            .rocket()
+            .state::<MaintenanceSwitch>()
+            .map_or(false, MaintenanceSwitch::is_on);
+        if enabled && !is_allowed(req.method(), req.uri().path().as_str()) {
+            req.set_method(Method::Get);
+            req.set_uri(Origin::parse(MAINTENANCE_PATH).expect("maintenance path is a valid uri"));
+        }
+    }
+}
+
+#[get("/maintenance")]
+pub fn maintenance() -> status::Custom<Json<MaintenanceMessage>> {
+    status::Custom(
+        Status::ServiceUnavailable,
+        Json(MaintenanceMessage { message: MAINTENANCE_MESSAGE.to_owned() }),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_safe_methods_are_reads() {
+        assert!(is_read(Method::Get));
+        assert!(is_read(Method::Head));
+        assert!(is_read(Method::Options));
+        assert!(!is_read(Method::Post));
+        assert!(!is_read(Method::Put));
+        assert!(!is_read(Method::Patch));
+        assert!(!is_read(Method::Delete));
+    }
+
+    #[test]
+    fn logins_logouts_and_the_switch_are_allowed() {
+        assert!(is_allowed(Method::Post, "/api/auth/login"));
+        assert!(is_allowed(Method::Post, "/api/auth/logout"));
+        assert!(is_allowed(Method::Put, "/api/admin/maintenance"));
+        assert!(!is_allowed(Method::Post, "/api/auth/register"));
+        assert!(is_allowed(Method::Get, "/api/lists"));
+    }
+
+    #[test]
+    fn clones_share_the_switch() {
+        let switch = MaintenanceSwitch::new(false);
+        let copy = switch.clone();
+        switch.set(true);
+        assert!(copy.is_on());
+    }
+}
@@ -0,0 +1,92 @@
+use serde::Deserialize;
+use mongodb::bson::oid::ObjectId;
+
+use crate::list::{List, ListItem};
+use crate::repo::{Repo, RepoError};
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SignupConfig {
+    #[serde(default)]
+    pub starter_list: Option<StarterList>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StarterList {
+    pub name: String,
+    #[serde(default)]
+    pub items: Vec<StarterItem>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StarterItem {
+    pub name: String,
+    pub category: Option<String>,
+    pub amount: Option<String>,
+}
+
+impl StarterList {
+    pub fn build(&self, user_id: ObjectId) -> List {
+        let mut builder = List::builder(self.name.to_owned(), user_id);
+        for item in self.items.iter() {
+            let mut item_builder = ListItem::builder(&item.name);
+            if let Some(category) = &item.category {
+                item_builder.category(category);
+            }
+            if let Some(amount) = &item.amount {
+                item_builder.amount(amount);
+            }
+            builder.add_item(item_builder.build());
+        }
+        builder.build()
+    }
+}
+
+pub async fn provision_user(
+    repo: &Repo,
+    config: &SignupConfig,
+    user_id: &ObjectId,
+) -> Result<Option<List>, RepoError> {
+    match &config.starter_list {
+        Some(starter_list) => repo.add_list(&starter_list.build(user_id.clone())).await,
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn starter_list_is_built_for_the_new_user() {
+        let user_id = ObjectId::new();
+        let starter_list = StarterList {
+            name: "Groceries".to_string(),
+            items: vec![
+                StarterItem {
+                    name: "milk".to_string(),
+                    category: Some("Dairy".to_string()),
+                    amount: None,
+                },
+                StarterItem {
+                    name: "eggs".to_string(),
+                    category: None,
+                    amount: Some("12".to_string()),
+                },
+            ],
+        };
+
+        let list = starter_list.build(user_id.clone());
+
+        assert_eq!(list.name, "Groceries");
+        assert_eq!(list.user_id, user_id);
+        let items: Vec<(&str, Option<&str>, Option<&str>)> = list
+            .items
+            .iter()
+            .map(|item| (item.name(), item.category(), item.amount()))
+            .collect();
+        assert_eq!(
+            items,
+            vec![("milk", Some("dairy"), None), ("eggs", None, Some("12"))]
+        );
+    }
+}
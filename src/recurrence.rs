@@ -0,0 +1,137 @@
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RecurrenceError {
+    #[error("not a known time zone: {0}")]
+    UnknownTimezone(String),
+    #[error("hour must be from 0 to 23, not: {0}")]
+    InvalidHour(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<Weekday> for chrono::Weekday {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Monday => chrono::Weekday::Mon,
+            Weekday::Tuesday => chrono::Weekday::Tue,
+            Weekday::Wednesday => chrono::Weekday::Wed,
+            Weekday::Thursday => chrono::Weekday::Thu,
+            Weekday::Friday => chrono::Weekday::Fri,
+            Weekday::Saturday => chrono::Weekday::Sat,
+            Weekday::Sunday => chrono::Weekday::Sun,
+        }
+    }
+}
+
+/// Weekly, on `weekday` at `hour` o'clock in the owner's time zone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    pub weekday: Weekday,
+    #[serde(default)]
+    pub hour: u32,
+    /// An IANA time zone name, like `America/Chicago`.
+    pub timezone: String,
+}
+
+impl Recurrence {
+    fn timezone(&self) -> Result<Tz, RecurrenceError> {
+        self.timezone
+            .parse()
+            .map_err(|_| RecurrenceError::UnknownTimezone(self.timezone.clone()))
+    }
+
+    pub fn validate(&self) -> Result<(), RecurrenceError> {
+        if self.hour > 23 {
+            return Err(RecurrenceError::InvalidHour(self.hour));
+        }
+        self.timezone().map(|_| ())
+    }
+
+    /// The first scheduled time strictly after `after`. When daylight saving
+    /// skips the hour, the run moves to the hour after.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Result<DateTime<Utc>, RecurrenceError> {
+        self.validate()?;
+        let timezone = self.timezone()?;
+        let today = after.with_timezone(&timezone).date().naive_local();
+        let weekday = chrono::Weekday::from(self.weekday);
+
+        for days in 0..=7 {
+            let date = today + Duration::days(days);
+            if date.weekday() != weekday {
+                continue;
+            }
+            let local = date.and_hms(self.hour, 0, 0);
+            let scheduled = timezone
+                .from_local_datetime(&local)
+                .earliest()
+                .or_else(|| timezone.from_local_datetime(&(local + Duration::hours(1))).earliest());
+            if let Some(scheduled) = scheduled.map(|scheduled| scheduled.with_timezone(&Utc)) {
+                if scheduled > after {
+                    return Ok(scheduled);
+                }
+            }
+        }
+        unreachable!("every weekday comes around within eight days")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sunday_at(hour: u32, timezone: &str) -> Recurrence {
+        Recurrence { weekday: Weekday::Sunday, hour, timezone: timezone.to_string() }
+    }
+
+    #[test]
+    fn next_run_is_in_the_owners_time_zone() {
+        // Saturday 2021-05-01 12:00 UTC; Sunday 08:00 in Chicago is 13:00 UTC.
+        let after = Utc.ymd(2021, 5, 1).and_hms(12, 0, 0);
+        assert_eq!(
+            sunday_at(8, "America/Chicago").next_after(after),
+            Ok(Utc.ymd(2021, 5, 2).and_hms(13, 0, 0))
+        );
+    }
+
+    #[test]
+    fn next_run_is_strictly_after() {
+        let scheduled = Utc.ymd(2021, 5, 2).and_hms(8, 0, 0);
+        assert_eq!(
+            sunday_at(8, "UTC").next_after(scheduled),
+            Ok(Utc.ymd(2021, 5, 9).and_hms(8, 0, 0))
+        );
+    }
+
+    #[test]
+    fn skipped_hours_run_an_hour_later() {
+        // Clocks in Chicago jumped from 02:00 to 03:00 on 2021-03-14.
+        let after = Utc.ymd(2021, 3, 13).and_hms(12, 0, 0);
+        assert_eq!(
+            sunday_at(2, "America/Chicago").next_after(after),
+            Ok(Utc.ymd(2021, 3, 14).and_hms(8, 0, 0))
+        );
+    }
+
+    #[test]
+    fn bad_recurrences_are_rejected() {
+        assert_eq!(
+            sunday_at(8, "Mars/Olympus").validate(),
+            Err(RecurrenceError::UnknownTimezone("Mars/Olympus".to_string()))
+        );
+        assert_eq!(sunday_at(24, "UTC").validate(), Err(RecurrenceError::InvalidHour(24)));
+    }
+}
@@ -0,0 +1,221 @@
+use rocket::http::ContentType;
+
+use crate::list::{List, ListItem};
+
+/// Three SMS segments; longer messages get split unpredictably by carriers.
+pub const SHARE_TEXT_MAX_LEN: usize = 459;
+
+fn item_text(item: &ListItem) -> String {
+    match item.amount() {
+        Some(amount) => format!("{} ({})", item.name(), amount),
+        None => item.name().to_owned(),
+    }
+}
+
+fn more_text(count: usize) -> String {
+    format!("\n...and {} more", count)
+}
+
+pub fn share_text(list: &List, categories: &[String], max_len: usize) -> String {
+    let lines: Vec<(String, usize)> = list
+        .items_by_category(categories)
+        .into_iter()
+        .map(|(category, items)| {
+            let names: Vec<String> = items.iter().map(item_text).collect();
+            let heading = category.unwrap_or_else(|| "other".to_owned()).to_uppercase();
+            (format!("{}: {}", heading, names.join(", ")), items.len())
+        })
+        .collect();
+
+    let mut text = list.name.to_owned();
+    let mut remaining: usize = lines.iter().map(|(_, count)| count).sum();
+    for (line, count) in lines.iter() {
+        let with_line = text.chars().count() + 1 + line.chars().count();
+        let left_after = remaining - count;
+        let suffix_len = if left_after == 0 { 0 } else { more_text(left_after).chars().count() };
+        if with_line + suffix_len > max_len {
+            text.push_str(&more_text(remaining));
+            return text;
+        }
+        text.push('\n');
+        text.push_str(line);
+        remaining = left_after;
+    }
+
+    text
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Renders each list on its own printed page.
+pub fn print_html(lists: &[List]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <style>section { page-break-after: always; } section:last-child { page-break-after: auto; }</style>\n\
+         </head>\n<body>\n",
+    );
+    for list in lists {
+        html.push_str(&format!("<section>\n<h1>{}</h1>\n", escape_html(&list.name)));
+        for (category, items) in list.items_by_category(&[]) {
+            let heading = category.unwrap_or_else(|| "other".to_owned());
+            html.push_str(&format!("<h2>{}</h2>\n<ul>\n", escape_html(&heading)));
+            for item in items.iter() {
+                html.push_str(&format!("<li>&#9744; {}</li>\n", escape_html(&item_text(item))));
+            }
+            html.push_str("</ul>\n");
+        }
+        html.push_str("</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// A downloadable rendering of a list, grouped in store order.
+pub trait Exporter: Send + Sync {
+    fn content_type(&self) -> ContentType;
+    fn export(&self, list: &List, categories: &[String]) -> String;
+}
+
+/// Looks up an exporter by the name clients pass as `format`.
+pub fn exporter(format: &str) -> Option<Box<dyn Exporter>> {
+    match format {
+        "markdown" => Some(Box::new(MarkdownExporter)),
+        "text" => Some(Box::new(TextExporter)),
+        _ => None,
+    }
+}
+
+/// The share text without the SMS length cap.
+pub struct TextExporter;
+
+impl Exporter for TextExporter {
+    fn content_type(&self) -> ContentType {
+        ContentType::Plain
+    }
+
+    fn export(&self, list: &List, categories: &[String]) -> String {
+        share_text(list, categories, usize::MAX)
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]#<>".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A checklist per category, for printing or pasting into a notes app.
+pub struct MarkdownExporter;
+
+impl Exporter for MarkdownExporter {
+    fn content_type(&self) -> ContentType {
+        ContentType::new("text", "markdown")
+    }
+
+    fn export(&self, list: &List, categories: &[String]) -> String {
+        let mut markdown = format!("# {}\n", escape_markdown(&list.name));
+        for (category, items) in list.items_by_category(categories) {
+            let heading = category.unwrap_or_else(|| "other".to_owned());
+            markdown.push_str(&format!("\n## {}\n\n", escape_markdown(&heading)));
+            for item in items.iter() {
+                let check = if item.is_checked() { "x" } else { " " };
+                markdown.push_str(&format!("- [{}] {}\n", check, escape_markdown(&item_text(item))));
+            }
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mongodb::bson::oid::ObjectId;
+
+    fn test_list() -> List {
+        List::builder("Groceries".to_string(), ObjectId::new())
+            .add_item(ListItem::builder("salmon").category("meat").amount("2lb").build())
+            .add_item(ListItem::builder("foil").build())
+            .add_item(ListItem::builder("broccoli").category("produce").build())
+            .add_item(ListItem::builder("apples").category("produce").amount("6").build())
+            .build()
+    }
+
+    #[test]
+    fn share_text_follows_store_order() {
+        let text = share_text(
+            &test_list(),
+            &["produce".to_string(), "meat".to_string()],
+            SHARE_TEXT_MAX_LEN,
+        );
+
+        assert_eq!(
+            text,
+            "Groceries\nPRODUCE: broccoli, apples (6)\nMEAT: salmon (2lb)\nOTHER: foil"
+        );
+    }
+
+    #[test]
+    fn share_text_is_capped_at_max_len() {
+        let text = share_text(&test_list(), &["produce".to_string(), "meat".to_string()], 60);
+
+        assert!(text.chars().count() <= 60);
+        assert_eq!(text, "Groceries\nPRODUCE: broccoli, apples (6)\n...and 2 more");
+    }
+
+    #[test]
+    fn print_html_puts_each_list_in_its_own_section() {
+        let other = List::builder("Hardware <misc>".to_string(), ObjectId::new())
+            .add_item(ListItem::builder("nails").build())
+            .build();
+
+        let html = print_html(&[test_list(), other]);
+
+        assert_eq!(html.matches("<section>").count(), 2);
+        assert!(html.contains("<h1>Groceries</h1>"));
+        assert!(html.contains("<h1>Hardware &lt;misc&gt;</h1>"));
+        assert!(html.contains("<li>&#9744; salmon (2lb)</li>"));
+    }
+
+    #[test]
+    fn markdown_export_groups_items_under_category_headings() {
+        let markdown = MarkdownExporter.export(&test_list(), &["produce".to_string(), "meat".to_string()]);
+
+        assert_eq!(
+            markdown,
+            "# Groceries\n\
+             \n## produce\n\n- [ ] broccoli\n- [ ] apples (6)\n\
+             \n## meat\n\n- [ ] salmon (2lb)\n\
+             \n## other\n\n- [ ] foil\n"
+        );
+    }
+
+    #[test]
+    fn markdown_export_escapes_names_and_marks_checked_items() {
+        let list = List::builder("*Weekly*".to_string(), ObjectId::new())
+            .add_item(ListItem::builder("eggs").checked(true).build())
+            .build();
+
+        let markdown = MarkdownExporter.export(&list, &[]);
+
+        assert!(markdown.starts_with("# \\*Weekly\\*\n"));
+        assert!(markdown.contains("- [x] eggs\n"));
+    }
+
+    #[test]
+    fn exporters_are_looked_up_by_format() {
+        assert!(exporter("markdown").is_some());
+        assert!(exporter("text").is_some());
+        assert!(exporter("pdf").is_none());
+    }
+}
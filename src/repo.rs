@@ -1,13 +1,23 @@
 use crate::DbConfig;
 
-use super::list::{List, ListItem};
+use super::api_key::ApiKey;
+use super::favorite::Favorite;
+use super::household::Household;
+use super::invitation::Invitation;
+use super::list::{
+    JoinCode, List, ListItem, ListItemUpdate, ListMembers, ListSummary, ListUpdate, ListView,
+};
+use super::recurrence::Recurrence;
+use super::sort::{ListSort, SortSpec, StoreSort};
 use super::store::Store;
-use super::user::User;
+use super::template::Template;
+use super::user::{PasswordReset, User};
 use bson::{oid::ObjectId, Bson};
+use chrono::Utc;
 use color_eyre::Result;
-use futures::stream::StreamExt;
-use mongodb::{bson, bson::doc, error::Error as MongoDbError, Client, Database};
-use serde::{de::DeserializeOwned, Serialize};
+use futures::stream::{BoxStream, StreamExt};
+use mongodb::{bson, bson::{doc, DateTime, Document}, error::{Error as MongoDbError, ErrorKind, WriteFailure}, options::{FindOneOptions, FindOptions, UpdateOptions}, Client, Database};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 use std::fmt;
 
@@ -22,26 +32,300 @@ pub enum RepoError {
     #[error("could not deserialize from bson")]
     BsonDe(#[from] bson::de::Error),
     #[error("could not find the specified object: {0:?} in the collection: {1}")]
-    ObjectNotFound(ObjectId, Collections)
+    ObjectNotFound(ObjectId, Collections),
+    #[error("the object: {0:?} in the collection: {1} was modified concurrently")]
+    ConcurrentModification(ObjectId, Collections),
+    #[error("another user already has that email")]
+    EmailTaken,
+}
+
+/// Unique, so it keeps to the email index, and under a reserved domain, so
+/// no real address can match it.
+fn anonymized_email(id: &ObjectId) -> String {
+    format!("deleted-{}@grok-list.invalid", id.to_hex())
+}
+
+/// The code mongo gives a write that would break a unique index.
+const DUPLICATE_KEY: i32 = 11000;
+
+fn is_duplicate_key(err: &MongoDbError) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::WriteError(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY
+        }
+        _ => false,
+    }
+}
+
+/// Reports a write that clashed with the unique email index as `EmailTaken`.
+fn email_conflict(err: RepoError) -> RepoError {
+    match err {
+        RepoError::MongoError(err) if is_duplicate_key(&err) => RepoError::EmailTaken,
+        err => err,
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Collections {
+    ApiKeys,
+    Favorites,
+    Households,
+    Invitations,
     Lists,
     Stores,
+    Templates,
     Users,
 }
 
 impl std::fmt::Display for Collections {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
+            Collections::ApiKeys => write!(f, "api_keys"),
+            Collections::Favorites => write!(f, "favorites"),
+            Collections::Households => write!(f, "households"),
+            Collections::Invitations => write!(f, "invitations"),
             Collections::Lists => write!(f, "lists"),
             Collections::Stores => write!(f, "stores"),
+            Collections::Templates => write!(f, "templates"),
             Collections::Users => write!(f, "users"),
         }
     }
 }
 
+/// Cursor pagination: at most `limit` documents, starting after the document `after`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Page {
+    pub limit: Option<i64>,
+    pub after: Option<ObjectId>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListQuery {
+    pub archived: Option<bool>,
+    pub sort: SortSpec<ListSort>,
+}
+
+/// How many of a user's most used categories `get_user_stats` reports.
+const TOP_CATEGORIES: i64 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CategoryCount {
+    pub category: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UserStats {
+    pub lists: i64,
+    #[serde(rename(serialize = "archivedLists", deserialize = "archivedLists"))]
+    pub archived_lists: i64,
+    pub items: i64,
+    #[serde(rename(serialize = "checkedItems", deserialize = "checkedItems"))]
+    pub checked_items: i64,
+    #[serde(rename(serialize = "averageItemsPerList", deserialize = "averageItemsPerList"))]
+    pub average_items_per_list: f64,
+    #[serde(rename(serialize = "topCategories", deserialize = "topCategories"))]
+    pub top_categories: Vec<CategoryCount>,
+}
+
+/// Totals across every account, for admins. Trashed lists aren't counted in
+/// `lists`, but their items are in `items`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Usage {
+    pub users: i64,
+    #[serde(rename(serialize = "disabledUsers", deserialize = "disabledUsers"))]
+    pub disabled_users: i64,
+    pub lists: i64,
+    #[serde(rename(serialize = "trashedLists", deserialize = "trashedLists"))]
+    pub trashed_lists: i64,
+    pub items: i64,
+    pub stores: i64,
+    pub templates: i64,
+    #[serde(rename(serialize = "apiKeys", deserialize = "apiKeys"))]
+    pub api_keys: i64,
+}
+
+/// What `delete_account` does with the user's data.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeletionMode {
+    /// Removes the user and everything they made.
+    Delete,
+    /// Strips the user's name and email and keeps what other people still use.
+    Anonymize,
+}
+
+/// What `delete_account` removed. `keptLists` are the user's lists left for
+/// their collaborators and households when anonymizing.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AccountDeletion {
+    pub lists: i64,
+    #[serde(rename(serialize = "keptLists", deserialize = "keptLists"))]
+    pub kept_lists: i64,
+    pub stores: i64,
+    pub favorites: i64,
+    pub templates: i64,
+    #[serde(rename(serialize = "apiKeys", deserialize = "apiKeys"))]
+    pub api_keys: i64,
+    pub households: i64,
+    pub invitations: i64,
+}
+
+/// A list's items as mongo has them, for fixing up.
+#[derive(Deserialize, Debug)]
+struct RawItems {
+    _id: ObjectId,
+    items: Vec<Bson>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ItemCount {
+    count: i64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct StatsTotals {
+    lists: i64,
+    #[serde(rename = "archivedLists")]
+    archived_lists: i64,
+    items: i64,
+    #[serde(rename = "checkedItems")]
+    checked_items: i64,
+}
+
+/// The single document the stats pipeline produces, one field per `$facet`.
+#[derive(Deserialize, Debug)]
+struct StatsFacets {
+    totals: Vec<StatsTotals>,
+    categories: Vec<CategoryCount>,
+}
+
+impl From<StatsFacets> for UserStats {
+    fn from(facets: StatsFacets) -> Self {
+        let totals = facets.totals.into_iter().next().unwrap_or_default();
+        let average_items_per_list = if totals.lists > 0 {
+            totals.items as f64 / totals.lists as f64
+        } else {
+            0.0
+        };
+        UserStats {
+            lists: totals.lists,
+            archived_lists: totals.archived_lists,
+            items: totals.items,
+            checked_items: totals.checked_items,
+            average_items_per_list,
+            top_categories: facets.categories,
+        }
+    }
+}
+
+/// An item name and how many times the user has put it on a list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FrequentItem {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub category: Option<String>,
+    pub count: i64,
+}
+
+/// Adds `updatedAt: now` to the `$set` of a list update and bumps its version.
+fn touch(mut update: Document) -> Document {
+    let mut set = update
+        .get_document("$set")
+        .map(|set| set.clone())
+        .unwrap_or_default();
+    set.insert("updatedAt", Utc::now());
+    update.insert("$set", set);
+    update.insert("$inc", doc! { "version": 1 });
+    update
+}
+
+/// Lists the user owns, has been shared or can see through one of their
+/// `households`, leaving out trashed ones.
+fn user_lists_filter(
+    user_id: &ObjectId,
+    households: &[ObjectId],
+    archived: Option<bool>,
+) -> Document {
+    let mut filter = doc! {
+        "$or": [
+            { "userId": user_id.clone() },
+            { "sharedWith": user_id.clone() },
+            { "householdId": { "$in": households.to_vec() } },
+        ],
+        "deletedAt": { "$exists": false },
+    };
+    if let Some(archived) = archived {
+        filter.insert("archived", archived);
+    }
+    filter
+}
+
+/// The filter and options shared by the queries for a user's lists.
+fn user_lists_find(
+    user_id: &ObjectId,
+    households: &[ObjectId],
+    query: &ListQuery,
+    page: &Page,
+) -> (Document, FindOptions) {
+    let mut filter = user_lists_filter(user_id, households, query.archived);
+    if let Some(after) = &page.after {
+        filter.insert("_id", doc! { "$gt": after.clone() });
+    }
+    let mut options = FindOptions::builder().sort(query.sort.to_document()).build();
+    options.limit = page.limit;
+    (filter, options)
+}
+
+/// Matches the list only while it's still at the version a client last saw.
+/// Lists saved before versioning have no `version` and count as version 0.
+fn list_filter(id: &ObjectId, version: Option<i64>) -> Document {
+    let mut filter = doc! { "_id": id.clone() };
+    match version {
+        Some(0) => {
+            filter.insert("version", doc! { "$in": [0i64, Bson::Null] });
+        }
+        Some(version) => {
+            filter.insert("version", version);
+        }
+        None => {}
+    }
+    filter
+}
+
+/// Finds the lists matching `filter` with the members of their households
+/// alongside their owner and collaborators, as `ListMembers`.
+fn list_members_pipeline(filter: Document) -> Vec<Document> {
+    vec![
+        doc! { "$match": filter },
+        doc! { "$lookup": {
+            "from": Collections::Households.to_string(),
+            "localField": "householdId",
+            "foreignField": "_id",
+            "as": "household",
+        } },
+        doc! { "$project": {
+            "userId": 1,
+            "sharedWith": 1,
+            "householdMembers": {
+                "$ifNull": [{ "$arrayElemAt": ["$household.members", 0] }, []]
+            },
+        } },
+    ]
+}
+
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[derive(Clone)]
 pub struct Repo {
     data_store: Database,
 }
@@ -52,6 +336,58 @@ impl Repo {
         Ok(Repo { data_store: client })
     }
 
+    /// Builds the indexes the app relies on; running it again changes nothing.
+    /// It fails if existing users already share an email, as the unique index
+    /// on `email` can't be built until they're sorted out.
+    pub async fn ensure_indexes(&self) -> Result<(), RepoError> {
+        let command = doc! {
+            "createIndexes": Collections::Users.to_string(),
+            "indexes": [{ "key": { "email": 1 }, "name": "email_unique", "unique": true }],
+        };
+        self.data_store.run_command(command, None).await?;
+        Ok(())
+    }
+
+    /// Gives an `_id` to list items saved without one, which otherwise get a
+    /// new one each time they're read and so can't be changed or removed.
+    /// Returns how many lists were fixed. A list changed in the meantime is
+    /// left for the next run.
+    pub async fn backfill_item_ids(&self) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let filter = doc! { "items": { "$elemMatch": { "_id": { "$exists": false } } } };
+        let mut lists = collection.find(filter, None).await?;
+        let mut fixed = 0;
+        while let Some(list) = lists.next().await {
+            let list: RawItems = bson::from_document(list?)?;
+            let with_ids: Vec<Bson> = list
+                .items
+                .iter()
+                .map(|item| match item {
+                    Bson::Document(item) if !item.contains_key("_id") => {
+                        let mut item = item.clone();
+                        item.insert("_id", ObjectId::new());
+                        Bson::Document(item)
+                    }
+                    item => item.clone(),
+                })
+                .collect();
+            let update_result = collection
+                .update_one(
+                    doc! { "_id": list._id, "items": list.items },
+                    doc! { "$set": { "items": with_ids } },
+                    None,
+                )
+                .await?;
+            fixed += update_result.modified_count;
+        }
+        Ok(fixed)
+    }
+
+    pub async fn ping(&self) -> Result<(), RepoError> {
+        self.data_store.run_command(doc! { "ping": 1 }, None).await?;
+        Ok(())
+    }
+
     async fn add_document<T: Serialize + DeserializeOwned + fmt::Debug>(
         &self,
         document: &T,
@@ -87,18 +423,68 @@ impl Repo {
         Ok(document)
     }
 
-    async fn replace_document_by_id<T: Serialize + DeserializeOwned + fmt::Debug>(
+    async fn find_documents<T: Serialize + DeserializeOwned + fmt::Debug>(
+        &self,
+        filter: Document,
+        options: FindOptions,
+        collection: &Collections,
+    ) -> Result<Vec<T>, RepoError> {
+        let collection = self.data_store.collection(&collection.to_string());
+        let documents = collection
+            .find(filter, options)
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+
+        documents
+            .into_iter()
+            .map(|doc_result| Ok(bson::from_document::<T>(doc_result?)?))
+            .collect()
+    }
+
+    /// The user's documents in the collection, by `userId`, read as the stream
+    /// is consumed rather than all at once.
+    pub async fn stream_documents_by_user<T: DeserializeOwned + Send + 'static>(
+        &self,
+        user_id: &ObjectId,
+        collection: &Collections,
+    ) -> Result<BoxStream<'static, Result<T, RepoError>>, RepoError> {
+        let collection = self.data_store.collection(&collection.to_string());
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        let documents = collection
+            .find(doc! { "userId": user_id }, options)
+            .await?
+            .map(|doc_result| -> Result<T, RepoError> {
+                Ok(bson::from_document::<T>(doc_result?)?)
+            });
+
+        Ok(documents.boxed())
+    }
+
+    async fn update_document_by_id<T: Serialize + DeserializeOwned + fmt::Debug>(
         &self,
         id: &ObjectId,
-        document: &T,
+        update: Document,
         collection: &Collections,
     ) -> Result<Option<T>, RepoError> {
         let db_collection = self.data_store.collection(&collection.to_string());
 
-        let replace_result = db_collection
-            .replace_one(doc! { "_id": id }, bson::to_document(document)?, None)
+        let update_result = db_collection
+            .update_one(doc! { "_id": id }, update, None)
             .await?;
-        self.get_document_by_id(&id, collection).await
+        if update_result.matched_count == 0 {
+            return Ok(None);
+        }
+        self.get_document_by_id(id, collection).await
+    }
+
+    async fn count_documents(
+        &self,
+        filter: Document,
+        collection: &Collections,
+    ) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&collection.to_string());
+        Ok(collection.count_documents(filter, None).await?)
     }
 
     async fn delete_document_by_id(
@@ -112,37 +498,124 @@ impl Repo {
     }
 
     pub async fn add_list(&self, list: &List) -> Result<Option<List>, RepoError> {
-        self.add_document(list, &Collections::Lists).await
+        let now = DateTime::from(Utc::now());
+        let mut list = list.clone();
+        list.created_at = Some(now.clone());
+        list.updated_at = Some(now);
+        self.add_document(&list, &Collections::Lists).await
     }
 
     pub async fn get_list_by_id(&self, id: &ObjectId) -> Result<Option<List>, RepoError> {
         self.get_document_by_id(id, &Collections::Lists).await
     }
 
-    pub async fn delete_list_by_id(&self, id: &ObjectId) -> Result<i64, RepoError> {
-        self.delete_document_by_id(id, &Collections::Lists).await
+    /// With a `version` the update only applies while the list is still at that
+    /// version; otherwise it fails with `ConcurrentModification`.
+    async fn update_list_matching(
+        &self,
+        id: &ObjectId,
+        version: Option<i64>,
+        update: Document,
+    ) -> Result<Option<List>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let update_result = collection
+            .update_one(list_filter(id, version), update, None)
+            .await?;
+        if update_result.matched_count == 0 {
+            return match (self.get_list_by_id(id).await?, version) {
+                (Some(_), Some(_)) => Err(RepoError::ConcurrentModification(id.clone(), Collections::Lists)),
+                _ => Ok(None),
+            };
+        }
+        self.get_list_by_id(id).await
     }
 
-    pub async fn add_store(&self, store: &Store) -> Result<Option<Store>, RepoError> {
-        self.add_document(store, &Collections::Stores).await
+    pub async fn update_list(
+        &self,
+        id: &ObjectId,
+        update: &ListUpdate,
+        version: Option<i64>,
+    ) -> Result<Option<List>, RepoError> {
+        let fields = bson::to_document(update)?;
+        if fields.is_empty() {
+            return self.get_list_by_id(id).await;
+        }
+        self.update_list_matching(id, version, touch(doc! { "$set": fields })).await
     }
 
-    pub async fn get_store_by_id(&self, id: &ObjectId) -> Result<Option<Store>, RepoError> {
-        self.get_document_by_id(id, &Collections::Stores).await
+    pub async fn update_list_view(
+        &self,
+        id: &ObjectId,
+        view: &ListView,
+        version: Option<i64>,
+    ) -> Result<Option<List>, RepoError> {
+        let view = bson::to_bson(view)?;
+        self.update_list_matching(id, version, touch(doc! { "$set": { "view": view } })).await
     }
 
-    pub async fn delete_store_by_id(&self, id: &ObjectId) -> Result<i64, RepoError> {
-        self.delete_document_by_id(id, &Collections::Stores).await
+    pub async fn set_list_archived(&self, id: &ObjectId, archived: bool) -> Result<Option<List>, RepoError> {
+        self.update_document_by_id(id, touch(doc! { "$set": { "archived": archived } }), &Collections::Lists)
+            .await
     }
 
-    pub async fn add_user(&self, user: &User) -> Result<Option<User>, RepoError> {
-        self.add_document(user, &Collections::Users).await
+    pub async fn add_list_collaborator(
+        &self,
+        id: &ObjectId,
+        user_id: &ObjectId,
+    ) -> Result<Option<List>, RepoError> {
+        self.update_document_by_id(
+            id,
+            touch(doc! { "$addToSet": { "sharedWith": user_id.clone() } }),
+            &Collections::Lists,
+        )
+        .await
     }
 
-    pub async fn get_user_by_name(&self, name: &str) -> Result<Option<User>, RepoError> {
-        let collection = self.data_store.collection(&Collections::Users.to_string());
+    /// Stops suggesting `name` for this list, ignoring case.
+    pub async fn dismiss_list_suggestion(
+        &self,
+        id: &ObjectId,
+        name: &str,
+    ) -> Result<Option<List>, RepoError> {
+        self.update_document_by_id(
+            id,
+            touch(doc! { "$addToSet": { "dismissedSuggestions": name.trim().to_lowercase() } }),
+            &Collections::Lists,
+        )
+        .await
+    }
+
+    pub async fn remove_list_collaborator(
+        &self,
+        id: &ObjectId,
+        user_id: &ObjectId,
+    ) -> Result<Option<List>, RepoError> {
+        self.update_document_by_id(
+            id,
+            touch(doc! { "$pull": { "sharedWith": user_id.clone() } }),
+            &Collections::Lists,
+        )
+        .await
+    }
+
+    /// Replaces the list's public share token; `None` revokes it.
+    pub async fn set_list_share_token(
+        &self,
+        id: &ObjectId,
+        token: Option<&str>,
+    ) -> Result<Option<List>, RepoError> {
+        let update = match token {
+            Some(token) => doc! { "$set": { "shareToken": token } },
+            None => doc! { "$unset": { "shareToken": "" } },
+        };
+        self.update_document_by_id(id, touch(update), &Collections::Lists).await
+    }
+
+    /// Trashed lists aren't served through their share token.
+    pub async fn get_list_by_share_token(&self, token: &str) -> Result<Option<List>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
         let document = collection
-            .find_one(doc! { "name": name}, None)
+            .find_one(doc! { "shareToken": token, "deletedAt": { "$exists": false } }, None)
             .await?
             .map(bson::from_document)
             .transpose()?;
@@ -150,235 +623,2505 @@ impl Repo {
         Ok(document)
     }
 
-    pub async fn get_lists_by_user(
+    pub async fn set_list_join_code(
         &self,
-        user_id: &ObjectId,
-    ) -> Result<Vec<Result<List, RepoError>>, RepoError> {
+        id: &ObjectId,
+        join_code: &JoinCode,
+    ) -> Result<Option<List>, RepoError> {
+        let join_code = bson::to_bson(join_code)?;
+        self.update_document_by_id(id, touch(doc! { "$set": { "joinCode": join_code } }), &Collections::Lists)
+            .await
+    }
+
+    /// Expired codes and trashed lists don't match.
+    pub async fn get_list_by_join_code(&self, code: &str) -> Result<Option<List>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let filter = doc! {
+            "joinCode.code": code,
+            "joinCode.expiresAt": { "$gt": Utc::now() },
+            "deletedAt": { "$exists": false },
+        };
+        let document = collection
+            .find_one(filter, None)
+            .await?
+            .map(bson::from_document)
+            .transpose()?;
+
+        Ok(document)
+    }
+
+    pub async fn delete_list_by_id(&self, id: &ObjectId) -> Result<i64, RepoError> {
+        self.delete_document_by_id(id, &Collections::Lists).await
+    }
+
+    /// The owner, collaborators and household members of the list, trashed or
+    /// not, without fetching its items.
+    pub async fn get_list_members(&self, id: &ObjectId) -> Result<Option<ListMembers>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let members = collection
+            .aggregate(list_members_pipeline(doc! { "_id": id }), None)
+            .await?
+            .next()
+            .await
+            .transpose()?
+            .map(bson::from_document)
+            .transpose()?;
+
+        Ok(members)
+    }
+
+    /// The members of each of the lists that exists. Lists in the trash are
+    /// only included with `include_trashed`.
+    pub async fn get_list_members_by_ids(
+        &self,
+        ids: &[ObjectId],
+        include_trashed: bool,
+    ) -> Result<Vec<ListMembers>, RepoError> {
         let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let mut filter = doc! { "_id": { "$in": ids.to_vec() } };
+        if !include_trashed {
+            filter.insert("deletedAt", doc! { "$exists": false });
+        }
         let documents = collection
-            .find(doc! { "userId": user_id }, None)
+            .aggregate(list_members_pipeline(filter), None)
             .await?
+            .collect::<Vec<_>>()
+            .await;
+
+        documents
+            .into_iter()
             .map(|doc_result| {
                 doc_result
                     .map_err(RepoError::from)
-                    .and_then(|doc| {
-                        bson::from_document::<List>(doc).map_err(RepoError::from)
-                    })
+                    .and_then(|doc| bson::from_document(doc).map_err(RepoError::from))
             })
-            .collect::<Vec<Result<List, RepoError>>>()
-            .await;
-
-        Ok(documents)
+            .collect()
     }
 
-    pub async fn add_list_item(
+    /// Moves the list into the household, or out of any with `None`.
+    pub async fn set_list_household(
         &self,
-        list_id: &ObjectId,
-        item: &ListItem,
+        id: &ObjectId,
+        household_id: Option<&ObjectId>,
     ) -> Result<Option<List>, RepoError> {
-        let mut list = self.get_list_by_id(list_id)
-                       .await?
-                       .ok_or_else(|| RepoError::ObjectNotFound(list_id.clone(), Collections::Lists))?;
-        list.add_item(item.clone());
-        self.replace_document_by_id(list_id, &list, &Collections::Lists).await
+        let update = match household_id {
+            Some(household_id) => doc! { "$set": { "householdId": household_id.clone() } },
+            None => doc! { "$unset": { "householdId": "" } },
+        };
+        self.update_document_by_id(id, touch(update), &Collections::Lists).await
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::test::clean_up_db;
-    use crate::DbConfig;
-    use super::super::list::ListItem;
-    use super::*;
-    use mongodb::bson::oid::ObjectId;
+    pub async fn set_lists_archived(&self, ids: &[ObjectId], archived: bool) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let update_result = collection
+            .update_many(
+                doc! { "_id": { "$in": ids.to_vec() } },
+                touch(doc! { "$set": { "archived": archived } }),
+                None,
+            )
+            .await?;
+        Ok(update_result.modified_count)
+    }
 
-    fn db_config() -> DbConfig {
-        DbConfig {
-            database_url: "mongodb://localhost:27017/".to_string(),
-            database_name: "grok_list_test".to_string(),
-        }
+    /// Takes lists out of both the archive and the trash.
+    pub async fn restore_lists_by_ids(&self, ids: &[ObjectId]) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let update_result = collection
+            .update_many(
+                doc! { "_id": { "$in": ids.to_vec() } },
+                touch(doc! { "$set": { "archived": false }, "$unset": { "deletedAt": "" } }),
+                None,
+            )
+            .await?;
+        Ok(update_result.modified_count)
     }
 
-    #[derive(Error, Debug)]
-    enum TestError {
-        #[error("got None when reading our writes from mongo")]
-        NoneFromMongo,
+    /// Moves lists to the trash. Lists already in the trash are left alone.
+    pub async fn trash_lists_by_ids(&self, ids: &[ObjectId]) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let update_result = collection
+            .update_many(
+                doc! { "_id": { "$in": ids.to_vec() }, "deletedAt": { "$exists": false } },
+                touch(doc! { "$set": { "deletedAt": Utc::now() } }),
+                None,
+            )
+            .await?;
+        Ok(update_result.modified_count)
     }
 
-    #[tokio::test]
-    async fn can_insert_and_retrieve_lists_by_id() -> Result<()> {
-        let repo = Repo::new(&db_config())
-            .await
-            .expect("Couldn't connect to mongo, is it running?");
-        let list_item = ListItem::builder("salmon")
-            .category("meat")
-            .amount("2lb")
-            .build();
-        let list = List::builder("test_list".to_string(), ObjectId::new())
-            .add_item(list_item)
-            .build();
+    /// Returns `None` if the list doesn't exist or isn't in the trash.
+    pub async fn restore_list(&self, id: &ObjectId) -> Result<Option<List>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let update_result = collection
+            .update_one(
+                doc! { "_id": id.clone(), "deletedAt": { "$exists": true } },
+                touch(doc! { "$unset": { "deletedAt": "" } }),
+                None,
+            )
+            .await?;
+        if update_result.matched_count == 0 {
+            return Ok(None);
+        }
 
-        let inserted_list = repo
-            .add_list(&list)
-            .await?
-            .ok_or(TestError::NoneFromMongo)?;
-        let retrieved = repo
-            .get_list_by_id(&inserted_list._id.clone().expect("Inserted list had no _id"))
-            .await
-            .expect("Error finding list")
-            .unwrap_or_else(|| {
-                panic!(
-                    "List with id: {:?} did not exist",
-                    inserted_list._id.clone()
-                )
-            });
+        self.get_list_by_id(id).await
+    }
 
-        assert_eq!(retrieved.user_id, list.user_id);
-        assert_eq!(retrieved.items, list.items);
+    pub async fn get_trashed_lists_by_user(&self, user_id: &ObjectId) -> Result<Vec<List>, RepoError> {
+        let filter = doc! { "userId": user_id, "deletedAt": { "$exists": true } };
+        let options = FindOptions::builder().sort(doc! { "deletedAt": -1, "_id": 1 }).build();
+        self.find_documents(filter, options, &Collections::Lists).await
+    }
 
-        let items_deleted = repo
-            .delete_list_by_id(&inserted_list._id.expect("Inserted list had no id"))
+    pub async fn add_list_items(
+        &self,
+        list_id: &ObjectId,
+        items: &[ListItem],
+    ) -> Result<Option<List>, RepoError> {
+        let items = bson::to_bson(items)?;
+        self.update_document_by_id(
+            list_id,
+            touch(doc! { "$push": { "items": { "$each": items } } }),
+            &Collections::Lists,
+        )
+        .await
+    }
+
+    pub async fn remove_list_item(
+        &self,
+        list_id: &ObjectId,
+        item_id: &ObjectId,
+    ) -> Result<Option<List>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let update_result = collection
+            .update_one(
+                doc! { "_id": list_id },
+                touch(doc! { "$pull": { "items": { "_id": item_id } } }),
+                None,
+            )
+            .await?;
+        if update_result.matched_count == 0 {
+            return Ok(None);
+        }
+        if update_result.modified_count == 0 {
+            return Err(RepoError::ObjectNotFound(item_id.clone(), Collections::Lists));
+        }
+        self.get_list_by_id(list_id).await
+    }
+
+    pub async fn update_list_item(
+        &self,
+        list_id: &ObjectId,
+        item_id: &ObjectId,
+        update: &ListItemUpdate,
+        version: Option<i64>,
+    ) -> Result<Option<List>, RepoError> {
+        let mut fields = Document::new();
+        if let Some(name) = &update.name {
+            fields.insert("items.$.name", name.clone());
+        }
+        if let Some(category) = &update.category {
+            fields.insert("items.$.category", category.to_lowercase());
+        }
+        if let Some(amount) = &update.amount {
+            fields.insert("items.$.amount", amount.clone());
+        }
+        if let Some(checked) = update.checked {
+            fields.insert("items.$.checked", checked);
+        }
+        if let Some(need_by) = &update.need_by {
+            fields.insert("items.$.needBy", need_by.clone());
+        }
+
+        if !fields.is_empty() {
+            let collection = self.data_store.collection(&Collections::Lists.to_string());
+            let mut filter = list_filter(list_id, version);
+            filter.insert("items._id", item_id.clone());
+            let update_result = collection
+                .update_one(filter, touch(doc! { "$set": fields }), None)
+                .await?;
+            if update_result.matched_count == 1 {
+                return self.get_list_by_id(list_id).await;
+            }
+        }
+
+        match self.get_list_by_id(list_id).await? {
+            Some(list) if version.map_or(false, |version| list.version != version) => {
+                Err(RepoError::ConcurrentModification(list_id.clone(), Collections::Lists))
+            }
+            Some(list) if list.items.iter().any(|item| item.id() == item_id) => Ok(Some(list)),
+            Some(_) => Err(RepoError::ObjectNotFound(item_id.clone(), Collections::Lists)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn set_list_item_checked(
+        &self,
+        list_id: &ObjectId,
+        item_id: &ObjectId,
+        checked: bool,
+    ) -> Result<Option<List>, RepoError> {
+        let update = ListItemUpdate {
+            checked: Some(checked),
+            ..ListItemUpdate::default()
+        };
+        self.update_list_item(list_id, item_id, &update, None).await
+    }
+
+    pub async fn clear_checked_items(&self, list_id: &ObjectId) -> Result<Option<List>, RepoError> {
+        self.update_document_by_id(
+            list_id,
+            touch(doc! { "$pull": { "items": { "checked": true } } }),
+            &Collections::Lists,
+        )
+        .await
+    }
+
+    pub async fn add_store(&self, store: &Store) -> Result<Option<Store>, RepoError> {
+        self.add_document(store, &Collections::Stores).await
+    }
+
+    pub async fn get_store_by_id(&self, id: &ObjectId) -> Result<Option<Store>, RepoError> {
+        self.get_document_by_id(id, &Collections::Stores).await
+    }
+
+    /// The user's own store with the name. Shared stores aren't included.
+    pub async fn get_store_by_name(
+        &self,
+        user_id: &ObjectId,
+        name: &str,
+    ) -> Result<Option<Store>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Stores.to_string());
+        let document = collection
+            .find_one(doc! { "userId": user_id, "name": name }, None)
+            .await?
+            .map(bson::from_document)
+            .transpose()?;
+
+        Ok(document)
+    }
+
+    /// Replaces the categories of the user's store with the same name,
+    /// creating it for them if needed.
+    pub async fn upsert_store_by_name(
+        &self,
+        user_id: &ObjectId,
+        store: &Store,
+    ) -> Result<Option<Store>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Stores.to_string());
+        let options = UpdateOptions::builder().upsert(true).build();
+        collection
+            .update_one(
+                doc! { "userId": user_id, "name": store.name.clone() },
+                doc! { "$set": { "categories": store.categories.clone() } },
+                options,
+            )
+            .await?;
+        self.get_store_by_name(user_id, &store.name).await
+    }
+
+    /// The user's stores, their households' and the shared ones, by name unless
+    /// `sort` says otherwise. `page.after` continues from the given store and
+    /// only applies to the default order.
+    pub async fn get_all_stores(
+        &self,
+        user_id: &ObjectId,
+        page: &Page,
+        sort: &SortSpec<StoreSort>,
+    ) -> Result<Vec<Store>, RepoError> {
+        let households = self.get_household_ids(user_id).await?;
+        let mut filter = doc! {
+            "$or": [
+                { "userId": user_id },
+                { "userId": Bson::Null },
+                { "householdId": { "$in": households } },
+            ]
+        };
+        if let Some(after) = &page.after {
+            let after_store = self
+                .get_store_by_id(after)
+                .await?
+                .ok_or_else(|| RepoError::ObjectNotFound(after.clone(), Collections::Stores))?;
+            filter = doc! {
+                "$and": [
+                    filter,
+                    {
+                        "$or": [
+                            { "name": { "$gt": after_store.name.clone() } },
+                            { "name": after_store.name, "_id": { "$gt": after } },
+                        ]
+                    },
+                ]
+            };
+        }
+        let sort = if sort.is_empty() { doc! { "name": 1, "_id": 1 } } else { sort.to_document() };
+        let mut options = FindOptions::builder().sort(sort).build();
+        options.limit = page.limit;
+        self.find_documents(filter, options, &Collections::Stores).await
+    }
+
+    /// Replaces the store's categories only if they still equal `current`, so a
+    /// reorder can't silently drop a category added in the meantime.
+    pub async fn replace_store_categories(
+        &self,
+        id: &ObjectId,
+        current: &[String],
+        categories: &[String],
+    ) -> Result<Option<Store>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Stores.to_string());
+        let update_result = collection
+            .update_one(
+                doc! { "_id": id, "categories": current.to_vec() },
+                doc! { "$set": { "categories": categories.to_vec() } },
+                None,
+            )
+            .await?;
+        if update_result.matched_count == 0 {
+            return match self.get_store_by_id(id).await? {
+                Some(_) => Err(RepoError::ConcurrentModification(id.clone(), Collections::Stores)),
+                None => Ok(None),
+            };
+        }
+        self.get_store_by_id(id).await
+    }
+
+    /// Appends the category unless the store already has it. Unlike
+    /// `replace_store_categories` this can't drop a concurrent addition.
+    pub async fn add_store_category(
+        &self,
+        id: &ObjectId,
+        category: &str,
+    ) -> Result<Option<Store>, RepoError> {
+        self.update_document_by_id(
+            id,
+            doc! { "$addToSet": { "categories": category.to_lowercase() } },
+            &Collections::Stores,
+        )
+        .await
+    }
+
+    /// Moves the store into the household, or out of any with `None`.
+    pub async fn set_store_household(
+        &self,
+        id: &ObjectId,
+        household_id: Option<&ObjectId>,
+    ) -> Result<Option<Store>, RepoError> {
+        let update = match household_id {
+            Some(household_id) => doc! { "$set": { "householdId": household_id.clone() } },
+            None => doc! { "$unset": { "householdId": "" } },
+        };
+        self.update_document_by_id(id, update, &Collections::Stores).await
+    }
+
+    pub async fn delete_store_by_id(&self, id: &ObjectId) -> Result<i64, RepoError> {
+        self.delete_document_by_id(id, &Collections::Stores).await
+    }
+
+    /// Stars an item for the user. Starring a name they've already starred,
+    /// ignoring case, returns the existing favorite.
+    pub async fn add_favorite(&self, favorite: &Favorite) -> Result<Option<Favorite>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Favorites.to_string());
+        let pattern = format!("^{}$", escape_regex(&favorite.name));
+        let existing = collection
+            .find_one(
+                doc! { "userId": &favorite.user_id, "name": { "$regex": pattern, "$options": "i" } },
+                None,
+            )
+            .await?
+            .map(bson::from_document)
+            .transpose()?;
+        match existing {
+            Some(existing) => Ok(Some(existing)),
+            None => self.add_document(favorite, &Collections::Favorites).await,
+        }
+    }
+
+    /// The user's favorites by name.
+    pub async fn get_favorites_by_user(&self, user_id: &ObjectId) -> Result<Vec<Favorite>, RepoError> {
+        let options = FindOptions::builder().sort(doc! { "name": 1, "_id": 1 }).build();
+        self.find_documents(doc! { "userId": user_id }, options, &Collections::Favorites).await
+    }
+
+    pub async fn delete_favorite(&self, user_id: &ObjectId, id: &ObjectId) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Favorites.to_string());
+        let delete_result = collection
+            .delete_one(doc! { "_id": id, "userId": user_id }, None)
+            .await?;
+        Ok(delete_result.deleted_count)
+    }
+
+    pub async fn delete_favorites_by_user(&self, user_id: &ObjectId) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Favorites.to_string());
+        let delete_result = collection.delete_many(doc! { "userId": user_id }, None).await?;
+        Ok(delete_result.deleted_count)
+    }
+
+    pub async fn add_household(&self, household: &Household) -> Result<Option<Household>, RepoError> {
+        let mut household = household.clone();
+        household.created_at = Some(DateTime::from(Utc::now()));
+        self.add_document(&household, &Collections::Households).await
+    }
+
+    pub async fn get_household_by_id(&self, id: &ObjectId) -> Result<Option<Household>, RepoError> {
+        self.get_document_by_id(id, &Collections::Households).await
+    }
+
+    pub async fn get_households_by_user(&self, user_id: &ObjectId) -> Result<Vec<Household>, RepoError> {
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        self.find_documents(doc! { "members": user_id }, options, &Collections::Households)
+            .await
+    }
+
+    async fn get_household_ids(&self, user_id: &ObjectId) -> Result<Vec<ObjectId>, RepoError> {
+        let households = self.get_households_by_user(user_id).await?;
+        Ok(households.into_iter().filter_map(|household| household._id).collect())
+    }
+
+    pub async fn add_household_member(
+        &self,
+        id: &ObjectId,
+        user_id: &ObjectId,
+    ) -> Result<Option<Household>, RepoError> {
+        self.update_document_by_id(
+            id,
+            doc! { "$addToSet": { "members": user_id.clone() } },
+            &Collections::Households,
+        )
+        .await
+    }
+
+    /// Removing the last member deletes the household; see
+    /// `delete_household_by_id`. The household is returned as it was left.
+    pub async fn remove_household_member(
+        &self,
+        id: &ObjectId,
+        user_id: &ObjectId,
+    ) -> Result<Option<Household>, RepoError> {
+        let household: Option<Household> = self
+            .update_document_by_id(
+                id,
+                doc! { "$pull": { "members": user_id.clone() } },
+                &Collections::Households,
+            )
+            .await?;
+        if let Some(household) = &household {
+            if household.members.is_empty() {
+                self.delete_household_by_id(id).await?;
+            }
+        }
+        Ok(household)
+    }
+
+    /// Takes the user out of every household they're in.
+    pub async fn leave_households(&self, user_id: &ObjectId) -> Result<i64, RepoError> {
+        let mut left = 0;
+        for id in self.get_household_ids(user_id).await? {
+            if self.remove_household_member(&id, user_id).await?.is_some() {
+                left += 1;
+            }
+        }
+        Ok(left)
+    }
+
+    /// The household's lists and stores go back to belonging only to the users
+    /// who made them.
+    pub async fn delete_household_by_id(&self, id: &ObjectId) -> Result<i64, RepoError> {
+        let lists = self.data_store.collection(&Collections::Lists.to_string());
+        lists
+            .update_many(
+                doc! { "householdId": id },
+                touch(doc! { "$unset": { "householdId": "" } }),
+                None,
+            )
+            .await?;
+        let stores = self.data_store.collection(&Collections::Stores.to_string());
+        stores
+            .update_many(doc! { "householdId": id }, doc! { "$unset": { "householdId": "" } }, None)
+            .await?;
+        self.delete_document_by_id(id, &Collections::Households).await
+    }
+
+    pub async fn add_invitation(&self, invitation: &Invitation) -> Result<Option<Invitation>, RepoError> {
+        self.add_document(invitation, &Collections::Invitations).await
+    }
+
+    /// The invitation with this token hash, unless it's been accepted or has
+    /// expired.
+    pub async fn get_pending_invitation(
+        &self,
+        token_hash: &str,
+        now: DateTime,
+    ) -> Result<Option<Invitation>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Invitations.to_string());
+        let filter = doc! {
+            "tokenHash": token_hash,
+            "acceptedAt": { "$exists": false },
+            "expiresAt": { "$gt": now },
+        };
+        let document = collection
+            .find_one(filter, None)
+            .await?
+            .map(bson::from_document)
+            .transpose()?;
+
+        Ok(document)
+    }
+
+    /// Marks the invitation accepted. Invitations can only be accepted once,
+    /// so this is `false` if it already was.
+    pub async fn accept_invitation(&self, id: &ObjectId, now: DateTime) -> Result<bool, RepoError> {
+        let collection = self.data_store.collection(&Collections::Invitations.to_string());
+        let update_result = collection
+            .update_one(
+                doc! { "_id": id, "acceptedAt": { "$exists": false } },
+                doc! { "$set": { "acceptedAt": now } },
+                None,
+            )
+            .await?;
+        Ok(update_result.matched_count > 0)
+    }
+
+    pub async fn add_api_key(&self, api_key: &ApiKey) -> Result<Option<ApiKey>, RepoError> {
+        self.add_document(api_key, &Collections::ApiKeys).await
+    }
+
+    /// The user's API keys, oldest first.
+    pub async fn get_api_keys_by_user(&self, user_id: &ObjectId) -> Result<Vec<ApiKey>, RepoError> {
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        self.find_documents(doc! { "userId": user_id }, options, &Collections::ApiKeys).await
+    }
+
+    pub async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, RepoError> {
+        let collection = self.data_store.collection(&Collections::ApiKeys.to_string());
+        let document = collection
+            .find_one(doc! { "keyHash": key_hash }, None)
+            .await?
+            .map(bson::from_document)
+            .transpose()?;
+        Ok(document)
+    }
+
+    pub async fn delete_api_key(&self, user_id: &ObjectId, id: &ObjectId) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::ApiKeys.to_string());
+        let delete_result = collection
+            .delete_one(doc! { "_id": id, "userId": user_id }, None)
+            .await?;
+        Ok(delete_result.deleted_count)
+    }
+
+    pub async fn delete_api_keys_by_user(&self, user_id: &ObjectId) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::ApiKeys.to_string());
+        let delete_result = collection.delete_many(doc! { "userId": user_id }, None).await?;
+        Ok(delete_result.deleted_count)
+    }
+
+    pub async fn add_template(&self, template: &Template) -> Result<Option<Template>, RepoError> {
+        self.add_document(template, &Collections::Templates).await
+    }
+
+    pub async fn get_template_by_id(&self, id: &ObjectId) -> Result<Option<Template>, RepoError> {
+        self.get_document_by_id(id, &Collections::Templates).await
+    }
+
+    /// The user's templates by name.
+    pub async fn get_templates_by_user(&self, user_id: &ObjectId) -> Result<Vec<Template>, RepoError> {
+        let options = FindOptions::builder().sort(doc! { "name": 1, "_id": 1 }).build();
+        self.find_documents(doc! { "userId": user_id }, options, &Collections::Templates).await
+    }
+
+    /// Sets or, with `None`, clears the template's schedule and its next run.
+    pub async fn set_template_recurrence(
+        &self,
+        id: &ObjectId,
+        recurrence: Option<(&Recurrence, DateTime)>,
+    ) -> Result<Option<Template>, RepoError> {
+        let update = match recurrence {
+            Some((recurrence, next_run_at)) => doc! { "$set": {
+                "recurrence": bson::to_bson(recurrence)?,
+                "nextRunAt": next_run_at,
+            } },
+            None => doc! { "$unset": { "recurrence": "", "nextRunAt": "" } },
+        };
+        self.update_document_by_id(id, update, &Collections::Templates).await
+    }
+
+    /// Recurring templates whose next run is at or before `now`.
+    pub async fn get_due_templates(&self, now: DateTime) -> Result<Vec<Template>, RepoError> {
+        let filter = doc! { "nextRunAt": { "$lte": now } };
+        self.find_documents(filter, FindOptions::default(), &Collections::Templates).await
+    }
+
+    /// Moves the template's next run from `scheduled` to `next`. Only one caller
+    /// can win for a given run, so it's safe with several instances running.
+    pub async fn advance_template_run(
+        &self,
+        id: &ObjectId,
+        scheduled: &DateTime,
+        next: DateTime,
+    ) -> Result<bool, RepoError> {
+        let collection = self.data_store.collection(&Collections::Templates.to_string());
+        let update_result = collection
+            .update_one(
+                doc! { "_id": id, "nextRunAt": scheduled.clone() },
+                doc! { "$set": { "nextRunAt": next } },
+                None,
+            )
+            .await?;
+        Ok(update_result.modified_count == 1)
+    }
+
+    pub async fn delete_template_by_id(&self, id: &ObjectId) -> Result<i64, RepoError> {
+        self.delete_document_by_id(id, &Collections::Templates).await
+    }
+
+    pub async fn delete_templates_by_user(&self, user_id: &ObjectId) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Templates.to_string());
+        let delete_result = collection.delete_many(doc! { "userId": user_id }, None).await?;
+        Ok(delete_result.deleted_count)
+    }
+
+    /// `EmailTaken` if someone already has the user's email.
+    pub async fn add_user(&self, user: &User) -> Result<Option<User>, RepoError> {
+        self.add_document(user, &Collections::Users).await.map_err(email_conflict)
+    }
+
+    pub async fn get_user_by_id(&self, id: &ObjectId) -> Result<Option<User>, RepoError> {
+        self.get_document_by_id(id, &Collections::Users).await
+    }
+
+    /// Changes the user's name and email. Their password is left alone.
+    /// `EmailTaken` if someone else already has the email.
+    pub async fn update_user(&self, id: &ObjectId, user: &User) -> Result<Option<User>, RepoError> {
+        let update = doc! { "$set": { "name": &user.name, "email": &user.email } };
+        self.update_document_by_id(id, update, &Collections::Users)
+            .await
+            .map_err(email_conflict)
+    }
+
+    pub async fn delete_user_by_id(&self, id: &ObjectId) -> Result<i64, RepoError> {
+        self.delete_document_by_id(id, &Collections::Users).await
+    }
+
+    /// Blanks the user's name, swaps their email for a placeholder no one can
+    /// have and takes away their password, so nobody can log in as them
+    /// again. Outstanding tokens and sessions stop working too.
+    async fn anonymize_user(&self, id: &ObjectId, now: DateTime) -> Result<Option<User>, RepoError> {
+        let update = doc! {
+            "$set": { "name": "", "email": anonymized_email(id), "deletedAt": now },
+            "$unset": { "passwordHash": "", "passwordReset": "" },
+            "$inc": { "credentialsVersion": 1 },
+        };
+        self.update_document_by_id(id, update, &Collections::Users).await
+    }
+
+    /// Closes the user's account. Either way their favorites, templates, API
+    /// keys and the invitations they sent are deleted, and they leave their
+    /// households and the lists shared with them. `Delete` then removes the
+    /// user and every list and store they made. `Anonymize` keeps the user,
+    /// anonymized, as the owner of lists shared with someone or in a household
+    /// and of household stores, and deletes the rest. `None` if there's no
+    /// such user.
+    pub async fn delete_account(
+        &self,
+        user_id: &ObjectId,
+        mode: DeletionMode,
+        now: DateTime,
+    ) -> Result<Option<AccountDeletion>, RepoError> {
+        // The account goes first so nothing can be added while the rest is cleared.
+        let found = match mode {
+            DeletionMode::Delete => self.delete_user_by_id(user_id).await? > 0,
+            DeletionMode::Anonymize => self.anonymize_user(user_id, now).await?.is_some(),
+        };
+        if !found {
+            return Ok(None);
+        }
+
+        // Before the lists, as the last member leaving drops a household's lists.
+        let households = self.leave_households(user_id).await?;
+        let lists = self.data_store.collection(&Collections::Lists.to_string());
+        lists
+            .update_many(
+                doc! { "sharedWith": user_id },
+                touch(doc! { "$pull": { "sharedWith": user_id.clone() } }),
+                None,
+            )
+            .await?;
+        let (deleted_lists, store_filter) = match mode {
+            DeletionMode::Delete => {
+                (self.delete_lists_by_user(user_id).await?, doc! { "userId": user_id })
+            }
+            DeletionMode::Anonymize => {
+                let unshared = doc! {
+                    "userId": user_id,
+                    "sharedWith.0": { "$exists": false },
+                    "householdId": { "$exists": false },
+                };
+                let deleted = lists.delete_many(unshared, None).await?.deleted_count;
+                (deleted, doc! { "userId": user_id, "householdId": { "$exists": false } })
+            }
+        };
+        let kept_lists = lists.count_documents(doc! { "userId": user_id }, None).await?;
+        let stores = self.data_store.collection(&Collections::Stores.to_string());
+        let deleted_stores = stores.delete_many(store_filter, None).await?.deleted_count;
+        let invitations = self.data_store.collection(&Collections::Invitations.to_string());
+        let deleted_invitations = invitations
+            .delete_many(doc! { "invitedBy": user_id }, None)
+            .await?
+            .deleted_count;
+
+        Ok(Some(AccountDeletion {
+            lists: deleted_lists,
+            kept_lists,
+            stores: deleted_stores,
+            favorites: self.delete_favorites_by_user(user_id).await?,
+            templates: self.delete_templates_by_user(user_id).await?,
+            api_keys: self.delete_api_keys_by_user(user_id).await?,
+            households,
+            invitations: deleted_invitations,
+        }))
+    }
+
+    /// Users in insertion order; `page.after` continues from the given user.
+    pub async fn get_users(&self, page: &Page) -> Result<Vec<User>, RepoError> {
+        let filter = match &page.after {
+            Some(after) => doc! { "_id": { "$gt": after.clone() } },
+            None => doc! {},
+        };
+        let mut options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        options.limit = page.limit;
+        self.find_documents(filter, options, &Collections::Users).await
+    }
+
+    pub async fn count_users(&self) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Users.to_string());
+        Ok(collection.count_documents(doc! {}, None).await?)
+    }
+
+    /// Users whose name or email contains `search`, ignoring case, paged like
+    /// `get_users`.
+    pub async fn search_users(&self, search: &str, page: &Page) -> Result<Vec<User>, RepoError> {
+        let pattern = doc! { "$regex": escape_regex(search), "$options": "i" };
+        let mut filter = doc! { "$or": [{ "name": pattern.clone() }, { "email": pattern }] };
+        if let Some(after) = &page.after {
+            filter.insert("_id", doc! { "$gt": after.clone() });
+        }
+        let mut options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        options.limit = page.limit;
+        self.find_documents(filter, options, &Collections::Users).await
+    }
+
+    /// Disables the user as of `disabled_at`, or enables them again with `None`.
+    pub async fn set_user_disabled(
+        &self,
+        id: &ObjectId,
+        disabled_at: Option<DateTime>,
+    ) -> Result<Option<User>, RepoError> {
+        let update = match disabled_at {
+            Some(disabled_at) => doc! { "$set": { "disabledAt": disabled_at } },
+            None => doc! { "$unset": { "disabledAt": "" } },
+        };
+        self.update_document_by_id(id, update, &Collections::Users).await
+    }
+
+    /// Counts across every account. Items are summed by mongo, so no list is
+    /// loaded into memory.
+    pub async fn get_usage(&self) -> Result<Usage, RepoError> {
+        let lists = self.data_store.collection(&Collections::Lists.to_string());
+        let pipeline = vec![doc! { "$group": {
+            "_id": Bson::Null,
+            "count": { "$sum": { "$size": { "$ifNull": ["$items", []] } } },
+        } }];
+        let items = lists
+            .aggregate(pipeline, None)
+            .await?
+            .next()
+            .await
+            .transpose()?
+            .map(bson::from_document::<ItemCount>)
+            .transpose()?;
+
+        let disabled = doc! { "disabledAt": { "$exists": true } };
+        let trashed = doc! { "deletedAt": { "$exists": true } };
+        let active = doc! { "deletedAt": { "$exists": false } };
+
+        Ok(Usage {
+            users: self.count_documents(doc! {}, &Collections::Users).await?,
+            disabled_users: self.count_documents(disabled, &Collections::Users).await?,
+            lists: self.count_documents(active, &Collections::Lists).await?,
+            trashed_lists: self.count_documents(trashed, &Collections::Lists).await?,
+            items: items.map_or(0, |items| items.count),
+            stores: self.count_documents(doc! {}, &Collections::Stores).await?,
+            templates: self.count_documents(doc! {}, &Collections::Templates).await?,
+            api_keys: self.count_documents(doc! {}, &Collections::ApiKeys).await?,
+        })
+    }
+
+    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Users.to_string());
+        let document = collection
+            .find_one(doc! { "email": email }, None)
+            .await?
+            .map(bson::from_document)
+            .transpose()?;
+
+        Ok(document)
+    }
+
+    pub async fn set_password_reset(
+        &self,
+        id: &ObjectId,
+        reset: &PasswordReset,
+    ) -> Result<Option<User>, RepoError> {
+        let update = doc! { "$set": { "passwordReset": bson::to_bson(reset)? } };
+        self.update_document_by_id(id, update, &Collections::Users).await
+    }
+
+    /// Sets a new password for whoever has an unexpired reset with this token
+    /// hash, ends their other sessions and deletes their API keys, in case
+    /// whoever had the account made some. The reset can only be used once.
+    pub async fn reset_password(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+        now: DateTime,
+    ) -> Result<bool, RepoError> {
+        let collection = self.data_store.collection(&Collections::Users.to_string());
+        let filter = doc! {
+            "passwordReset.tokenHash": token_hash,
+            "passwordReset.expiresAt": { "$gt": now },
+        };
+        let update = doc! {
+            "$set": { "passwordHash": password_hash },
+            "$inc": { "credentialsVersion": 1 },
+            "$unset": { "passwordReset": "" },
+        };
+        let user = collection.find_one_and_update(filter, update, None).await?;
+        let user = match user {
+            Some(user) => bson::from_document::<User>(user)?,
+            None => return Ok(false),
+        };
+        if let Some(user_id) = &user._id {
+            self.delete_api_keys_by_user(user_id).await?;
+        }
+        Ok(true)
+    }
+
+    pub async fn get_user_by_name(&self, name: &str) -> Result<Option<User>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Users.to_string());
+        let document = collection
+            .find_one(doc! { "name": name}, None)
+            .await?
+            .map(bson::from_document)
+            .transpose()?;
+
+        Ok(document)
+    }
+
+    /// Lists in insertion order unless `query` sorts them; `page.after` continues
+    /// from the given list and only applies to insertion order.
+    pub async fn get_lists_by_user(
+        &self,
+        user_id: &ObjectId,
+        query: &ListQuery,
+        page: &Page,
+    ) -> Result<Vec<Result<List, RepoError>>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let households = self.get_household_ids(user_id).await?;
+        let (filter, options) = user_lists_find(user_id, &households, query, page);
+        let documents = collection
+            .find(filter, options)
+            .await?
+            .map(|doc_result| {
+                doc_result
+                    .map_err(RepoError::from)
+                    .and_then(|doc| {
+                        bson::from_document::<List>(doc).map_err(RepoError::from)
+                    })
+            })
+            .collect::<Vec<Result<List, RepoError>>>()
+            .await;
+
+        Ok(documents)
+    }
+
+    /// Like `get_lists_by_user`, but only the fields in `projection` (and `_id`)
+    /// are fetched, as raw documents.
+    pub async fn get_list_fields_by_user(
+        &self,
+        user_id: &ObjectId,
+        query: &ListQuery,
+        page: &Page,
+        projection: Document,
+    ) -> Result<Vec<Document>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let households = self.get_household_ids(user_id).await?;
+        let (filter, mut options) = user_lists_find(user_id, &households, query, page);
+        options.projection = Some(projection);
+        let documents = collection
+            .find(filter, options)
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+
+        documents
+            .into_iter()
+            .map(|doc_result| doc_result.map_err(RepoError::from))
+            .collect()
+    }
+
+    /// Like `get_lists_by_user`, but each list comes back without its items,
+    /// counted instead.
+    pub async fn get_list_summaries_by_user(
+        &self,
+        user_id: &ObjectId,
+        query: &ListQuery,
+        page: &Page,
+    ) -> Result<Vec<ListSummary>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let households = self.get_household_ids(user_id).await?;
+        let (filter, options) = user_lists_find(user_id, &households, query, page);
+        let mut pipeline = vec![
+            doc! { "$match": filter },
+            doc! { "$sort": query.sort.to_document() },
+        ];
+        if let Some(limit) = options.limit {
+            pipeline.push(doc! { "$limit": limit });
+        }
+        pipeline.push(doc! { "$project": {
+            "name": 1,
+            "userId": 1,
+            "itemCount": { "$size": { "$ifNull": ["$items", []] } },
+            "archived": 1,
+            "createdAt": 1,
+            "updatedAt": 1,
+            "sharedWith": 1,
+            "householdId": 1,
+            "version": 1,
+        } });
+        let documents = collection
+            .aggregate(pipeline, None)
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+
+        documents
+            .into_iter()
+            .map(|doc_result| {
+                doc_result
+                    .map_err(RepoError::from)
+                    .and_then(|doc| bson::from_document(doc).map_err(RepoError::from))
+            })
+            .collect()
+    }
+
+    /// How many lists `get_lists_by_user` would return across every page.
+    pub async fn count_lists_by_user(
+        &self,
+        user_id: &ObjectId,
+        archived: Option<bool>,
+    ) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let households = self.get_household_ids(user_id).await?;
+        let filter = user_lists_filter(user_id, &households, archived);
+        Ok(collection.count_documents(filter, None).await?)
+    }
+
+    /// How many items are on the list, counted in the database so the items
+    /// aren't fetched. `None` when there's no such list.
+    pub async fn count_list_items(&self, id: &ObjectId) -> Result<Option<i64>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let pipeline = vec![
+            doc! { "$match": { "_id": id } },
+            doc! { "$project": { "count": { "$size": { "$ifNull": ["$items", []] } } } },
+        ];
+        let count = collection
+            .aggregate(pipeline, None)
+            .await?
+            .next()
+            .await
+            .transpose()?
+            .map(bson::from_document::<ItemCount>)
+            .transpose()?;
+
+        Ok(count.map(|count| count.count))
+    }
+
+    pub async fn get_list_fields(
+        &self,
+        id: &ObjectId,
+        projection: Document,
+    ) -> Result<Option<Document>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let options = FindOneOptions::builder().projection(projection).build();
+        Ok(collection.find_one(doc! { "_id": id }, options).await?)
+    }
+
+    pub async fn delete_lists_by_user(&self, user_id: &ObjectId) -> Result<i64, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let delete_result = collection.delete_many(doc! { "userId": user_id }, None).await?;
+        Ok(delete_result.deleted_count)
+    }
+
+    /// Counts across the user's own lists, not counting the trash. Everything is
+    /// computed by mongo so no list is loaded into memory.
+    pub async fn get_user_stats(&self, user_id: &ObjectId) -> Result<UserStats, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let pipeline = vec![
+            doc! { "$match": { "userId": user_id, "deletedAt": { "$exists": false } } },
+            doc! { "$facet": {
+                "totals": [{ "$group": {
+                    "_id": Bson::Null,
+                    "lists": { "$sum": 1 },
+                    "archivedLists": { "$sum": { "$cond": [{ "$eq": ["$archived", true] }, 1, 0] } },
+                    "items": { "$sum": { "$size": { "$ifNull": ["$items", []] } } },
+                    "checkedItems": { "$sum": { "$size": { "$filter": {
+                        "input": { "$ifNull": ["$items", []] },
+                        "as": "item",
+                        "cond": { "$eq": ["$$item.checked", true] },
+                    } } } },
+                } }],
+                "categories": [
+                    { "$unwind": "$items" },
+                    { "$match": { "items.category": { "$type": "string" } } },
+                    { "$group": { "_id": "$items.category", "count": { "$sum": 1 } } },
+                    { "$sort": { "count": -1, "_id": 1 } },
+                    { "$limit": TOP_CATEGORIES },
+                    { "$project": { "_id": 0, "category": "$_id", "count": 1 } },
+                ],
+            } },
+        ];
+        let facets = collection
+            .aggregate(pipeline, None)
+            .await?
+            .next()
+            .await
+            .transpose()?
+            .map(bson::from_document::<StatsFacets>)
+            .transpose()?;
+
+        Ok(facets.map(UserStats::from).unwrap_or_default())
+    }
+
+    /// The item names the user adds most often across all of their lists,
+    /// including archived and trashed ones. Names are grouped ignoring case.
+    pub async fn get_frequent_items(
+        &self,
+        user_id: &ObjectId,
+        limit: i64,
+    ) -> Result<Vec<FrequentItem>, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let pipeline = vec![
+            doc! { "$match": { "userId": user_id } },
+            doc! { "$unwind": "$items" },
+            doc! { "$group": {
+                "_id": { "$toLower": { "$trim": { "input": "$items.name" } } },
+                "name": { "$first": { "$trim": { "input": "$items.name" } } },
+                "category": { "$max": "$items.category" },
+                "count": { "$sum": 1 },
+            } },
+            doc! { "$sort": { "count": -1, "_id": 1 } },
+            doc! { "$limit": limit },
+            doc! { "$project": { "_id": 0, "name": 1, "category": 1, "count": 1 } },
+        ];
+        let documents = collection
+            .aggregate(pipeline, None)
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+
+        documents
+            .into_iter()
+            .map(|doc_result| Ok(bson::from_document::<FrequentItem>(doc_result?)?))
+            .collect()
+    }
+
+    /// Whether the user already owns an active (not archived or trashed) list
+    /// with this name, ignoring case and surrounding whitespace.
+    pub async fn has_active_list_named(&self, user_id: &ObjectId, name: &str) -> Result<bool, RepoError> {
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let pattern = format!("^\\s*{}\\s*$", escape_regex(name.trim()));
+        let filter = doc! {
+            "userId": user_id,
+            "archived": { "$ne": true },
+            "deletedAt": { "$exists": false },
+            "name": { "$regex": pattern, "$options": "i" },
+        };
+        Ok(collection.count_documents(filter, None).await? > 0)
+    }
+
+    /// Finds the user's lists with at least one item whose name contains `query`,
+    /// ignoring case.
+    pub async fn search_lists_by_item_name(
+        &self,
+        user_id: &ObjectId,
+        query: &str,
+    ) -> Result<Vec<List>, RepoError> {
+        let filter = doc! {
+            "userId": user_id,
+            "deletedAt": { "$exists": false },
+            "items.name": { "$regex": escape_regex(query), "$options": "i" },
+        };
+        self.find_documents(filter, FindOptions::default(), &Collections::Lists).await
+    }
+
+    /// Lists with at least one unchecked item needed by `before` (YYYY-MM-DD), or
+    /// with any need-by date when `before` is `None`.
+    pub async fn get_lists_with_items_due(
+        &self,
+        user_id: &ObjectId,
+        before: Option<&str>,
+    ) -> Result<Vec<List>, RepoError> {
+        let mut due = doc! { "$exists": true };
+        if let Some(before) = before {
+            due.insert("$lte", before);
+        }
+        let filter = doc! {
+            "userId": user_id,
+            "deletedAt": { "$exists": false },
+            "items": { "$elemMatch": { "needBy": due, "checked": { "$ne": true } } },
+        };
+        self.find_documents(filter, FindOptions::default(), &Collections::Lists).await
+    }
+
+    /// With `merge`, an item with the same name is folded into the existing
+    /// one instead of being added again.
+    pub async fn add_list_item(
+        &self,
+        list_id: &ObjectId,
+        item: &ListItem,
+        merge: bool,
+    ) -> Result<Option<List>, RepoError> {
+        let mut list = self.get_list_by_id(list_id)
+                       .await?
+                       .ok_or_else(|| RepoError::ObjectNotFound(list_id.clone(), Collections::Lists))?;
+        let version = list.version;
+        if merge {
+            list.merge_item(item.clone());
+        } else {
+            list.add_item(item.clone());
+        }
+        list.updated_at = Some(DateTime::from(Utc::now()));
+        list.version += 1;
+
+        let collection = self.data_store.collection(&Collections::Lists.to_string());
+        let replace_result = collection
+            .replace_one(list_filter(list_id, Some(version)), bson::to_document(&list)?, None)
+            .await?;
+        if replace_result.matched_count == 0 {
+            return match self.get_list_by_id(list_id).await? {
+                Some(_) => Err(RepoError::ConcurrentModification(list_id.clone(), Collections::Lists)),
+                None => Ok(None),
+            };
+        }
+        self.get_list_by_id(list_id).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::clean_up_db;
+    use crate::DbConfig;
+    use super::super::api_key::hash_key;
+    use super::super::favorite::Favorite;
+    use super::super::invitation::InvitationTarget;
+    use super::super::list::ListItem;
+    use super::*;
+    use mongodb::bson::oid::ObjectId;
+
+    #[test]
+    fn regex_metacharacters_are_escaped() {
+        assert_eq!(escape_regex("1.5 (l) milk"), "1\\.5 \\(l\\) milk");
+    }
+
+    fn db_config() -> DbConfig {
+        DbConfig {
+            database_url: "mongodb://localhost:27017/".to_string(),
+            database_name: "grok_list_test".to_string(),
+        }
+    }
+
+    #[derive(Error, Debug)]
+    enum TestError {
+        #[error("got None when reading our writes from mongo")]
+        NoneFromMongo,
+    }
+
+    #[tokio::test]
+    async fn can_insert_and_retrieve_lists_by_id() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let list_item = ListItem::builder("salmon")
+            .category("meat")
+            .amount("2lb")
+            .build();
+        let list = List::builder("test_list".to_string(), ObjectId::new())
+            .add_item(list_item)
+            .build();
+
+        let inserted_list = repo
+            .add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let retrieved = repo
+            .get_list_by_id(&inserted_list._id.clone().expect("Inserted list had no _id"))
+            .await
+            .expect("Error finding list")
+            .unwrap_or_else(|| {
+                panic!(
+                    "List with id: {:?} did not exist",
+                    inserted_list._id.clone()
+                )
+            });
+
+        assert_eq!(retrieved.user_id, list.user_id);
+        assert_eq!(retrieved.items, list.items);
+
+        let items_deleted = repo
+            .delete_list_by_id(&inserted_list._id.expect("Inserted list had no id"))
+            .await?;
+        assert_eq!(1, items_deleted);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_insert_and_retrieve_categories_by_id() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let mut store = Store::new("test_store");
+        store.add_category("MEAT");
+        store.add_category("Produce");
+
+        let inserted_store = repo
+            .add_store(&store)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let retrieved = repo
+            .get_store_by_id(
+                &inserted_store
+                    ._id
+                    .clone()
+                    .expect("Inserted store had no _id"),
+            )
+            .await
+            .expect("Error finding store")
+            .unwrap_or_else(|| {
+                panic!(
+                    "Store with id: {:?} did not exist",
+                    inserted_store._id.clone()
+                )
+            });
+
+        assert_eq!(retrieved.name, store.name);
+        assert_eq!(retrieved.categories, store.categories);
+
+        let stores_deleted = repo
+            .delete_store_by_id(&inserted_store._id.expect("Inserted store had no _id"))
+            .await?;
+        assert_eq!(1, stores_deleted);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_insert_user() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user = User::new("test_user".to_string(), "test@email.com".to_string());
+
+        let inserted_user = repo
+            .add_user(&user)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let retrieved = repo
+            .get_user_by_name(&user.name)
+            .await
+            .expect("Error finding store")
+            .unwrap_or_else(|| panic!("User with name: {:?} did not exist", user.name));
+
+        assert_eq!(retrieved.name, user.name);
+        assert_eq!(retrieved.email, user.email);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_update_user() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user = User::new("test_user_update".to_string(), "test@email.com".to_string());
+
+        let inserted_user = repo
+            .add_user(&user)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let updated_user = repo
+            .update_user(
+                &inserted_user._id.clone().expect("Inserted user had no _id"),
+                &User::new("updated_user".to_string(), "updated@email.com".to_string()),
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        assert_eq!(updated_user._id, inserted_user._id);
+        assert_eq!(updated_user.name, "updated_user");
+        assert_eq!(updated_user.email, "updated@email.com");
+
+        let missing = repo.update_user(&ObjectId::new(), &user).await?;
+        assert_eq!(missing, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn items_saved_without_ids_get_one_that_sticks() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let list_id = ObjectId::new();
+        let lists = repo.data_store.collection(&Collections::Lists.to_string());
+        lists
+            .insert_one(
+                doc! {
+                    "_id": list_id.clone(),
+                    "name": "old list",
+                    "userId": ObjectId::new(),
+                    "items": [{ "name": "milk", "category": null, "amount": null }],
+                },
+                None,
+            )
+            .await?;
+
+        assert!(repo.backfill_item_ids().await? >= 1);
+        let first = repo.get_list_by_id(&list_id).await?.ok_or(TestError::NoneFromMongo)?;
+        let second = repo.get_list_by_id(&list_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(first.items, second.items);
+        assert_eq!(repo.backfill_item_ids().await?, 0);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_fetch_lists_by_user() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user = User::new("test_user".to_string(), "test@email.com".to_string());
+
+        let inserted_user = repo
+            .add_user(&user)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        let list_item = ListItem::builder("salmon")
+            .category("meat")
+            .amount("2lb")
+            .build();
+        let list = List::builder(
+            "test_list".to_string(),
+            inserted_user._id.clone().expect("Inserted user had no _id"),
+        )
+        .add_item(list_item)
+        .build();
+
+        let inserted_list = repo
+            .add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        let lists: Vec<List> = repo
+            .get_lists_by_user(
+                &inserted_user._id.expect("Inserted user had no _id"),
+                &ListQuery::default(),
+                &Page::default(),
+            )
+            .await?
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(lists, vec![inserted_list]);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_delete_lists_by_user() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let other_user_id = ObjectId::new();
+
+        repo.add_list(&List::builder("first".to_string(), user_id.clone()).build()).await?;
+        repo.add_list(&List::builder("second".to_string(), user_id.clone()).build()).await?;
+        repo.add_list(&List::builder("other".to_string(), other_user_id.clone()).build()).await?;
+
+        let lists_deleted = repo.delete_lists_by_user(&user_id).await?;
+        assert_eq!(2, lists_deleted);
+        let remaining = repo
+            .get_lists_by_user(&user_id, &ListQuery::default(), &Page::default())
+            .await?;
+        assert_eq!(0, remaining.len());
+        let remaining = repo
+            .get_lists_by_user(&other_user_id, &ListQuery::default(), &Page::default())
+            .await?;
+        assert_eq!(1, remaining.len());
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_archive_and_delete_lists_in_bulk() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let first = repo
+            .add_list(&List::builder("first".to_string(), user_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let second = repo
+            .add_list(&List::builder("second".to_string(), user_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let ids = vec![
+            first._id.clone().expect("Inserted list had no _id"),
+            second._id.clone().expect("Inserted list had no _id"),
+        ];
+
+        let members =
+            repo.get_list_members_by_ids(&[ids[0].clone(), ObjectId::new()], false).await?;
+        assert_eq!(
+            members,
+            vec![ListMembers {
+                _id: ids[0].clone(),
+                user_id: user_id.clone(),
+                shared_with: vec![],
+                household_members: vec![],
+            }]
+        );
+
+        assert_eq!(2, repo.set_lists_archived(&ids, true).await?);
+        let archived = repo.get_list_by_id(&ids[0]).await?.ok_or(TestError::NoneFromMongo)?;
+        assert!(archived.archived);
+
+        assert_eq!(2, repo.trash_lists_by_ids(&ids).await?);
+        let remaining = repo
+            .get_lists_by_user(&user_id, &ListQuery::default(), &Page::default())
+            .await?;
+        assert_eq!(0, remaining.len());
+        assert!(repo.get_list_members_by_ids(&ids, false).await?.is_empty());
+        assert_eq!(repo.get_list_members_by_ids(&ids, true).await?.len(), 2);
+
+        assert_eq!(2, repo.restore_lists_by_ids(&ids).await?);
+        let restored = repo.get_list_by_id(&ids[0]).await?.ok_or(TestError::NoneFromMongo)?;
+        assert!(!restored.archived);
+        assert_eq!(restored.deleted_at, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_partially_update_list() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let list_item = ListItem::builder("salmon").build();
+        let list = List::builder("test_list_update".to_string(), ObjectId::new())
+            .add_item(list_item.clone())
+            .build();
+        let inserted_list = repo.add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = inserted_list._id.clone().expect("Inserted list had no _id");
+
+        let renamed = repo
+            .update_list(
+                &list_id,
+                &ListUpdate { name: Some("renamed".to_string()), user_id: None },
+                None,
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(renamed.name, "renamed");
+        assert_eq!(renamed.user_id, list.user_id);
+        assert_eq!(renamed.items, vec![list_item]);
+
+        let new_owner = ObjectId::new();
+        let reassigned = repo
+            .update_list(
+                &list_id,
+                &ListUpdate { name: None, user_id: Some(new_owner.clone()) },
+                None,
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(reassigned.name, "renamed");
+        assert_eq!(reassigned.user_id, new_owner);
+
+        let missing = repo.update_list(&ObjectId::new(), &ListUpdate::default(), None).await?;
+        assert_eq!(missing, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_add_items_to_existing_list() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+
+        let list_item = ListItem::builder("salmon")
+            .category("meat")
+            .amount("2lb")
+            .build();
+        let list = List::builder(
+            "test_list_add_items".to_string(),
+            ObjectId::new()
+        )
+        .add_item(list_item.clone())
+        .build();
+        let inserted_list = repo.add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        let new_list_item = ListItem::builder("brocc")
+            .category("veg")
+            .amount("1")
+            .build();
+
+        let list_id = inserted_list._id.expect("Inserted list had no _id");
+        let updated_list = repo
+            .add_list_item(&list_id, &new_list_item, false)
+            .await.unwrap()
+            .ok_or(TestError::NoneFromMongo)?;
+
+        assert_eq!(list.name, updated_list.name);
+        assert_eq!(vec![list_item, new_list_item], updated_list.items);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_remove_item_from_list() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+
+        let salmon = ListItem::builder("salmon").build();
+        let brocc = ListItem::builder("brocc").build();
+        let list = List::builder("test_list_remove_item".to_string(), ObjectId::new())
+            .add_item(salmon.clone())
+            .add_item(brocc.clone())
+            .build();
+        let inserted_list = repo.add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = inserted_list._id.expect("Inserted list had no _id");
+
+        let updated_list = repo
+            .remove_list_item(&list_id, salmon.id())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(vec![brocc], updated_list.items);
+
+        let missing_item = repo.remove_list_item(&list_id, salmon.id()).await;
+        assert!(matches!(missing_item, Err(RepoError::ObjectNotFound(..))));
+
+        let missing_list = repo.remove_list_item(&ObjectId::new(), salmon.id()).await?;
+        assert_eq!(missing_list, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_update_item_in_place() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+
+        let salmon = ListItem::builder("salmn").category("meat").amount("1lb").build();
+        let brocc = ListItem::builder("brocc").build();
+        let list = List::builder("test_list_update_item".to_string(), ObjectId::new())
+            .add_item(salmon.clone())
+            .add_item(brocc.clone())
+            .build();
+        let inserted_list = repo.add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = inserted_list._id.expect("Inserted list had no _id");
+
+        let updated_list = repo
+            .update_list_item(
+                &list_id,
+                salmon.id(),
+                &ListItemUpdate {
+                    name: Some("salmon".to_string()),
+                    category: None,
+                    amount: Some("2lb".to_string()),
+                    checked: None,
+                    need_by: None,
+                },
+                None,
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let updated_item = &updated_list.items[0];
+        assert_eq!(updated_item.id(), salmon.id());
+        assert_eq!(updated_item.name(), "salmon");
+        assert_eq!(updated_item.category(), Some("meat"));
+        assert_eq!(updated_item.amount(), Some("2lb"));
+        assert_eq!(updated_list.items[1], brocc);
+
+        let missing_item = repo
+            .update_list_item(&list_id, &ObjectId::new(), &ListItemUpdate::default(), None)
+            .await;
+        assert!(matches!(missing_item, Err(RepoError::ObjectNotFound(..))));
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_fetch_all_stores_sorted_by_name() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let costco = repo.add_store(&Store::new("costco")).await?.ok_or(TestError::NoneFromMongo)?;
+        let aldi = repo
+            .add_store(&Store::new("aldi").owned_by(user_id.clone()))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let other = repo
+            .add_store(&Store::new("bodega").owned_by(ObjectId::new()))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        let stores: Vec<Store> = repo
+            .get_all_stores(&user_id, &Page::default(), &SortSpec::default())
+            .await?
+            .into_iter()
+            .filter(|store| [&aldi._id, &costco._id, &other._id].contains(&&store._id))
+            .collect();
+
+        assert_eq!(stores, vec![aldi, costco]);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_add_several_items_at_once() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+
+        let salmon = ListItem::builder("salmon").build();
+        let list = List::builder("test_list_bulk_items".to_string(), ObjectId::new())
+            .add_item(salmon.clone())
+            .build();
+        let inserted_list = repo.add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let new_items = vec![
+            ListItem::builder("brocc").build(),
+            ListItem::builder("la croix").build(),
+        ];
+
+        let updated_list = repo
+            .add_list_items(&inserted_list._id.expect("Inserted list had no _id"), &new_items)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        assert_eq!(updated_list.items, vec![salmon, new_items[0].clone(), new_items[1].clone()]);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_clear_checked_items() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+
+        let salmon = ListItem::builder("salmon").checked(true).build();
+        let brocc = ListItem::builder("brocc").build();
+        let list = List::builder("test_list_clear_checked".to_string(), ObjectId::new())
+            .add_item(salmon)
+            .add_item(brocc.clone())
+            .build();
+        let inserted_list = repo.add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        let updated_list = repo
+            .clear_checked_items(&inserted_list._id.expect("Inserted list had no _id"))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        assert_eq!(updated_list.items, vec![brocc]);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_check_and_uncheck_items() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+
+        let salmon = ListItem::builder("salmon").build();
+        let list = List::builder("test_list_check_item".to_string(), ObjectId::new())
+            .add_item(salmon.clone())
+            .build();
+        let inserted_list = repo.add_list(&list)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = inserted_list._id.expect("Inserted list had no _id");
+
+        let checked = repo
+            .set_list_item_checked(&list_id, salmon.id(), true)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert!(checked.items[0].is_checked());
+
+        let checked_again = repo
+            .set_list_item_checked(&list_id, salmon.id(), true)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert!(checked_again.items[0].is_checked());
+
+        let unchecked = repo
+            .set_list_item_checked(&list_id, salmon.id(), false)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert!(!unchecked.items[0].is_checked());
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_search_lists_by_item_name() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+
+        let with_milk = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("Oat Milk").build())
+                    .build(),
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        repo.add_list(
+            &List::builder("hardware".to_string(), user_id.clone())
+                .add_item(ListItem::builder("nails").build())
+                .build(),
+        )
+        .await?;
+        repo.add_list(
+            &List::builder("someone else's".to_string(), ObjectId::new())
+                .add_item(ListItem::builder("milk").build())
+                .build(),
+        )
+        .await?;
+
+        let lists = repo.search_lists_by_item_name(&user_id, "milk").await?;
+
+        assert_eq!(lists, vec![with_milk]);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_upsert_stores_by_name() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let mut store = Store::new("test_store_upsert");
+        store.add_category("meat");
+
+        let created = repo
+            .upsert_store_by_name(&user_id, &store)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(created.categories, vec!["meat"]);
+        assert_eq!(created.user_id, Some(user_id.clone()));
+
+        store.add_category("produce");
+        let updated = repo
+            .upsert_store_by_name(&user_id, &store)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(updated._id, created._id);
+        assert_eq!(updated.categories, vec!["meat", "produce"]);
+
+        let someone_elses = repo
+            .upsert_store_by_name(&ObjectId::new(), &store)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_ne!(someone_elses._id, created._id);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_filter_and_sort_lists_by_user() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let mut names = Vec::new();
+        for name in ["b", "c", "a"].iter() {
+            let list = repo
+                .add_list(&List::builder(name.to_string(), user_id.clone()).build())
+                .await?
+                .ok_or(TestError::NoneFromMongo)?;
+            names.push(list);
+        }
+        repo.set_lists_archived(&[names[1]._id.clone().expect("Inserted list had no _id")], true)
+            .await?;
+
+        let query = ListQuery {
+            archived: Some(false),
+            sort: SortSpec::parse(Some("name:desc"), false).unwrap(),
+        };
+        let lists: Vec<String> = repo
+            .get_lists_by_user(&user_id, &query, &Page::default())
+            .await?
+            .into_iter()
+            .map(|list| list.unwrap().name)
+            .collect();
+
+        assert_eq!(lists, vec!["b", "a"]);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn list_updates_bump_updated_at() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let inserted_list = repo
+            .add_list(&List::builder("test_list_touch".to_string(), ObjectId::new()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert!(inserted_list.created_at.is_some());
+        assert_eq!(inserted_list.created_at, inserted_list.updated_at);
+
+        let updated_list = repo
+            .add_list_items(
+                &inserted_list._id.clone().expect("Inserted list had no _id"),
+                &[ListItem::builder("salmon").build()],
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        assert_eq!(updated_list.created_at, inserted_list.created_at);
+        let inserted_at = inserted_list.updated_at.ok_or(TestError::NoneFromMongo)?;
+        let updated_at = updated_list.updated_at.ok_or(TestError::NoneFromMongo)?;
+        assert!(updated_at.0 >= inserted_at.0);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn can_fetch_user_by_email() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user = User::new("test_user_email".to_string(), "lookup@email.com".to_string());
+        let inserted_user = repo
+            .add_user(&user)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+
+        let retrieved = repo.get_user_by_email("lookup@email.com").await?;
+        assert_eq!(retrieved, Some(inserted_user));
+
+        let missing = repo.get_user_by_email("nobody@email.com").await?;
+        assert_eq!(missing, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn lists_shared_with_a_user_are_returned_for_them() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let owner_id = ObjectId::new();
+        let collaborator_id = ObjectId::new();
+        let list = repo
+            .add_list(&List::builder("test_list_shared".to_string(), owner_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = list._id.clone().expect("Inserted list had no _id");
+
+        let shared = repo
+            .add_list_collaborator(&list_id, &collaborator_id)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(shared.shared_with, vec![collaborator_id.clone()]);
+
+        let lists = repo
+            .get_lists_by_user(&collaborator_id, &ListQuery::default(), &Page::default())
             .await?;
-        assert_eq!(1, items_deleted);
+        assert_eq!(lists.len(), 1);
+        let members = repo.get_list_members(&list_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert!(members.includes(&owner_id));
+        assert!(members.includes(&collaborator_id));
+
+        let unshared = repo
+            .remove_list_collaborator(&list_id, &collaborator_id)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert!(unshared.shared_with.is_empty());
+        let lists = repo
+            .get_lists_by_user(&collaborator_id, &ListQuery::default(), &Page::default())
+            .await?;
+        assert!(lists.is_empty());
 
         clean_up_db(&db_config()).await
     }
 
     #[tokio::test]
-    async fn can_insert_and_retrieve_categories_by_id() -> Result<()> {
+    async fn store_categories_are_added_once() -> Result<()> {
         let repo = Repo::new(&db_config())
             .await
             .expect("Couldn't connect to mongo, is it running?");
-        let mut store = Store::new("test_store");
-        store.add_category("MEAT");
-        store.add_category("Produce");
+        let layout = Store::with_categories("test_store_add_category", &["produce".to_string()])?;
+        let store = repo
+            .add_store(&layout)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let store_id = store._id.clone().expect("Inserted store had no _id");
 
-        let inserted_store = repo
-            .add_store(&store)
+        repo.add_store_category(&store_id, "Dairy").await?;
+        let store = repo
+            .add_store_category(&store_id, "dairy")
             .await?
             .ok_or(TestError::NoneFromMongo)?;
-        let retrieved = repo
-            .get_store_by_id(
-                &inserted_store
-                    ._id
-                    .clone()
-                    .expect("Inserted store had no _id"),
-            )
+
+        assert_eq!(store.categories, vec!["produce".to_string(), "dairy".to_string()]);
+        assert_eq!(repo.add_store_category(&ObjectId::new(), "dairy").await?, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn stale_list_versions_are_rejected() -> Result<()> {
+        let repo = Repo::new(&db_config())
             .await
-            .expect("Error finding store")
-            .unwrap_or_else(|| {
-                panic!(
-                    "Store with id: {:?} did not exist",
-                    inserted_store._id.clone()
-                )
-            });
+            .expect("Couldn't connect to mongo, is it running?");
+        let list = repo
+            .add_list(&List::builder("test_list_version".to_string(), ObjectId::new()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = list._id.clone().expect("Inserted list had no _id");
+        assert_eq!(list.version, 0);
+        let rename = ListUpdate { name: Some("renamed".to_string()), user_id: None };
 
-        assert_eq!(retrieved.name, store.name);
-        assert_eq!(retrieved.categories, store.categories);
+        let renamed = repo
+            .update_list(&list_id, &rename, Some(0))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(renamed.name, "renamed");
+        assert_eq!(renamed.version, 1);
 
-        let stores_deleted = repo
-            .delete_store_by_id(&inserted_store._id.expect("Inserted store had no _id"))
-            .await?;
-        assert_eq!(1, stores_deleted);
+        let stale = repo.update_list(&list_id, &rename, Some(0)).await;
+        assert!(matches!(stale, Err(RepoError::ConcurrentModification(..))));
+
+        let missing = repo.update_list(&ObjectId::new(), &rename, Some(0)).await?;
+        assert_eq!(missing, None);
 
         clean_up_db(&db_config()).await
     }
 
     #[tokio::test]
-    async fn can_insert_user() -> Result<()> {
+    async fn only_active_lists_count_as_duplicates() -> Result<()> {
         let repo = Repo::new(&db_config())
             .await
             .expect("Couldn't connect to mongo, is it running?");
-        let user = User::new("test_user".to_string(), "test@email.com".to_string());
+        let user_id = ObjectId::new();
+        let list = repo
+            .add_list(&List::builder("Groceries".to_string(), user_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = list._id.clone().expect("Inserted list had no _id");
 
-        let inserted_user = repo
-            .add_user(&user)
+        assert!(repo.has_active_list_named(&user_id, " groceries ").await?);
+        assert!(!repo.has_active_list_named(&user_id, "Groceries 2").await?);
+        assert!(!repo.has_active_list_named(&ObjectId::new(), "Groceries").await?);
+
+        repo.set_list_archived(&list_id, true).await?;
+        assert!(!repo.has_active_list_named(&user_id, "Groceries").await?);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn user_stats_are_aggregated() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let mut groceries = List::builder("groceries".to_string(), user_id.clone());
+        groceries
+            .add_item(ListItem::builder("milk").category("dairy").checked(true).build())
+            .add_item(ListItem::builder("eggs").category("dairy").build())
+            .add_item(ListItem::builder("foil").build());
+        repo.add_list(&groceries.build()).await?;
+        let mut hardware = List::builder("hardware".to_string(), user_id.clone());
+        hardware.add_item(ListItem::builder("nails").category("tools").build());
+        let hardware = repo.add_list(&hardware.build()).await?.ok_or(TestError::NoneFromMongo)?;
+        repo.set_list_archived(hardware._id.as_ref().unwrap(), true).await?;
+        let trashed = repo
+            .add_list(&List::builder("trashed".to_string(), user_id.clone()).build())
             .await?
             .ok_or(TestError::NoneFromMongo)?;
-        let retrieved = repo
-            .get_user_by_name(&user.name)
+        repo.trash_lists_by_ids(&[trashed._id.unwrap()]).await?;
+
+        let stats = repo.get_user_stats(&user_id).await?;
+        assert_eq!(
+            stats,
+            UserStats {
+                lists: 2,
+                archived_lists: 1,
+                items: 4,
+                checked_items: 1,
+                average_items_per_list: 2.0,
+                top_categories: vec![
+                    CategoryCount { category: "dairy".to_string(), count: 2 },
+                    CategoryCount { category: "tools".to_string(), count: 1 },
+                ],
+            }
+        );
+
+        assert_eq!(repo.get_user_stats(&ObjectId::new()).await?, UserStats::default());
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn frequent_items_are_counted_across_lists() -> Result<()> {
+        let repo = Repo::new(&db_config())
             .await
-            .expect("Error finding store")
-            .unwrap_or_else(|| panic!("User with name: {:?} did not exist", user.name));
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        for names in [vec!["Milk", "eggs"], vec!["milk ", "bread"], vec!["milk", "eggs"]].iter() {
+            let mut list = List::builder("groceries".to_string(), user_id.clone());
+            for name in names {
+                list.add_item(ListItem::builder(name).category("dairy").build());
+            }
+            repo.add_list(&list.build()).await?;
+        }
 
-        assert_eq!(retrieved.name, user.name);
-        assert_eq!(retrieved.email, user.email);
+        let frequent = repo.get_frequent_items(&user_id, 2).await?;
+        let summary: Vec<_> = frequent
+            .iter()
+            .map(|item| (item.name.to_lowercase(), item.count))
+            .collect();
+        assert_eq!(summary, vec![("milk".to_string(), 3), ("eggs".to_string(), 2)]);
+        assert_eq!(frequent[0].category, Some("dairy".to_string()));
+
+        assert_eq!(repo.get_frequent_items(&ObjectId::new(), 2).await?, vec![]);
 
         clean_up_db(&db_config()).await
     }
 
     #[tokio::test]
-    async fn can_fetch_lists_by_user() -> Result<()> {
+    async fn favorites_can_only_be_deleted_by_their_owner() -> Result<()> {
         let repo = Repo::new(&db_config())
             .await
             .expect("Couldn't connect to mongo, is it running?");
-        let user = User::new("test_user".to_string(), "test@email.com".to_string());
+        let user_id = ObjectId::new();
+        let favorite = repo
+            .add_favorite(&Favorite::new(user_id.clone(), "milk", None, None))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let favorite_id = favorite._id.clone().expect("Inserted favorite had no _id");
 
-        let inserted_user = repo
-            .add_user(&user)
+        assert_eq!(repo.delete_favorite(&ObjectId::new(), &favorite_id).await?, 0);
+        assert_eq!(repo.get_favorites_by_user(&user_id).await?, vec![favorite]);
+        assert_eq!(repo.delete_favorite(&user_id, &favorite_id).await?, 1);
+        assert_eq!(repo.get_favorites_by_user(&user_id).await?, vec![]);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn list_summaries_count_items() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let groceries = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("milk").build())
+                    .add_item(ListItem::builder("eggs").build())
+                    .build(),
+            )
             .await?
             .ok_or(TestError::NoneFromMongo)?;
+        repo.add_list(&List::builder("hardware".to_string(), user_id.clone()).build()).await?;
 
-        let list_item = ListItem::builder("salmon")
-            .category("meat")
-            .amount("2lb")
-            .build();
-        let list = List::builder(
-            "test_list".to_string(),
-            inserted_user._id.clone().expect("Inserted user had no _id"),
-        )
-        .add_item(list_item)
-        .build();
+        let summaries = repo
+            .get_list_summaries_by_user(&user_id, &ListQuery::default(), &Page::default())
+            .await?;
+        let counts: Vec<_> = summaries
+            .iter()
+            .map(|summary| (summary.name.as_str(), summary.item_count))
+            .collect();
+        assert_eq!(counts, vec![("groceries", 2), ("hardware", 0)]);
+        assert_eq!(Some(summaries[0]._id.clone()), groceries._id);
 
-        let inserted_list = repo
-            .add_list(&list)
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn lists_and_items_are_counted() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let groceries = repo
+            .add_list(
+                &List::builder("groceries".to_string(), user_id.clone())
+                    .add_item(ListItem::builder("milk").build())
+                    .add_item(ListItem::builder("eggs").build())
+                    .build(),
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let hardware = repo
+            .add_list(&List::builder("hardware".to_string(), user_id.clone()).build())
             .await?
             .ok_or(TestError::NoneFromMongo)?;
+        repo.set_list_archived(&hardware._id.unwrap(), true).await?;
 
-        let lists: Vec<List> = repo
-            .get_lists_by_user(&inserted_user._id.expect("Inserted user had no _id"))
+        assert_eq!(repo.count_lists_by_user(&user_id, None).await?, 2);
+        assert_eq!(repo.count_lists_by_user(&user_id, Some(false)).await?, 1);
+        assert_eq!(repo.count_list_items(&groceries._id.unwrap()).await?, Some(2));
+        assert_eq!(repo.count_list_items(&ObjectId::new()).await?, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn api_keys_are_found_by_hash_and_revoked_by_their_owner() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let (key, api_key) =
+            ApiKey::generate(user_id.clone(), "scripts", DateTime::from(Utc::now()));
+        let api_key = repo.add_api_key(&api_key).await?.ok_or(TestError::NoneFromMongo)?;
+        let key_id = api_key._id.clone().expect("Inserted API key had no _id");
+
+        assert_eq!(repo.get_api_key_by_hash(&hash_key(&key)).await?, Some(api_key));
+        assert_eq!(repo.delete_api_key(&ObjectId::new(), &key_id).await?, 0);
+        assert_eq!(repo.delete_api_key(&user_id, &key_id).await?, 1);
+        assert_eq!(repo.get_api_key_by_hash(&hash_key(&key)).await?, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn password_resets_work_once_and_only_before_they_expire() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user = repo
+            .add_user(&User::with_password(
+                "reset_user".to_string(),
+                "reset@email.com".to_string(),
+                "old hash".to_string(),
+            ))
             .await?
-            .into_iter()
-            .map(Result::unwrap)
-            .collect();
+            .ok_or(TestError::NoneFromMongo)?;
+        let user_id = user._id.clone().expect("Inserted user had no _id");
+        let now = Utc::now();
+        let reset = PasswordReset {
+            token_hash: "token hash".to_string(),
+            expires_at: DateTime::from(now + chrono::Duration::minutes(5)),
+        };
+        repo.set_password_reset(&user_id, &reset).await?;
+        let (key, api_key) =
+            ApiKey::generate(user_id.clone(), "scripts", DateTime::from(Utc::now()));
+        repo.add_api_key(&api_key).await?;
 
-        assert_eq!(lists, vec![inserted_list]);
+        let later = DateTime::from(now + chrono::Duration::minutes(10));
+        assert!(!repo.reset_password("token hash", "new hash", later).await?);
+        assert!(repo.reset_password("token hash", "new hash", DateTime::from(now)).await?);
+        assert!(!repo.reset_password("token hash", "newer hash", DateTime::from(now)).await?);
+
+        let user = repo.get_user_by_id(&user_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(user.password_hash.as_deref(), Some("new hash"));
+        assert_eq!(user.credentials_version, 1);
+        assert_eq!(user.password_reset, None);
+        assert_eq!(repo.get_api_key_by_hash(&hash_key(&key)).await?, None);
 
         clean_up_db(&db_config()).await
     }
 
     #[tokio::test]
-    async fn can_add_items_to_existing_list() -> Result<()> {
+    async fn users_can_be_searched_and_disabled() -> Result<()> {
         let repo = Repo::new(&db_config())
             .await
             .expect("Couldn't connect to mongo, is it running?");
+        let user = repo
+            .add_user(&User::new("Search Me".to_string(), "found@email.com".to_string()))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        repo.add_user(&User::new("other_user".to_string(), "other@email.com".to_string()))
+            .await?;
+        let user_id = user._id.clone().expect("Inserted user had no _id");
 
-        let list_item = ListItem::builder("salmon")
-            .category("meat")
-            .amount("2lb")
-            .build();
-        let list = List::builder(
-            "test_list_add_items".to_string(),
-            ObjectId::new()
+        let found = repo.search_users("search", &Page::default()).await?;
+        assert_eq!(found, vec![user.clone()]);
+        let found = repo.search_users("FOUND@", &Page::default()).await?;
+        assert_eq!(found, vec![user]);
+        assert!(repo.search_users("found.email", &Page::default()).await?.is_empty());
+
+        let disabled_at = DateTime::from(Utc::now());
+        let disabled = repo
+            .set_user_disabled(&user_id, Some(disabled_at))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert!(disabled.is_disabled());
+
+        let enabled = repo
+            .set_user_disabled(&user_id, None)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert!(!enabled.is_disabled());
+        assert_eq!(repo.set_user_disabled(&ObjectId::new(), None).await?, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn usage_is_counted_across_accounts() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let before = repo.get_usage().await?;
+        repo.add_user(&User::new("usage_user".to_string(), "usage@email.com".to_string()))
+            .await?;
+        let trashed = repo
+            .add_list(
+                &List::builder("old".to_string(), ObjectId::new())
+                    .add_item(ListItem::builder("milk").build())
+                    .build(),
+            )
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        repo.trash_lists_by_ids(&[trashed._id.unwrap()]).await?;
+        repo.add_list(
+            &List::builder("groceries".to_string(), ObjectId::new())
+                .add_item(ListItem::builder("eggs").build())
+                .add_item(ListItem::builder("bread").build())
+                .build(),
         )
-        .add_item(list_item.clone())
-        .build();
-        let inserted_list = repo.add_list(&list)
+        .await?;
+        repo.add_store(&Store::new("corner store")).await?;
+
+        let after = repo.get_usage().await?;
+        assert_eq!(after.users - before.users, 1);
+        assert_eq!(after.lists - before.lists, 1);
+        assert_eq!(after.trashed_lists - before.trashed_lists, 1);
+        assert_eq!(after.items - before.items, 3);
+        assert_eq!(after.stores - before.stores, 1);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn household_members_share_lists_and_stores() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let owner = ObjectId::new();
+        let housemate = ObjectId::new();
+        let household = repo
+            .add_household(&Household::new("home", owner.clone()))
             .await?
             .ok_or(TestError::NoneFromMongo)?;
+        let household_id = household._id.clone().expect("Inserted household had no _id");
+        repo.add_household_member(&household_id, &housemate).await?;
+        let list = repo
+            .add_list(&List::builder("groceries".to_string(), owner.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let list_id = list._id.clone().expect("Inserted list had no _id");
+        let store = repo
+            .add_store(&Store::new("household store").owned_by(owner.clone()))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let store_id = store._id.clone().expect("Inserted store had no _id");
 
-        let new_list_item = ListItem::builder("brocc")
-            .category("veg")
-            .amount("1")
-            .build();
+        let members = repo.get_list_members(&list_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert!(!members.includes(&housemate));
 
-        let updated_list = repo
-            .add_list_item(&inserted_list._id.expect("Inserted list had no _id"), &new_list_item)
-            .await.unwrap()
+        repo.set_list_household(&list_id, Some(&household_id)).await?;
+        repo.set_store_household(&store_id, Some(&household_id)).await?;
+        let members = repo.get_list_members(&list_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert!(members.includes(&housemate));
+        let lists = repo
+            .get_lists_by_user(&housemate, &ListQuery::default(), &Page::default())
+            .await?;
+        assert_eq!(lists.len(), 1);
+        assert_eq!(repo.count_lists_by_user(&housemate, None).await?, 1);
+        let stores = repo.get_all_stores(&housemate, &Page::default(), &SortSpec::default()).await?;
+        assert!(stores.iter().any(|store| store._id.as_ref() == Some(&store_id)));
+
+        assert_eq!(repo.leave_households(&housemate).await?, 1);
+        assert_eq!(repo.count_lists_by_user(&housemate, None).await?, 0);
+        let left = repo
+            .remove_household_member(&household_id, &owner)
+            .await?
             .ok_or(TestError::NoneFromMongo)?;
+        assert!(left.members.is_empty());
+        assert_eq!(repo.get_household_by_id(&household_id).await?, None);
+        let list = repo.get_list_by_id(&list_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(list.household_id, None);
+        let store = repo.get_store_by_id(&store_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(store.household_id, None);
 
-        assert_eq!(list.name, updated_list.name);
-        assert_eq!(vec![list_item, new_list_item], updated_list.items);
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn anonymized_accounts_keep_their_shared_lists() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user = User::with_password(
+            "leaving".to_string(),
+            "leaving@email.com".to_string(),
+            "hash".to_string(),
+        );
+        let user = repo.add_user(&user).await?.ok_or(TestError::NoneFromMongo)?;
+        let user_id = user._id.clone().expect("Inserted user had no _id");
+        let friend = ObjectId::new();
+        let shared = repo
+            .add_list(&List::builder("shared".to_string(), user_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let shared_id = shared._id.clone().expect("Inserted list had no _id");
+        repo.add_list_collaborator(&shared_id, &friend).await?;
+        let private = repo
+            .add_list(&List::builder("private".to_string(), user_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let friends = repo
+            .add_list(&List::builder("friend's".to_string(), friend.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let friends_id = friends._id.clone().expect("Inserted list had no _id");
+        repo.add_list_collaborator(&friends_id, &user_id).await?;
+        repo.add_favorite(&Favorite::new(user_id.clone(), "milk", None, None)).await?;
+
+        let deleted = repo
+            .delete_account(&user_id, DeletionMode::Anonymize, DateTime::from(Utc::now()))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(deleted.lists, 1);
+        assert_eq!(deleted.kept_lists, 1);
+        assert_eq!(deleted.favorites, 1);
+
+        let user = repo.get_user_by_id(&user_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(user.name, "");
+        assert_eq!(user.email, anonymized_email(&user_id));
+        assert_eq!(user.password_hash, None);
+        assert_eq!(user.credentials_version, 1);
+        assert!(user.is_disabled());
+        let members = repo.get_list_members(&shared_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert!(members.includes(&friend));
+        assert_eq!(repo.get_list_by_id(private._id.as_ref().unwrap()).await?, None);
+        let members = repo.get_list_members(&friends_id).await?.ok_or(TestError::NoneFromMongo)?;
+        assert!(!members.includes(&user_id));
+
+        let deleted = repo
+            .delete_account(&user_id, DeletionMode::Delete, DateTime::from(Utc::now()))
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        assert_eq!(deleted.lists, 1);
+        assert_eq!(repo.get_user_by_id(&user_id).await?, None);
+        assert_eq!(repo.get_list_by_id(&shared_id).await?, None);
+        let missing = repo
+            .delete_account(&user_id, DeletionMode::Delete, DateTime::from(Utc::now()))
+            .await?;
+        assert_eq!(missing, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn invitations_can_be_accepted_once() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let now = Utc::now();
+        let (token, invitation) = Invitation::generate(
+            "invited@email.com",
+            InvitationTarget::List(ObjectId::new()),
+            ObjectId::new(),
+            DateTime::from(now),
+            DateTime::from(now + chrono::Duration::days(1)),
+        );
+        let invitation = repo
+            .add_invitation(&invitation)
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let id = invitation._id.clone().expect("Inserted invitation had no _id");
+
+        let later = DateTime::from(now + chrono::Duration::days(2));
+        assert_eq!(repo.get_pending_invitation(&hash_key(&token), later).await?, None);
+        let pending = repo.get_pending_invitation(&hash_key(&token), DateTime::from(now)).await?;
+        assert_eq!(pending, Some(invitation));
+
+        assert!(repo.accept_invitation(&id, DateTime::from(now)).await?);
+        assert!(!repo.accept_invitation(&id, DateTime::from(now)).await?);
+        let accepted = repo.get_pending_invitation(&hash_key(&token), DateTime::from(now)).await?;
+        assert_eq!(accepted, None);
+
+        clean_up_db(&db_config()).await
+    }
+
+    #[tokio::test]
+    async fn documents_can_be_streamed_by_user() -> Result<()> {
+        let repo = Repo::new(&db_config())
+            .await
+            .expect("Couldn't connect to mongo, is it running?");
+        let user_id = ObjectId::new();
+        let first = repo
+            .add_list(&List::builder("first".to_string(), user_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        let second = repo
+            .add_list(&List::builder("second".to_string(), user_id.clone()).build())
+            .await?
+            .ok_or(TestError::NoneFromMongo)?;
+        repo.add_list(&List::builder("other".to_string(), ObjectId::new()).build()).await?;
+
+        let lists = repo
+            .stream_documents_by_user::<List>(&user_id, &Collections::Lists)
+            .await?
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(lists, vec![first, second]);
 
         clean_up_db(&db_config()).await
     }
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use mongodb::bson::DateTime;
+use rocket::{error, fairing::AdHoc, info, tokio::time::sleep};
+use serde::Deserialize;
+
+use crate::list::List;
+use crate::maintenance::MaintenanceSwitch;
+use crate::repo::{Repo, RepoError};
+
+/// How often recurring templates are checked, in seconds. 0 turns the
+/// scheduler off, as in tests.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SchedulerConfig {
+    #[serde(default)]
+    pub recurrence_interval_secs: u64,
+}
+
+/// Makes a list from every recurring template that's due. A run that was
+/// missed while the server was down is made once, not once per missed week.
+pub async fn materialize_due_lists(
+    repo: &Repo,
+    now: chrono::DateTime<Utc>,
+) -> Result<Vec<List>, RepoError> {
+    let mut lists = Vec::new();
+    for template in repo.get_due_templates(DateTime::from(now)).await? {
+        let (id, recurrence, scheduled) =
+            match (&template._id, &template.recurrence, &template.next_run_at) {
+                (Some(id), Some(recurrence), Some(scheduled)) => (id, recurrence, scheduled),
+                _ => continue,
+            };
+        let next = match recurrence.next_after(now) {
+            Ok(next) => next,
+            Err(err) => {
+                error!("Skipping template {:?}: {:?}", id, err);
+                continue;
+            }
+        };
+        // Only move the run on once the list is saved, so a failed insert is
+        // retried on the next tick. If another instance got there first, take
+        // this list back out.
+        let list = match repo.add_list(&template.instantiate(None)).await? {
+            Some(list) => list,
+            None => continue,
+        };
+        if !repo.advance_template_run(id, scheduled, DateTime::from(next)).await? {
+            if let Some(list_id) = &list._id {
+                repo.delete_list_by_id(list_id).await?;
+            }
+            continue;
+        }
+        lists.push(list);
+    }
+
+    Ok(lists)
+}
+
+/// Starts checking for due recurring templates once the server is up. No
+/// lists are made while maintenance mode is on.
+pub fn recurring_lists() -> AdHoc {
+    AdHoc::on_liftoff("Recurring lists", |rocket| {
+        Box::pin(async move {
+            let interval = rocket
+                .state::<SchedulerConfig>()
+                .map_or(0, |config| config.recurrence_interval_secs);
+            let repo = match rocket.state::<Repo>() {
+                Some(repo) if interval > 0 => repo.clone(),
+                _ => return,
+            };
+            let maintenance = rocket.state::<MaintenanceSwitch>().cloned().unwrap_or_default();
+
+            rocket::tokio::spawn(async move {
+                loop {
+                    if maintenance.is_on() {
+                        sleep(Duration::from_secs(interval)).await;
+                        continue;
+                    }
+                    match materialize_due_lists(&repo, Utc::now()).await {
+                        Ok(lists) if !lists.is_empty() => {
+                            info!("Made {} recurring list(s)", lists.len())
+                        }
+                        Ok(_) => {}
+                        Err(err) => error!("{:?}", err),
+                    }
+                    sleep(Duration::from_secs(interval)).await;
+                }
+            });
+        })
+    })
+}
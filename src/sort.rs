@@ -0,0 +1,175 @@
+use mongodb::bson::Document;
+use thiserror::Error;
+
+/// A field a collection may be sorted by. Only names `from_param` accepts can
+/// be sorted on, so clients can't sort by unindexed or private fields.
+pub trait SortField: Copy + PartialEq {
+    fn from_param(name: &str) -> Option<Self>;
+    fn field(&self) -> &'static str;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListSort {
+    Name,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl SortField for ListSort {
+    fn from_param(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(ListSort::Name),
+            "created_at" => Some(ListSort::CreatedAt),
+            "updated_at" => Some(ListSort::UpdatedAt),
+            _ => None,
+        }
+    }
+
+    fn field(&self) -> &'static str {
+        match self {
+            ListSort::Name => "name",
+            ListSort::CreatedAt => "createdAt",
+            ListSort::UpdatedAt => "updatedAt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StoreSort {
+    Name,
+}
+
+impl SortField for StoreSort {
+    fn from_param(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(StoreSort::Name),
+            _ => None,
+        }
+    }
+
+    fn field(&self) -> &'static str {
+        match self {
+            StoreSort::Name => "name",
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SortSpecError {
+    #[error("can't sort by: {0}")]
+    UnknownField(String),
+    #[error("sort direction must be asc or desc, not: {0}")]
+    UnknownDirection(String),
+    #[error("field is sorted by more than once: {0}")]
+    Repeated(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey<F> {
+    pub field: F,
+    pub descending: bool,
+}
+
+/// Sort keys in priority order. `descending` is the direction of the `_id`
+/// tie-break when there are no keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortSpec<F> {
+    pub keys: Vec<SortKey<F>>,
+    pub descending: bool,
+}
+
+impl<F> Default for SortSpec<F> {
+    fn default() -> Self {
+        SortSpec { keys: Vec::new(), descending: false }
+    }
+}
+
+impl<F: SortField> SortSpec<F> {
+    /// Parses comma separated `field[:asc|desc]` keys, as in
+    /// `updated_at:desc,name`. Keys without a direction use `descending`.
+    pub fn parse(spec: Option<&str>, descending: bool) -> Result<Self, SortSpecError> {
+        let mut keys: Vec<SortKey<F>> = Vec::new();
+        for key in spec.into_iter().flat_map(|spec| spec.split(',')) {
+            let (name, direction) = match key.find(':') {
+                Some(colon) => (key[..colon].trim(), Some(key[colon + 1..].trim())),
+                None => (key.trim(), None),
+            };
+            let field = F::from_param(name).ok_or_else(|| SortSpecError::UnknownField(name.to_owned()))?;
+            let descending = match direction {
+                None => descending,
+                Some("asc") => false,
+                Some("desc") => true,
+                Some(direction) => return Err(SortSpecError::UnknownDirection(direction.to_owned())),
+            };
+            if keys.iter().any(|key| key.field == field) {
+                return Err(SortSpecError::Repeated(name.to_owned()));
+            }
+            keys.push(SortKey { field, descending });
+        }
+
+        Ok(SortSpec { keys, descending })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The mongo sort document, ending with `_id` so ties keep a stable order.
+    pub fn to_document(&self) -> Document {
+        let mut sort = Document::new();
+        for key in self.keys.iter() {
+            sort.insert(key.field.field(), if key.descending { -1 } else { 1 });
+        }
+        let descending = self.keys.first().map_or(self.descending, |key| key.descending);
+        sort.insert("_id", if descending { -1 } else { 1 });
+        sort
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn compound_sorts_are_parsed_in_order() {
+        let spec = SortSpec::<ListSort>::parse(Some("updated_at:desc, name"), false).unwrap();
+        assert_eq!(
+            spec.keys,
+            vec![
+                SortKey { field: ListSort::UpdatedAt, descending: true },
+                SortKey { field: ListSort::Name, descending: false },
+            ]
+        );
+        assert_eq!(spec.to_document(), doc! { "updatedAt": -1, "name": 1, "_id": -1 });
+    }
+
+    #[test]
+    fn keys_without_a_direction_use_the_default() {
+        let spec = SortSpec::<ListSort>::parse(Some("name,created_at:asc"), true).unwrap();
+        assert_eq!(spec.to_document(), doc! { "name": -1, "createdAt": 1, "_id": -1 });
+    }
+
+    #[test]
+    fn empty_specs_sort_by_id() {
+        let spec = SortSpec::<StoreSort>::parse(None, true).unwrap();
+        assert!(spec.is_empty());
+        assert_eq!(spec.to_document(), doc! { "_id": -1 });
+    }
+
+    #[test]
+    fn only_allowed_fields_can_be_sorted_on() {
+        assert_eq!(
+            SortSpec::<StoreSort>::parse(Some("categories"), false),
+            Err(SortSpecError::UnknownField("categories".to_string()))
+        );
+        assert_eq!(
+            SortSpec::<ListSort>::parse(Some("name:up"), false),
+            Err(SortSpecError::UnknownDirection("up".to_string()))
+        );
+        assert_eq!(
+            SortSpec::<ListSort>::parse(Some("name,name:desc"), false),
+            Err(SortSpecError::Repeated("name".to_string()))
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use rocket::{error, get, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+
+use crate::repo::Repo;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Health {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Component {
+    pub name: String,
+    pub status: Health,
+    /// RFC 3339 time of the last successful check, if there has been one.
+    #[serde(rename(serialize = "lastSuccess", deserialize = "lastSuccess"))]
+    pub last_success: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatusReport {
+    pub components: Vec<Component>,
+}
+
+/// Remembers when Mongo last answered so a status page can show how long an
+/// outage has lasted.
+#[derive(Debug, Default)]
+pub struct StatusTracker {
+    mongo_last_success: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl StatusTracker {
+    fn record_mongo(&self, up: bool) -> Option<DateTime<Utc>> {
+        let mut last_success = self.mongo_last_success.lock().unwrap_or_else(|err| err.into_inner());
+        if up {
+            *last_success = Some(Utc::now());
+        }
+        *last_success
+    }
+}
+
+fn component(name: &str, up: bool, last_success: Option<DateTime<Utc>>) -> Component {
+    Component {
+        name: name.to_owned(),
+        status: if up { Health::Up } else { Health::Down },
+        last_success: last_success.map(|time| time.to_rfc3339()),
+    }
+}
+
+/// Health of each piece the API depends on. Always answers 200 so the page can
+/// render even when a component is down.
+#[get("/status")]
+pub async fn get_status(repo: &State<Repo>, tracker: &State<StatusTracker>) -> Json<StatusReport> {
+    let mongo_up = match repo.ping().await {
+        Ok(()) => true,
+        Err(err) => {
+            error!("Mongo status check failed: {:?}", err);
+            false
+        }
+    };
+    let mongo_last_success = tracker.record_mongo(mongo_up);
+
+    Json(StatusReport {
+        components: vec![
+            component("api", true, Some(Utc::now())),
+            component("mongo", mongo_up, mongo_last_success),
+        ],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn last_success_survives_failed_checks() {
+        let tracker = StatusTracker::default();
+        assert_eq!(tracker.record_mongo(false), None);
+
+        let success = tracker.record_mongo(true);
+        assert!(success.is_some());
+        assert_eq!(tracker.record_mongo(false), success);
+    }
+}
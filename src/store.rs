@@ -1,5 +1,16 @@
 use serde::{Serialize, Deserialize};
 use mongodb::bson::oid::ObjectId;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CategoryOrderError {
+    #[error("category appears more than once: {0}")]
+    Duplicate(String),
+    #[error("category is not in the store: {0}")]
+    Unknown(String),
+    #[error("category is missing from the new order: {0}")]
+    Missing(String),
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Store {
@@ -7,6 +18,21 @@ pub struct Store {
     pub _id: Option<ObjectId>,
     pub name: String,
     pub categories: Vec<String>,
+    /// Who made the store. Stores without one are shared layouts that anyone
+    /// can use but nobody can change.
+    #[serde(
+        rename(serialize = "userId", deserialize = "userId"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub user_id: Option<ObjectId>,
+    /// Every member of the household can use and change the store.
+    #[serde(
+        rename(serialize = "householdId", deserialize = "householdId"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub household_id: Option<ObjectId>,
 }
 
 impl Store {
@@ -15,12 +41,60 @@ impl Store {
             _id: None,
             name: name.to_owned(),
             categories: Vec::new(),
+            user_id: None,
+            household_id: None,
+        }
+    }
+
+    pub fn owned_by(mut self, user_id: ObjectId) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn is_owned_by(&self, user_id: &ObjectId) -> bool {
+        self.user_id.as_ref() == Some(user_id)
+    }
+
+    /// Users see their own stores and the shared ones.
+    pub fn is_visible_to(&self, user_id: &ObjectId) -> bool {
+        self.user_id.is_none() || self.is_owned_by(user_id)
+    }
+
+    /// Builds a store from an ordered layout, rejecting repeated categories.
+    pub fn with_categories(name: &str, categories: &[String]) -> Result<Self, CategoryOrderError> {
+        let mut store = Store::new(name);
+        for category in categories.iter() {
+            if store.categories.contains(&category.to_lowercase()) {
+                return Err(CategoryOrderError::Duplicate(category.to_lowercase()));
+            }
+            store.add_category(category);
         }
+        Ok(store)
     }
 
     pub fn add_category(&mut self, category: &str) {
         self.categories.push(category.to_lowercase());
     }
+
+    /// Checks that `ordered` contains exactly this store's categories, returning
+    /// them lowercased in the new order.
+    pub fn reordered_categories(&self, ordered: &[String]) -> Result<Vec<String>, CategoryOrderError> {
+        let mut categories: Vec<String> = Vec::new();
+        for category in ordered.iter().map(|category| category.to_lowercase()) {
+            if categories.contains(&category) {
+                return Err(CategoryOrderError::Duplicate(category));
+            }
+            if !self.categories.contains(&category) {
+                return Err(CategoryOrderError::Unknown(category));
+            }
+            categories.push(category);
+        }
+        if let Some(missing) = self.categories.iter().find(|category| !categories.contains(category)) {
+            return Err(CategoryOrderError::Missing(missing.to_owned()));
+        }
+
+        Ok(categories)
+    }
 }
 
 #[cfg(test)]
@@ -36,4 +110,64 @@ mod test {
 
         assert_eq!(store.categories, vec!["meat", "produce"]);
     }
+
+    #[test]
+    fn categories_can_be_reordered() {
+        let mut store = Store::new("test_store");
+        store.add_category("meat");
+        store.add_category("produce");
+        store.add_category("dairy");
+
+        let reordered = store.reordered_categories(&[
+            "Produce".to_string(),
+            "dairy".to_string(),
+            "meat".to_string(),
+        ]);
+
+        assert_eq!(reordered, Ok(vec!["produce".to_string(), "dairy".to_string(), "meat".to_string()]));
+    }
+
+    #[test]
+    fn reordering_rejects_duplicate_unknown_and_missing_categories() {
+        let mut store = Store::new("test_store");
+        store.add_category("meat");
+        store.add_category("produce");
+
+        assert_eq!(
+            store.reordered_categories(&["meat".to_string(), "MEAT".to_string()]),
+            Err(CategoryOrderError::Duplicate("meat".to_string()))
+        );
+        assert_eq!(
+            store.reordered_categories(&["meat".to_string(), "bakery".to_string()]),
+            Err(CategoryOrderError::Unknown("bakery".to_string()))
+        );
+        assert_eq!(
+            store.reordered_categories(&["meat".to_string()]),
+            Err(CategoryOrderError::Missing("produce".to_string()))
+        );
+    }
+
+    #[test]
+    fn layouts_with_repeated_categories_are_rejected() {
+        let store = Store::with_categories("test_store", &["Meat".to_string(), "produce".to_string()]);
+        assert_eq!(store.map(|store| store.categories), Ok(vec!["meat".to_string(), "produce".to_string()]));
+
+        let store = Store::with_categories("test_store", &["meat".to_string(), "MEAT".to_string()]);
+        assert_eq!(store, Err(CategoryOrderError::Duplicate("meat".to_string())));
+    }
+
+    #[test]
+    fn shared_stores_are_visible_to_everyone_but_owned_by_no_one() {
+        let owner = ObjectId::new();
+        let other = ObjectId::new();
+        let shared = Store::new("shared_store");
+        let owned = Store::new("owned_store").owned_by(owner.clone());
+
+        assert!(shared.is_visible_to(&other));
+        assert!(!shared.is_owned_by(&other));
+        assert!(owned.is_visible_to(&owner));
+        assert!(owned.is_owned_by(&owner));
+        assert!(!owned.is_visible_to(&other));
+        assert!(!owned.is_owned_by(&other));
+    }
 }
@@ -0,0 +1,212 @@
+use rocket::{State, delete, get, http::Status, post, put, serde::{json::Json}};
+use serde::{Serialize, Deserialize};
+use rocket::error;
+
+use crate::access::StoreAccess;
+use crate::auth::AuthenticatedUser;
+use crate::handler_util::{parse_object_id, parse_order, parse_page, repo_error_status};
+use crate::repo::Repo;
+use crate::sort::{SortSpec, StoreSort};
+use crate::store::Store;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NewStore {
+    pub name: String,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+/// The new store belongs to the caller; only they can see or change it.
+#[post("/stores", data="<store>")]
+pub async fn create_store(
+    auth: AuthenticatedUser,
+    store: Json<NewStore>,
+    repo: &State<Repo>,
+) -> Result<Json<Store>, Status> {
+    if store.name.trim().is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let mut new_store = Store::new(store.name.trim()).owned_by(auth.id);
+    for category in store.categories.iter() {
+        new_store.add_category(category);
+    }
+
+    let new_store = repo.add_store(&new_store)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new store returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(new_store))
+}
+
+#[get("/stores/<id>")]
+pub async fn get_store(
+    _access: StoreAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Store>, Status> {
+    let id = parse_object_id(id)?;
+    let store = repo.get_store_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(store))
+}
+
+/// The caller's stores and the shared ones, by name by default. `sort` and
+/// `order` work as they do for lists, and the only sortable field is `name`.
+/// The `after` cursor follows the default order, so it can't be combined with
+/// `sort`.
+#[get("/stores?<limit>&<after>&<sort>&<order>")]
+pub async fn get_stores(
+    auth: AuthenticatedUser,
+    limit: Option<i64>,
+    after: Option<&str>,
+    sort: Option<&str>,
+    order: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<Store>>, Status> {
+    let page = parse_page(limit, after)?;
+    let sort = SortSpec::<StoreSort>::parse(sort, parse_order(order)?).map_err(|err| {
+        error!("{:?}", err);
+        Status::BadRequest
+    })?;
+    if !sort.is_empty() && page.after.is_some() {
+        return Err(Status::BadRequest);
+    }
+    let stores = repo.get_all_stores(&auth.id, &page, &sort)
+        .await
+        .map_err(repo_error_status)?;
+
+    Ok(Json(stores))
+}
+
+#[delete("/stores/<id>")]
+pub async fn delete_store(
+    _access: StoreAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let stores_deleted = repo.delete_store_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if stores_deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
+
+#[put("/stores/<id>/categories", data="<categories>")]
+pub async fn reorder_store_categories(
+    _access: StoreAccess,
+    id: &str,
+    categories: Json<Vec<String>>,
+    repo: &State<Repo>,
+) -> Result<Json<Store>, Status> {
+    let id = parse_object_id(id)?;
+    let store = repo.get_store_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let reordered = store.reordered_categories(&categories).map_err(|err| {
+        error!("{}", err);
+        Status::BadRequest
+    })?;
+
+    let store = repo.replace_store_categories(&id, &store.categories, &reordered)
+        .await
+        .map_err(repo_error_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(store))
+}
+
+/// Adds one category to the end of the store's layout; adding a category the
+/// store already has leaves it unchanged.
+#[post("/stores/<id>/categories", data="<category>")]
+pub async fn add_store_category(
+    _access: StoreAccess,
+    id: &str,
+    category: Json<String>,
+    repo: &State<Repo>,
+) -> Result<Json<Store>, Status> {
+    let id = parse_object_id(id)?;
+    let category = category.trim();
+    if category.is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let store = repo.add_store_category(&id, category)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(store))
+}
+
+/// Upserts each layout by name among the caller's stores; nothing is written
+/// unless every layout is valid.
+#[post("/stores/import", data="<layouts>")]
+pub async fn import_store_layouts(
+    auth: AuthenticatedUser,
+    layouts: Json<Vec<NewStore>>,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<Store>>, Status> {
+    let mut stores: Vec<Store> = Vec::new();
+    for layout in layouts.iter() {
+        let name = layout.name.trim();
+        if name.is_empty() {
+            error!("Store layout without a name");
+            return Err(Status::BadRequest);
+        }
+        if stores.iter().any(|store| store.name == name) {
+            error!("Store layout for {} appears more than once", name);
+            return Err(Status::BadRequest);
+        }
+        let store = Store::with_categories(name, &layout.categories).map_err(|err| {
+            error!("Invalid layout for {}: {}", name, err);
+            Status::BadRequest
+        })?;
+        stores.push(store);
+    }
+
+    let mut imported = Vec::new();
+    for store in stores.iter() {
+        let store = repo.upsert_store_by_name(&auth.id, store)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                Status::InternalServerError
+            })?
+            .ok_or_else(|| {
+                error!("No store returned after upsert");
+                Status::InternalServerError
+            })?;
+        imported.push(store);
+    }
+
+    Ok(Json(imported))
+}
@@ -0,0 +1,80 @@
+use serde::{Serialize, Deserialize};
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+use crate::list::{List, ListItem};
+use crate::recurrence::Recurrence;
+
+/// A reusable set of items, like "weekly staples", that new lists can be
+/// started from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Template {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub name: String,
+    #[serde(rename(serialize = "userId", deserialize = "userId"))]
+    pub user_id: ObjectId,
+    pub items: Vec<ListItem>,
+    /// When set, a list is made from the template on this schedule.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub recurrence: Option<Recurrence>,
+    #[serde(
+        rename(serialize = "nextRunAt", deserialize = "nextRunAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub next_run_at: Option<DateTime>,
+}
+
+impl Template {
+    /// Saves the list's items as they'd be on a new list: unchecked and
+    /// without need-by dates.
+    pub fn from_list(list: &List, name: Option<&str>) -> Self {
+        Template {
+            _id: None,
+            name: name.map(str::to_owned).unwrap_or_else(|| list.name.clone()),
+            user_id: list.user_id.clone(),
+            items: list.items.iter().map(ListItem::fresh_copy).collect(),
+            recurrence: None,
+            next_run_at: None,
+        }
+    }
+
+    /// A new, unsaved list with the template's items. Items get new ids each
+    /// time so lists made from the same template don't share them.
+    pub fn instantiate(&self, name: Option<&str>) -> List {
+        let mut builder = List::builder(
+            name.map(str::to_owned).unwrap_or_else(|| self.name.clone()),
+            self.user_id.clone(),
+        );
+        for item in self.items.iter() {
+            builder.add_item(item.fresh_copy());
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn templates_keep_items_but_not_their_state() {
+        let list = List::builder("groceries".to_string(), ObjectId::new())
+            .add_item(ListItem::builder("milk").category("dairy").checked(true).build())
+            .build();
+
+        let template = Template::from_list(&list, Some("weekly staples"));
+        assert_eq!(template.name, "weekly staples");
+        assert_eq!(template.user_id, list.user_id);
+        assert_eq!(template.items.len(), 1);
+        assert_eq!(template.items[0].category(), Some("dairy"));
+        assert!(!template.items[0].is_checked());
+
+        let first = template.instantiate(None);
+        let second = template.instantiate(Some("this week"));
+        assert_eq!(first.name, "weekly staples");
+        assert_eq!(second.name, "this week");
+        assert_eq!(first.items[0].name(), "milk");
+        assert_ne!(first.items[0].id(), second.items[0].id());
+    }
+}
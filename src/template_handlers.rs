@@ -0,0 +1,175 @@
+use chrono::Utc;
+use mongodb::bson::DateTime;
+use rocket::{State, delete, get, http::Status, post, put, serde::{json::Json}};
+use rocket::error;
+
+use crate::access::{ListAccess, TemplateAccess, UserAccess};
+use crate::handler_util::parse_object_id;
+use crate::list::List;
+use crate::recurrence::Recurrence;
+use crate::repo::Repo;
+use crate::template::Template;
+
+/// Saves the list's items as a template named `name`, or after the list. The
+/// template belongs to the caller, even for a list shared with them.
+#[post("/lists/<id>/template?<name>")]
+pub async fn save_list_as_template(
+    access: ListAccess,
+    id: &str,
+    name: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<Json<Template>, Status> {
+    let id = parse_object_id(id)?;
+    let list = repo.get_list_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let mut template = Template::from_list(&list, name);
+    template.user_id = access.user.id;
+    let template = repo.add_template(&template)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new template returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(template))
+}
+
+#[get("/templates/<id>")]
+pub async fn get_template(
+    _access: TemplateAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Template>, Status> {
+    let id = parse_object_id(id)?;
+    let template = repo.get_template_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(template))
+}
+
+#[get("/users/<id>/templates")]
+pub async fn get_user_templates(
+    _access: UserAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<Template>>, Status> {
+    let id = parse_object_id(id)?;
+    let templates = repo.get_templates_by_user(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(templates))
+}
+
+/// Starts a new list from the template, named `name` or after the template.
+#[post("/templates/<id>/lists?<name>")]
+pub async fn create_list_from_template(
+    _access: TemplateAccess,
+    id: &str,
+    name: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<Json<List>, Status> {
+    let id = parse_object_id(id)?;
+    let template = repo.get_template_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    let new_list = repo.add_list(&template.instantiate(name))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or_else(|| {
+            error!("No new list returned");
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(new_list))
+}
+
+#[delete("/templates/<id>")]
+pub async fn delete_template(
+    _access: TemplateAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Status, Status> {
+    let id = parse_object_id(id)?;
+    let deleted = repo.delete_template_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+    if deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Status::NoContent)
+}
+
+/// Makes a list from the template every week, starting with the next
+/// scheduled time. An unknown time zone or an hour past 23 is a 400.
+#[put("/templates/<id>/recurrence", data="<recurrence>")]
+pub async fn set_template_recurrence(
+    _access: TemplateAccess,
+    id: &str,
+    recurrence: Json<Recurrence>,
+    repo: &State<Repo>,
+) -> Result<Json<Template>, Status> {
+    let id = parse_object_id(id)?;
+    let next_run_at = recurrence.next_after(Utc::now()).map_err(|err| {
+        error!("{:?}", err);
+        Status::BadRequest
+    })?;
+    let schedule = (&*recurrence, DateTime::from(next_run_at));
+    let template = repo.set_template_recurrence(&id, Some(schedule))
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(template))
+}
+
+#[delete("/templates/<id>/recurrence")]
+pub async fn clear_template_recurrence(
+    _access: TemplateAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<Template>, Status> {
+    let id = parse_object_id(id)?;
+    let template = repo.set_template_recurrence(&id, None)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(template))
+}
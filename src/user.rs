@@ -1,5 +1,5 @@
 use serde::{Serialize, Deserialize};
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{oid::ObjectId, DateTime};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[non_exhaustive]
@@ -8,6 +8,70 @@ pub struct User {
     pub _id: Option<ObjectId>,
     pub name: String,
     pub email: String,
+    /// An argon2 hash in PHC string format. Users made before accounts had
+    /// passwords have none and can't log in.
+    #[serde(
+        rename(serialize = "passwordHash", deserialize = "passwordHash"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub password_hash: Option<String>,
+    /// Bumped when the password is reset. Tokens and sessions carry the
+    /// version they were issued at and stop working once it changes.
+    #[serde(
+        rename(serialize = "credentialsVersion", deserialize = "credentialsVersion"),
+        default
+    )]
+    pub credentials_version: i64,
+    #[serde(
+        rename(serialize = "passwordReset", deserialize = "passwordReset"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub password_reset: Option<PasswordReset>,
+    #[serde(default)]
+    pub role: Role,
+    /// Set while an admin has the account disabled. Disabled users can't log
+    /// in and none of their tokens, sessions or API keys work.
+    #[serde(
+        rename(serialize = "disabledAt", deserialize = "disabledAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub disabled_at: Option<DateTime>,
+    /// Set when the user deleted their account and it was anonymized rather
+    /// than removed, so lists they shared still have an owner.
+    #[serde(
+        rename(serialize = "deletedAt", deserialize = "deletedAt"),
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub deleted_at: Option<DateTime>,
+}
+
+/// Admins can use the `/admin` routes. There's no route that makes someone
+/// an admin; it's set on their user document directly.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::User
+    }
+}
+
+/// An outstanding request to reset the user's password. Only a hash of the
+/// token that was mailed to them is kept.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PasswordReset {
+    #[serde(rename(serialize = "tokenHash", deserialize = "tokenHash"))]
+    pub token_hash: String,
+    #[serde(rename(serialize = "expiresAt", deserialize = "expiresAt"))]
+    pub expires_at: DateTime,
 }
 
 impl User {
@@ -16,8 +80,30 @@ impl User {
             _id: None,
             name,
             email,
+            password_hash: None,
+            credentials_version: 0,
+            password_reset: None,
+            role: Role::User,
+            disabled_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn with_password(name: String, email: String, password_hash: String) -> Self {
+        User {
+            password_hash: Some(password_hash),
+            ..User::new(name, email)
         }
     }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// Deleted accounts count as disabled, even if an admin enables them.
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some() || self.deleted_at.is_some()
+    }
 }
 
 #[cfg(test)]
@@ -33,5 +119,19 @@ mod test {
         assert_eq!(user.name, name);
         assert_eq!(user.email, email);
         assert_eq!(user._id, None);
+        assert_eq!(user.password_hash, None);
+    }
+
+    #[test]
+    fn users_without_a_role_are_enabled_ordinary_users() {
+        let user: User = mongodb::bson::from_document(mongodb::bson::doc! {
+            "name": "foo",
+            "email": "foo@bar.com",
+        })
+        .unwrap();
+
+        assert_eq!(user.role, Role::User);
+        assert!(!user.is_admin());
+        assert!(!user.is_disabled());
     }
 }
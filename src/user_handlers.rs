@@ -1,9 +1,14 @@
-use rocket::{State, http::Status, post, serde::{json::Json}};
+use rocket::{State, get, http::Status, post, put, serde::{json::Json}};
 use serde::{Serialize, Deserialize};
 use mongodb::bson::oid::ObjectId;
 use rocket::error;
 
-use crate::repo::{Repo, RepoError};
+use crate::account_export::AccountArchive;
+use crate::access::{access_status, check_user, AdminAccess, UserAccess};
+use crate::auth::{normalize_email, AuthenticatedUser};
+use crate::handler_util::{parse_object_id, parse_page, repo_error_status, Count};
+use crate::provisioning::{provision_user, SignupConfig};
+use crate::repo::{Repo, RepoError, UserStats};
 use crate::user::User as RepoUser;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -25,20 +30,150 @@ impl User {
     }
 }
 
+/// Makes an account without a password, for admins; the user sets one with
+/// a password reset. Everyone else registers. An email that's already
+/// registered is a 409.
 #[post("/users", data="<user>")]
-pub async fn create_user(user: Json<User>, repo: &State<Repo>) -> Result<Json<User>, Status> {
-    let new_user = repo.add_user(&RepoUser::new(
-        user.name.to_owned(),
-        user.email.to_owned()))
+pub async fn create_user(
+    _admin: AdminAccess,
+    user: Json<User>,
+    repo: &State<Repo>,
+    signup: &State<SignupConfig>,
+) -> Result<Json<User>, Status> {
+    let email = normalize_email(&user.email);
+    if user.name.trim().is_empty() || email.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let new_user = repo.add_user(&RepoUser::new(user.name.trim().to_owned(), email))
+        .await
+        .map_err(repo_error_status)?
+        .ok_or_else(|| {
+            error!("No new user returned");
+            Status::InternalServerError
+        })?;
+
+    if let Some(user_id) = &new_user._id {
+        if let Err(err) = provision_user(repo, signup, user_id).await {
+            error!("Could not provision new user {:?}: {:?}", user_id, err);
+        }
+    }
+
+    Ok(Json(User::new(new_user._id, new_user.name, new_user.email)))
+}
+
+/// An email someone else already has is a 409.
+#[put("/users/<id>", data="<user>")]
+pub async fn update_user(
+    _access: UserAccess,
+    id: &str,
+    user: Json<User>,
+    repo: &State<Repo>,
+) -> Result<Json<User>, Status> {
+    let id = parse_object_id(id)?;
+    let email = normalize_email(&user.email);
+    if user.name.trim().is_empty() || email.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let updated_user = repo.update_user(&id, &RepoUser::new(user.name.trim().to_owned(), email))
+        .await
+        .map_err(repo_error_status)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(User::new(updated_user._id, updated_user.name, updated_user.email)))
+}
+
+/// Users can only look themselves up; anyone else's email is a 403.
+#[get("/users?<email>", rank = 1)]
+pub async fn get_user_by_email(
+    auth: AuthenticatedUser,
+    email: &str,
+    repo: &State<Repo>,
+) -> Result<Json<User>, Status> {
+    let user = repo.get_user_by_email(email)
         .await
         .map_err(|err| {
             error!("{:?}", err);
             Status::InternalServerError
         })?
-        .ok_or_else(|| {
-            error!("No new user returned");
+        .ok_or(Status::NotFound)?;
+    check_user(&auth, user._id.as_ref().ok_or(Status::NotFound)?).map_err(access_status)?;
+
+    Ok(Json(User::new(user._id, user.name, user.email)))
+}
+
+/// The whole user directory, for admins only.
+#[get("/users?<limit>&<after>", rank = 2)]
+pub async fn get_users(
+    _admin: AdminAccess,
+    limit: Option<i64>,
+    after: Option<&str>,
+    repo: &State<Repo>,
+) -> Result<Json<Vec<User>>, Status> {
+    let page = parse_page(limit, after)?;
+    let users = repo.get_users(&page)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .into_iter()
+        .map(|user| User::new(user._id, user.name, user.email))
+        .collect();
+
+    Ok(Json(users))
+}
+
+#[get("/users/count")]
+pub async fn count_users(
+    _admin: AdminAccess,
+    repo: &State<Repo>,
+) -> Result<Json<Count>, Status> {
+    let count = repo.count_users()
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
             Status::InternalServerError
         })?;
 
-    Ok(Json(User::new(new_user._id, new_user.name, new_user.email)))
-}
\ No newline at end of file
+    Ok(Json(Count { count }))
+}
+
+#[get("/users/<id>/stats")]
+pub async fn get_user_stats(
+    _access: UserAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<Json<UserStats>, Status> {
+    let id = parse_object_id(id)?;
+    let stats = repo.get_user_stats(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(stats))
+}
+
+/// Downloads everything stored for the user as one JSON file, streamed out as
+/// it's read rather than gathered up first.
+#[get("/users/<id>/export")]
+pub async fn export_user(
+    _access: UserAccess,
+    id: &str,
+    repo: &State<Repo>,
+) -> Result<AccountArchive, Status> {
+    let id = parse_object_id(id)?;
+    let user = repo.get_user_by_id(&id)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+
+    AccountArchive::build(repo, user).await.map_err(|err| {
+        error!("{:?}", err);
+        Status::InternalServerError
+    })
+}